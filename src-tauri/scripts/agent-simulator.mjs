@@ -1,9 +1,13 @@
+import {createInterface} from 'node:readline';
+
 const prompt =
 	process.argv[2] ?? 'Write me a new hello world script using typescript';
 const cwd = process.argv[3] ?? process.cwd();
-const sessionId = '29c7275b-dbcc-4a5f-aaff-05b6c93cb038';
+const sessionId = process.argv[4] ?? '29c7275b-dbcc-4a5f-aaff-05b6c93cb038';
 const requestId = '9bbaedf1-917d-4671-a362-4805c54171ba';
 const modelCallId = `${requestId}-0-odvi`;
+const callId = 'toolu_01PPh4fR7b2zfbx8D58VvVz7';
+const requireApproval = process.env.AGENT_SIMULATOR_REQUIRE_APPROVAL === '1';
 
 const sleep = milliseconds =>
 	new Promise(resolve => {
@@ -67,7 +71,7 @@ const stream = [
 	{
 		type: 'tool_call',
 		subtype: 'started',
-		call_id: 'toolu_01PPh4fR7b2zfbx8D58VvVz7',
+		call_id: callId,
 		tool_call: {
 			editToolCall: {
 				args: {
@@ -83,7 +87,7 @@ const stream = [
 	{
 		type: 'tool_call',
 		subtype: 'completed',
-		call_id: 'toolu_01PPh4fR7b2zfbx8D58VvVz7',
+		call_id: callId,
 		tool_call: {
 			editToolCall: {
 				args: {
@@ -132,7 +136,76 @@ const stream = [
 	},
 ];
 
+const deniedStream = reason => {
+	const text = `I did not create \`hello.ts\` because the edit was denied${
+		reason ? `: ${reason}` : '.'
+	}`;
+	return [
+		{
+			type: 'assistant',
+			message: {role: 'assistant', content: [{type: 'text', text}]},
+			session_id: sessionId,
+		},
+		{
+			type: 'result',
+			subtype: 'success',
+			duration_ms: 20656,
+			duration_api_ms: 20656,
+			is_error: false,
+			result: text,
+			session_id: sessionId,
+			request_id: requestId,
+		},
+	];
+};
+
+// Resolves with the `approval_response` for the tool call read from stdin.
+const waitForApproval = async () => {
+	const lines = createInterface({input: process.stdin});
+	try {
+		for await (const line of lines) {
+			try {
+				const response = JSON.parse(line);
+				if (
+					response.type === 'approval_response' &&
+					response.call_id === callId
+				) {
+					return response;
+				}
+			} catch {
+				// Not a JSON line, keep waiting.
+			}
+		}
+	} finally {
+		lines.close();
+	}
+	return {decision: 'denied', reason: 'stdin was closed'};
+};
+
 for (const payload of stream) {
+	if (
+		requireApproval &&
+		payload.type === 'tool_call' &&
+		payload.subtype === 'started'
+	) {
+		console.log(
+			JSON.stringify({
+				type: 'approval_request',
+				call_id: callId,
+				tool_call: payload.tool_call,
+				session_id: sessionId,
+				timestamp_ms: Date.now(),
+			}),
+		);
+		const response = await waitForApproval();
+		if (response.decision !== 'approved') {
+			for (const deniedPayload of deniedStream(response.reason)) {
+				console.log(JSON.stringify(deniedPayload));
+				await sleep(300);
+			}
+			break;
+		}
+	}
 	console.log(JSON.stringify(payload));
 	await sleep(300);
 }
@@ -0,0 +1,509 @@
+use crate::commands::emit_runtime_event;
+use crate::host::Host;
+use crate::host_api::web_access_url;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rand::distr::Alphanumeric;
+use rand::Rng;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const ACCESS_TOKEN_PREFIX: &str = "sym_";
+const ACCESS_TOKEN_COLUMNS: &str = "id, name, kind, scopes, created_at, expires_at, last_used_at";
+/// Sent when a token is revoked, so open event streams can drop it right away.
+pub(crate) const ACCESS_TOKEN_REVOKED_EVENT: &str = "access-token-revoked";
+
+/// What an access token may do over the host bridge. Every scope can read.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessScope {
+    /// View repos, agents and runs, and receive events.
+    Read,
+    /// Create, run, stop and approve agents, and manage their worktrees.
+    RunAgents,
+    /// Add, remove, pull and change repos, branches and groups.
+    ManageRepos,
+    /// Everything, including host settings and access tokens.
+    Admin,
+}
+
+impl AccessScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AccessScope::Read => "read",
+            AccessScope::RunAgents => "run_agents",
+            AccessScope::ManageRepos => "manage_repos",
+            AccessScope::Admin => "admin",
+        }
+    }
+
+    fn from_db(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(AccessScope::Read),
+            "run_agents" => Some(AccessScope::RunAgents),
+            "manage_repos" => Some(AccessScope::ManageRepos),
+            "admin" => Some(AccessScope::Admin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessTokenKind {
+    /// Created by hand and shared as a token or link.
+    Token,
+    /// Issued to a device that paired with the host.
+    Device,
+}
+
+impl AccessTokenKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AccessTokenKind::Token => "token",
+            AccessTokenKind::Device => "device",
+        }
+    }
+
+    fn from_db(value: &str) -> Self {
+        match value {
+            "device" => AccessTokenKind::Device,
+            _ => AccessTokenKind::Token,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessToken {
+    pub id: i64,
+    pub name: String,
+    pub kind: AccessTokenKind,
+    pub scopes: Vec<AccessScope>,
+    pub created_at: String,
+    /// RFC 3339 times in UTC; the token never expires when `None`.
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+/// A new token with its secret, which is only shown once.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedAccessToken {
+    pub token: AccessToken,
+    pub secret: String,
+    /// Opens the web UI signed in with the token.
+    pub access_url: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessTokenRevokedPayload {
+    pub token_id: i64,
+}
+
+/// The scopes a bridge client was authenticated with.
+#[derive(Debug, Clone)]
+pub struct AccessGrant {
+    scopes: Vec<AccessScope>,
+}
+
+impl AccessGrant {
+    /// The host's own token, which may do everything.
+    pub fn admin() -> Self {
+        Self {
+            scopes: vec![AccessScope::Admin],
+        }
+    }
+
+    pub fn scopes(&self) -> &[AccessScope] {
+        &self.scopes
+    }
+
+    pub fn allows(&self, scope: AccessScope) -> bool {
+        scope == AccessScope::Read
+            || self.scopes.contains(&AccessScope::Admin)
+            || self.scopes.contains(&scope)
+    }
+}
+
+/// The scope a bridge command needs. Commands not listed here need `admin`.
+pub fn command_scope(command_name: &str, args: Option<&Value>) -> AccessScope {
+    match command_name {
+        "list_repos"
+        | "get_remote_url"
+        | "get_current_branch"
+        | "list_local_branches"
+        | "get_repo_working_tree_status"
+        | "get_repo_sync_status"
+        | "list_git_history"
+        | "get_commit_changes"
+        | "list_working_tree_changes"
+        | "get_working_tree_file_diff"
+        | "list_agents"
+        | "list_agent_models"
+        | "list_agent_queue"
+        | "list_scheduled_runs"
+        | "get_scheduler_settings"
+        | "list_agent_schedules"
+        | "list_agent_runs"
+        | "get_agent_run_transcript"
+        | "get_agent_run_changes"
+        | "list_agent_policy_violations"
+        | "get_agent_stats"
+        | "get_repo_stats"
+        | "get_group_stats"
+        | "get_agent_batch"
+        | "list_agent_batches"
+        | "list_agent_checkpoints"
+        | "list_groups"
+        | "get_host_access_settings" => AccessScope::Read,
+        // A custom backend command runs arbitrary programs on the host.
+        "create_agent"
+            if args
+                .and_then(|args| args.get("backendCommand"))
+                .is_some_and(|command| !command.is_null()) =>
+        {
+            AccessScope::Admin
+        }
+        "create_agent"
+        | "delete_agent"
+        | "rename_agent"
+        | "set_agent_run_limits"
+        | "reset_agent_session"
+        | "run_repo_agent"
+        | "stop_repo_agent"
+        | "send_agent_input"
+        | "approve_tool_call"
+        | "deny_tool_call"
+        | "reorder_agent_queue"
+        | "cancel_queued_prompt"
+        | "create_agent_schedule"
+        | "set_agent_schedule_enabled"
+        | "delete_agent_schedule"
+        | "run_group_agents"
+        | "delete_agent_batch"
+        | "revert_agent_run"
+        | "set_agent_checkpoints"
+        | "restore_agent_checkpoint"
+        | "delete_agent_checkpoint"
+        | "create_agent_worktree"
+        | "rebase_agent_worktree"
+        | "discard_agent_worktree" => AccessScope::RunAgents,
+        "add_repo"
+        | "clone_repo"
+        | "remove_repo"
+        | "open_in_cursor"
+        | "open_in_file_manager"
+        | "switch_branch"
+        | "create_local_branch"
+        | "delete_local_branch"
+        | "pull_repo"
+        | "commit_working_tree"
+        | "merge_agent_worktree"
+        | "cleanup_stale_worktrees"
+        | "create_group"
+        | "rename_group"
+        | "delete_group"
+        | "move_repo_to_group" => AccessScope::ManageRepos,
+        _ => AccessScope::Admin,
+    }
+}
+
+/// Whether the secret looks like a scoped token, as opposed to the host's own.
+pub(crate) fn is_scoped_access_token(secret: &str) -> bool {
+    secret.starts_with(ACCESS_TOKEN_PREFIX)
+}
+
+fn hash_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub(crate) fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn parse_scopes(value: &str) -> Vec<AccessScope> {
+    value.split(',').filter_map(AccessScope::from_db).collect()
+}
+
+fn map_access_token_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<AccessToken> {
+    let kind: String = row.get(2)?;
+    let scopes: String = row.get(3)?;
+    Ok(AccessToken {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        kind: AccessTokenKind::from_db(&kind),
+        scopes: parse_scopes(&scopes),
+        created_at: row.get(4)?,
+        expires_at: row.get(5)?,
+        last_used_at: row.get(6)?,
+    })
+}
+
+fn list_access_tokens_of_kind(
+    conn: &Connection,
+    kind: Option<AccessTokenKind>,
+) -> Result<Vec<AccessToken>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM access_tokens
+             WHERE ?1 IS NULL OR kind = ?1
+             ORDER BY created_at DESC, id DESC",
+            ACCESS_TOKEN_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let tokens = stmt
+        .query_map(
+            rusqlite::params![kind.map(AccessTokenKind::as_str)],
+            map_access_token_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tokens)
+}
+
+/// Stores a new token and returns it with its secret.
+pub(crate) fn insert_access_token(
+    conn: &Connection,
+    name: &str,
+    kind: AccessTokenKind,
+    scopes: &[AccessScope],
+    expires_at: Option<String>,
+) -> Result<(AccessToken, String), String> {
+    let mut scope_names: Vec<&str> = Vec::new();
+    for scope in scopes {
+        if !scope_names.contains(&scope.as_str()) {
+            scope_names.push(scope.as_str());
+        }
+    }
+    let random_part: String = rand::rng()
+        .sample_iter(Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+    let secret = format!("{}{}", ACCESS_TOKEN_PREFIX, random_part);
+
+    conn.execute(
+        "INSERT INTO access_tokens (name, kind, token_hash, scopes, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![
+            name,
+            kind.as_str(),
+            hash_secret(&secret),
+            scope_names.join(","),
+            expires_at
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let token = get_access_token_by_id(conn, conn.last_insert_rowid())?;
+    Ok((token, secret))
+}
+
+fn get_access_token_by_id(conn: &Connection, token_id: i64) -> Result<AccessToken, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM access_tokens WHERE id = ?1",
+            ACCESS_TOKEN_COLUMNS
+        ),
+        rusqlite::params![token_id],
+        map_access_token_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => "Access token not found".to_string(),
+        other => other.to_string(),
+    })
+}
+
+/// Looks up the token with the given secret and records its use. Returns `None`
+/// for unknown and expired tokens.
+pub fn authenticate_access_token(host: &Host, secret: &str) -> Option<AccessGrant> {
+    if !secret.starts_with(ACCESS_TOKEN_PREFIX) {
+        return None;
+    }
+    let conn = host.db().conn.lock().ok()?;
+    let (token_id, scopes, expires_at) = conn
+        .query_row(
+            "SELECT id, scopes, expires_at FROM access_tokens WHERE token_hash = ?1",
+            rusqlite::params![hash_secret(secret)],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            },
+        )
+        .optional()
+        .ok()??;
+
+    let now = Utc::now();
+    let is_expired = expires_at.is_some_and(|expires_at| {
+        DateTime::parse_from_rfc3339(&expires_at).map_or(true, |expires_at| expires_at <= now)
+    });
+    if is_expired {
+        return None;
+    }
+    let _ = conn.execute(
+        "UPDATE access_tokens SET last_used_at = ?1 WHERE id = ?2",
+        rusqlite::params![format_time(now), token_id],
+    );
+    Some(AccessGrant {
+        scopes: parse_scopes(&scopes),
+    })
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_access_tokens(host: Host) -> Result<Vec<AccessToken>, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    list_access_tokens_of_kind(&conn, None)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_paired_devices(host: Host) -> Result<Vec<AccessToken>, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    list_access_tokens_of_kind(&conn, Some(AccessTokenKind::Device))
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn create_access_token(
+    host: Host,
+    name: String,
+    scopes: Vec<AccessScope>,
+    expires_in_days: Option<u32>,
+) -> Result<CreatedAccessToken, String> {
+    let trimmed_name = name.trim();
+    if trimmed_name.is_empty() {
+        return Err("Token name is required".to_string());
+    }
+    if scopes.is_empty() {
+        return Err("Choose at least one scope".to_string());
+    }
+    if expires_in_days == Some(0) {
+        return Err("Tokens must be valid for at least one day".to_string());
+    }
+
+    let expires_at =
+        expires_in_days.map(|days| format_time(Utc::now() + Duration::days(i64::from(days))));
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let (token, secret) = insert_access_token(
+        &conn,
+        trimmed_name,
+        AccessTokenKind::Token,
+        &scopes,
+        expires_at,
+    )?;
+    Ok(CreatedAccessToken {
+        token,
+        access_url: web_access_url(&secret),
+        secret,
+    })
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn revoke_access_token(host: Host, token_id: i64) -> Result<(), String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let deleted_rows = conn
+        .execute(
+            "DELETE FROM access_tokens WHERE id = ?1",
+            rusqlite::params![token_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+    drop(conn);
+
+    if deleted_rows == 0 {
+        return Err("Access token not found".to_string());
+    }
+    emit_runtime_event(
+        &host,
+        ACCESS_TOKEN_REVOKED_EVENT,
+        AccessTokenRevokedPayload { token_id },
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn read_commands_need_read_scope() {
+        assert_eq!(command_scope("list_repos", None), AccessScope::Read);
+        assert_eq!(command_scope("list_agent_runs", None), AccessScope::Read);
+    }
+
+    #[test]
+    fn agent_commands_need_run_agents_scope() {
+        assert_eq!(
+            command_scope("run_repo_agent", None),
+            AccessScope::RunAgents
+        );
+        assert_eq!(
+            command_scope("create_agent", Some(&json!({ "name": "reviewer" }))),
+            AccessScope::RunAgents
+        );
+        assert_eq!(
+            command_scope(
+                "create_agent",
+                Some(&json!({ "name": "reviewer", "backendCommand": null }))
+            ),
+            AccessScope::RunAgents
+        );
+    }
+
+    #[test]
+    fn custom_backend_commands_need_admin_scope() {
+        let args = json!({ "name": "reviewer", "backendCommand": "./agent.sh" });
+        assert_eq!(
+            command_scope("create_agent", Some(&args)),
+            AccessScope::Admin
+        );
+    }
+
+    #[test]
+    fn repo_commands_need_manage_repos_scope() {
+        assert_eq!(command_scope("add_repo", None), AccessScope::ManageRepos);
+        assert_eq!(command_scope("pull_repo", None), AccessScope::ManageRepos);
+    }
+
+    #[test]
+    fn unknown_commands_need_admin_scope() {
+        assert_eq!(
+            command_scope("create_access_token", None),
+            AccessScope::Admin
+        );
+        assert_eq!(command_scope("not_a_command", None), AccessScope::Admin);
+    }
+
+    #[test]
+    fn admin_grant_allows_everything() {
+        let grant = AccessGrant::admin();
+        assert!(grant.allows(AccessScope::Read));
+        assert!(grant.allows(AccessScope::RunAgents));
+        assert!(grant.allows(AccessScope::ManageRepos));
+        assert!(grant.allows(AccessScope::Admin));
+    }
+
+    #[test]
+    fn scoped_grant_allows_read_and_its_own_scopes() {
+        let grant = AccessGrant {
+            scopes: vec![AccessScope::RunAgents],
+        };
+        assert!(grant.allows(AccessScope::Read));
+        assert!(grant.allows(AccessScope::RunAgents));
+        assert!(!grant.allows(AccessScope::ManageRepos));
+        assert!(!grant.allows(AccessScope::Admin));
+
+        let read_only = AccessGrant { scopes: Vec::new() };
+        assert!(read_only.allows(AccessScope::Read));
+        assert!(!read_only.allows(AccessScope::RunAgents));
+    }
+}
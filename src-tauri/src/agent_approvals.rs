@@ -0,0 +1,162 @@
+use crate::agent_input::write_agent_stdin_line;
+use crate::agent_policy::{review_approval_request, AgentPolicy};
+use crate::agent_stream::ApprovalRequestRecord;
+use crate::commands::emit_runtime_event;
+use crate::host::Host;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentApprovalDecision {
+    Approved,
+    Denied,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentApprovalRequestPayload {
+    pub run_id: String,
+    pub agent_id: i64,
+    pub call_id: String,
+    pub tool_call: Value,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentApprovalResolvedPayload {
+    pub run_id: String,
+    pub agent_id: i64,
+    pub call_id: String,
+    pub decision: AgentApprovalDecision,
+    pub reason: Option<String>,
+}
+
+/// Tracks a tool call the agent is waiting on. Unless the repository's policy
+/// decides on it, clients are asked to approve or deny it.
+pub(crate) fn register_approval_request(
+    host: &Host,
+    agent_id: i64,
+    run_id: &str,
+    working_directory: &str,
+    policy: Option<&AgentPolicy>,
+    record: &ApprovalRequestRecord,
+) {
+    {
+        let state = host.agent_runtime();
+        let Ok(mut process_guard) = state.processes_by_agent_id.lock() else {
+            return;
+        };
+        let Some(process) = process_guard
+            .get_mut(&agent_id)
+            .filter(|process| process.run_id == run_id)
+        else {
+            return;
+        };
+        if !process.pending_approvals.contains(&record.call_id) {
+            process.pending_approvals.push(record.call_id.clone());
+        }
+    }
+
+    let needs_human = policy.is_none_or(|policy| {
+        review_approval_request(host, agent_id, run_id, working_directory, policy, record)
+    });
+    if !needs_human {
+        return;
+    }
+
+    emit_runtime_event(
+        host,
+        "repo-agent-approval-request",
+        AgentApprovalRequestPayload {
+            run_id: run_id.to_string(),
+            agent_id,
+            call_id: record.call_id.clone(),
+            tool_call: record.tool_call.clone(),
+        },
+    );
+}
+
+/// Writes the decision for a pending tool call to the agent's stdin. The call is
+/// claimed before writing, so a second decision for it is rejected.
+pub(crate) fn answer_approval_request(
+    host: &Host,
+    agent_id: i64,
+    call_id: &str,
+    decision: AgentApprovalDecision,
+    reason: Option<String>,
+) -> Result<(), String> {
+    let (run_id, stdin) = {
+        let state = host.agent_runtime();
+        let mut process_guard = state
+            .processes_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+        let process = process_guard
+            .get_mut(&agent_id)
+            .ok_or("Agent is not running".to_string())?;
+        let position = process
+            .pending_approvals
+            .iter()
+            .position(|pending| pending == call_id)
+            .ok_or("No approval is pending for this tool call".to_string())?;
+        let stdin = process
+            .stdin
+            .clone()
+            .ok_or("Agent is not accepting input".to_string())?;
+        process.pending_approvals.remove(position);
+        (process.run_id.clone(), stdin)
+    };
+
+    let line = serde_json::json!({
+        "type": "approval_response",
+        "call_id": call_id,
+        "decision": decision,
+        "reason": reason,
+    })
+    .to_string();
+    write_agent_stdin_line(host, &run_id, &stdin, &line)?;
+
+    emit_runtime_event(
+        host,
+        "repo-agent-approval-resolved",
+        AgentApprovalResolvedPayload {
+            run_id,
+            agent_id,
+            call_id: call_id.to_string(),
+            decision,
+            reason,
+        },
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn approve_tool_call(host: Host, agent_id: i64, call_id: String) -> Result<(), String> {
+    answer_approval_request(
+        &host,
+        agent_id,
+        &call_id,
+        AgentApprovalDecision::Approved,
+        None,
+    )
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn deny_tool_call(
+    host: Host,
+    agent_id: i64,
+    call_id: String,
+    reason: Option<String>,
+) -> Result<(), String> {
+    let reason = reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+    answer_approval_request(
+        &host,
+        agent_id,
+        &call_id,
+        AgentApprovalDecision::Denied,
+        reason,
+    )
+}
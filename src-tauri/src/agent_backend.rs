@@ -0,0 +1,656 @@
+use crate::agent_stream::{parse_agent_stream_line, AgentStreamEvent};
+use crate::commands::AgentModelOption;
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentBackendKind {
+    CursorAgent,
+    Simulator,
+    JsonLines,
+}
+
+impl AgentBackendKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AgentBackendKind::CursorAgent => "cursor-agent",
+            AgentBackendKind::Simulator => "simulator",
+            AgentBackendKind::JsonLines => "json-lines",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "" | "cursor-agent" => Ok(AgentBackendKind::CursorAgent),
+            "simulator" => Ok(AgentBackendKind::Simulator),
+            "json-lines" => Ok(AgentBackendKind::JsonLines),
+            other => Err(format!("Unknown agent backend: {}", other)),
+        }
+    }
+}
+
+/// The signal that first asks a running agent to stop. The agent's process group
+/// is killed if it is still alive once the stop grace period has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentStopSignal {
+    Interrupt,
+    Terminate,
+}
+
+pub struct AgentCommandRequest<'a> {
+    pub prompt: &'a str,
+    pub working_directory: &'a str,
+    pub model: Option<&'a str>,
+    pub force_approve: bool,
+    /// Session id reported by a previous run's `system/init` record to continue.
+    pub resume_session_id: Option<&'a str>,
+}
+
+/// A CLI that Symphony can drive as an agent. Implementations build the process to
+/// spawn and interpret what it prints; the runtime handles piping, persistence and
+/// lifecycle events.
+pub trait AgentBackend: Send + Sync {
+    /// Human readable name used in error messages, e.g. "Cursor agent".
+    fn display_name(&self) -> &'static str;
+
+    fn build_command(&self, request: &AgentCommandRequest<'_>) -> Result<Command, String>;
+
+    fn list_models(&self) -> Result<Vec<AgentModelOption>, String>;
+
+    fn supports_resume(&self) -> bool {
+        false
+    }
+
+    /// Whether the CLI reads follow-up stream-json `user` records from stdin while it
+    /// runs. Other backends get no stdin unless they ask for tool call approvals.
+    fn supports_input(&self) -> bool {
+        false
+    }
+
+    /// Whether the CLI can ask for approval of tool calls with `approval_request`
+    /// records when it is not started with `force_approve`, reading the
+    /// `approval_response` lines from stdin. This is Symphony's own protocol; runs
+    /// of backends without it must use `force_approve`.
+    fn supports_tool_approval(&self) -> bool {
+        false
+    }
+
+    fn parse_output_line(&self, line: &str) -> Option<AgentStreamEvent> {
+        parse_agent_stream_line(line)
+    }
+
+    fn stop_signal(&self) -> AgentStopSignal {
+        AgentStopSignal::Terminate
+    }
+}
+
+pub struct CursorAgentBackend;
+
+impl AgentBackend for CursorAgentBackend {
+    fn display_name(&self) -> &'static str {
+        "Cursor agent"
+    }
+
+    fn build_command(&self, request: &AgentCommandRequest<'_>) -> Result<Command, String> {
+        create_cursor_agent_command(
+            request.prompt,
+            request.force_approve,
+            request.model,
+            request.resume_session_id,
+        )
+    }
+
+    fn list_models(&self) -> Result<Vec<AgentModelOption>, String> {
+        list_cursor_agent_models()
+    }
+
+    fn supports_resume(&self) -> bool {
+        true
+    }
+
+    fn stop_signal(&self) -> AgentStopSignal {
+        AgentStopSignal::Interrupt
+    }
+}
+
+/// Replays a canned cursor-agent session from `scripts/agent-simulator.mjs`.
+pub struct SimulatorBackend;
+
+impl AgentBackend for SimulatorBackend {
+    fn display_name(&self) -> &'static str {
+        "simulator agent"
+    }
+
+    fn build_command(&self, request: &AgentCommandRequest<'_>) -> Result<Command, String> {
+        let mut command = create_simulator_agent_command(
+            request.prompt,
+            request.working_directory,
+            request.resume_session_id,
+        )?;
+        if !request.force_approve {
+            command.env("AGENT_SIMULATOR_REQUIRE_APPROVAL", "1");
+        }
+        Ok(command)
+    }
+
+    fn list_models(&self) -> Result<Vec<AgentModelOption>, String> {
+        Ok(vec![AgentModelOption {
+            id: "simulator".to_string(),
+            name: "Simulator".to_string(),
+        }])
+    }
+
+    fn supports_resume(&self) -> bool {
+        true
+    }
+
+    fn supports_tool_approval(&self) -> bool {
+        true
+    }
+}
+
+/// Any CLI that prints one JSON object per line, launched from a command template
+/// such as `my-agent run --model {model} --resume {session_id} --cwd {cwd} {prompt}`.
+///
+/// `{prompt}`, `{model}`, `{session_id}` and `{cwd}` are substituted per argument, so
+/// a prompt with spaces stays a single argument. When no model is selected or no
+/// session is resumed, arguments containing `{model}` or `{session_id}` are dropped
+/// together with a preceding `--flag`. If the template has no `{prompt}` placeholder,
+/// the prompt is appended as the last argument.
+///
+/// Follow-up messages are written to stdin as stream-json `user` records. Without
+/// `force_approve`, the CLI may also print `approval_request` records and read one
+/// `approval_response` JSON line from stdin per request.
+pub struct JsonLinesBackend {
+    command_template: Vec<String>,
+}
+
+impl JsonLinesBackend {
+    pub fn new(command_template: &str) -> Result<Self, String> {
+        let command_template = split_command_template(command_template)?;
+        if command_template.is_empty() {
+            return Err("A command template is required for the JSON lines backend".to_string());
+        }
+        Ok(Self { command_template })
+    }
+}
+
+/// Replaces placeholders in one pass over the template token, so placeholder-like
+/// text inside a substituted value, such as a prompt mentioning `{cwd}`, is kept.
+fn substitute_placeholders(token: &str, values: &[(&str, &str)]) -> String {
+    let mut substituted = String::with_capacity(token.len());
+    let mut rest = token;
+    while !rest.is_empty() {
+        if let Some((placeholder, value)) = values
+            .iter()
+            .find(|(placeholder, _)| rest.starts_with(placeholder))
+        {
+            substituted.push_str(value);
+            rest = &rest[placeholder.len()..];
+            continue;
+        }
+        let Some(character) = rest.chars().next() else {
+            break;
+        };
+        substituted.push(character);
+        rest = &rest[character.len_utf8()..];
+    }
+    substituted
+}
+
+impl AgentBackend for JsonLinesBackend {
+    fn display_name(&self) -> &'static str {
+        "agent CLI"
+    }
+
+    fn build_command(&self, request: &AgentCommandRequest<'_>) -> Result<Command, String> {
+        let mut arguments: Vec<String> = Vec::new();
+        let mut has_prompt_placeholder = false;
+        // The template token of the last argument, if it is a literal `--flag`. The
+        // substituted argument is not checked, since a prompt or model may start
+        // with `-` too.
+        let mut last_flag: Option<&str> = None;
+
+        let optional_placeholders = [
+            ("{model}", request.model),
+            ("{session_id}", request.resume_session_id),
+        ];
+
+        for token in &self.command_template {
+            let missing_optional_value = optional_placeholders
+                .iter()
+                .any(|(placeholder, value)| value.is_none() && token.contains(placeholder));
+            if missing_optional_value {
+                if last_flag.take().is_some() {
+                    arguments.pop();
+                }
+                continue;
+            }
+            last_flag = (token.starts_with('-') && !token.contains('{')).then_some(token.as_str());
+            has_prompt_placeholder |= token.contains("{prompt}");
+            arguments.push(substitute_placeholders(
+                token,
+                &[
+                    ("{prompt}", request.prompt),
+                    ("{model}", request.model.unwrap_or_default()),
+                    (
+                        "{session_id}",
+                        request.resume_session_id.unwrap_or_default(),
+                    ),
+                    ("{cwd}", request.working_directory),
+                ],
+            ));
+        }
+        if !has_prompt_placeholder {
+            arguments.push(request.prompt.to_string());
+        }
+
+        let mut arguments = arguments.into_iter();
+        let program = arguments
+            .next()
+            .ok_or("The command template does not name a program".to_string())?;
+        let mut command = Command::new(program);
+        command.args(arguments);
+        Ok(command)
+    }
+
+    fn list_models(&self) -> Result<Vec<AgentModelOption>, String> {
+        Ok(Vec::new())
+    }
+
+    fn supports_resume(&self) -> bool {
+        self.command_template
+            .iter()
+            .any(|token| token.contains("{session_id}"))
+    }
+
+    fn supports_input(&self) -> bool {
+        true
+    }
+
+    fn supports_tool_approval(&self) -> bool {
+        true
+    }
+}
+
+pub fn create_agent_backend(
+    kind: AgentBackendKind,
+    command_template: Option<&str>,
+) -> Result<Box<dyn AgentBackend>, String> {
+    match kind {
+        AgentBackendKind::CursorAgent => Ok(Box::new(CursorAgentBackend)),
+        AgentBackendKind::Simulator => Ok(Box::new(SimulatorBackend)),
+        AgentBackendKind::JsonLines => Ok(Box::new(JsonLinesBackend::new(
+            command_template.unwrap_or_default(),
+        )?)),
+    }
+}
+
+/// Loads the backend configured for an agent, falling back to cursor-agent for
+/// agents that are not stored in the database.
+pub fn load_agent_backend(
+    conn: &Connection,
+    agent_id: i64,
+) -> Result<Box<dyn AgentBackend>, String> {
+    let configuration: Option<(String, Option<String>)> = conn
+        .query_row(
+            "SELECT backend, backend_command FROM agents WHERE id = ?1",
+            rusqlite::params![agent_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    match configuration {
+        Some((backend, backend_command)) => create_agent_backend(
+            AgentBackendKind::parse(&backend)?,
+            backend_command.as_deref(),
+        ),
+        None => create_agent_backend(AgentBackendKind::CursorAgent, None),
+    }
+}
+
+fn split_command_template(template: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut has_token = false;
+    let mut quote: Option<char> = None;
+
+    for character in template.trim().chars() {
+        match (quote, character) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(character);
+                has_token = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if has_token {
+                    tokens.push(std::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                has_token = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err("Unterminated quote in command template".to_string());
+    }
+    if has_token {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+fn list_cursor_agent_models() -> Result<Vec<AgentModelOption>, String> {
+    let output = Command::new("agent")
+        .arg("models")
+        .output()
+        .map_err(|e| format!("Failed to run agent models: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let message = if !stderr.is_empty() {
+            stderr
+        } else if !stdout.is_empty() {
+            stdout
+        } else {
+            "Failed to list models from agent CLI".to_string()
+        };
+        return Err(message);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let cleaned_output = strip_ansi_codes(&stdout);
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut models: Vec<AgentModelOption> = Vec::new();
+
+    for line in cleaned_output.lines() {
+        let trimmed_line = line.trim();
+        if trimmed_line.is_empty() {
+            continue;
+        }
+        if trimmed_line.starts_with("Loading models") {
+            continue;
+        }
+        if trimmed_line.starts_with("Available models") {
+            continue;
+        }
+        if trimmed_line.starts_with("Tip: use --model") {
+            continue;
+        }
+
+        let (model_id, display_name) =
+            if let Some((id_part, name_part)) = trimmed_line.split_once(" - ") {
+                let model_id = id_part.trim();
+                if model_id.is_empty() {
+                    continue;
+                }
+                let name_part = name_part.trim();
+                let display_name = if name_part.is_empty() {
+                    model_id.to_string()
+                } else {
+                    name_part.to_string()
+                };
+                (model_id, display_name)
+            } else {
+                let model_id = trimmed_line;
+                if model_id.is_empty() {
+                    continue;
+                }
+                (model_id, model_id.to_string())
+            };
+
+        if !model_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        {
+            continue;
+        }
+        let id_owned = model_id.to_string();
+        if seen.insert(id_owned.clone()) {
+            models.push(AgentModelOption {
+                id: id_owned,
+                name: display_name,
+            });
+        }
+    }
+
+    if models.is_empty() {
+        return Err("No models were returned by the agent CLI".to_string());
+    }
+
+    Ok(models)
+}
+
+fn create_cursor_agent_command(
+    prompt: &str,
+    force_approve: bool,
+    model: Option<&str>,
+    resume_session_id: Option<&str>,
+) -> Result<std::process::Command, String> {
+    #[cfg(target_os = "windows")]
+    {
+        let local_app_data =
+            std::env::var("LOCALAPPDATA").map_err(|_| "LOCALAPPDATA env var not found")?;
+        let agent_path = Path::new(&local_app_data)
+            .join("cursor-agent")
+            .join("agent.CMD");
+        if !agent_path.exists() {
+            return Err(format!("agent.CMD not found at {}", agent_path.display()));
+        }
+
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", agent_path.to_string_lossy().as_ref()]);
+        command.arg(prompt);
+        command.args(["--output-format", "stream-json", "--print"]);
+        if let Some(trimmed_model) = model.map(str::trim).filter(|value| !value.is_empty()) {
+            command.args(["--model", trimmed_model]);
+        }
+        if let Some(session_id) = resume_session_id {
+            command.args(["--resume", session_id]);
+        }
+        if force_approve {
+            command.arg("--force");
+        }
+        return Ok(command);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let mut command = std::process::Command::new("cursor-agent");
+        command.arg(prompt);
+        command.args(["--output-format", "stream-json", "--print"]);
+        if let Some(trimmed_model) = model.map(str::trim).filter(|value| !value.is_empty()) {
+            command.args(["--model", trimmed_model]);
+        }
+        if let Some(session_id) = resume_session_id {
+            command.args(["--resume", session_id]);
+        }
+        if force_approve {
+            command.arg("--force");
+        }
+        Ok(command)
+    }
+}
+
+fn create_simulator_agent_command(
+    prompt: &str,
+    repo_path: &str,
+    resume_session_id: Option<&str>,
+) -> Result<Command, String> {
+    let script_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("scripts")
+        .join("agent-simulator.mjs");
+    if !script_path.exists() {
+        return Err(format!(
+            "Simulator script not found at {}",
+            script_path.display()
+        ));
+    }
+
+    let script_path_string = script_path.to_string_lossy().to_string();
+    if command_exists("bun") {
+        let mut command = Command::new("bun");
+        command.args([script_path_string.as_str(), prompt, repo_path]);
+        command.args(resume_session_id);
+        return Ok(command);
+    }
+
+    if command_exists("node") {
+        let mut command = Command::new("node");
+        command.args([script_path_string.as_str(), prompt, repo_path]);
+        command.args(resume_session_id);
+        return Ok(command);
+    }
+
+    Err("Simulator mode requires `bun` or `node` in PATH".to_string())
+}
+
+fn command_exists(command: &str) -> bool {
+    Command::new(command)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+fn strip_ansi_codes(input: &str) -> String {
+    let mut output = String::new();
+    let mut character_iterator = input.chars().peekable();
+
+    while let Some(character) = character_iterator.next() {
+        if character != '\u{1b}' {
+            output.push(character);
+            continue;
+        }
+
+        let Some(next_character) = character_iterator.peek().copied() else {
+            break;
+        };
+        if next_character != '[' {
+            continue;
+        }
+
+        let _ = character_iterator.next();
+        for escape_character in character_iterator.by_ref() {
+            if ('@'..='~').contains(&escape_character) {
+                break;
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command_line(template: &str, model: Option<&str>, session_id: Option<&str>) -> Vec<String> {
+        let command = JsonLinesBackend::new(template)
+            .unwrap()
+            .build_command(&AgentCommandRequest {
+                prompt: "fix the tests",
+                working_directory: "/work/repo",
+                model,
+                force_approve: true,
+                resume_session_id: session_id,
+            })
+            .unwrap();
+        std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|argument| argument.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn splits_templates_on_whitespace_outside_quotes() {
+        assert_eq!(
+            split_command_template(r#"  agent run --name "two words" 'it''s' "" "#).unwrap(),
+            vec!["agent", "run", "--name", "two words", "its", ""]
+        );
+        assert!(split_command_template("agent \"unterminated").is_err());
+        assert!(split_command_template("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn substitutes_placeholders_once() {
+        assert_eq!(
+            substitute_placeholders(
+                "--cwd={cwd}:{prompt}",
+                &[("{prompt}", "mention {cwd}"), ("{cwd}", "/work/repo")]
+            ),
+            "--cwd=/work/repo:mention {cwd}"
+        );
+        assert_eq!(substitute_placeholders("{unknown}", &[]), "{unknown}");
+    }
+
+    #[test]
+    fn substitutes_values_and_keeps_the_prompt_one_argument() {
+        assert_eq!(
+            command_line(
+                "agent --model {model} --resume {session_id} --cwd {cwd} {prompt}",
+                Some("fast"),
+                Some("session-1")
+            ),
+            vec![
+                "agent",
+                "--model",
+                "fast",
+                "--resume",
+                "session-1",
+                "--cwd",
+                "/work/repo",
+                "fix the tests"
+            ]
+        );
+    }
+
+    #[test]
+    fn appends_the_prompt_without_a_placeholder() {
+        assert_eq!(
+            command_line("agent run", None, None),
+            vec!["agent", "run", "fix the tests"]
+        );
+    }
+
+    #[test]
+    fn drops_missing_values_with_their_flag() {
+        assert_eq!(
+            command_line(
+                "agent --model {model} --resume={session_id} {prompt}",
+                None,
+                None
+            ),
+            vec!["agent", "fix the tests"]
+        );
+    }
+
+    #[test]
+    fn only_drops_flags_written_in_the_template() {
+        // The value before the missing session starts with `-`, but it is the
+        // model, not a flag.
+        assert_eq!(
+            command_line(
+                "agent --model {model} {session_id} {prompt}",
+                Some("-fast"),
+                None
+            ),
+            vec!["agent", "--model", "-fast", "fix the tests"]
+        );
+        assert_eq!(
+            command_line("agent {prompt} {session_id}", None, None),
+            vec!["agent", "fix the tests"]
+        );
+    }
+}
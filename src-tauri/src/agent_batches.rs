@@ -0,0 +1,393 @@
+use crate::agent_queue::{drop_queued_prompts, ensure_agents_idle, AgentPromptRequest};
+use crate::commands::{
+    current_time_ms, delete_agent_rows, emit_runtime_event, start_agent_run, AgentDonePayload,
+    AgentRunOutcome,
+};
+use crate::host::Host;
+use crate::worktrees::{provision_agent_worktree, release_agent_worktrees};
+use rusqlite::Connection;
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentBatchRun {
+    pub repo_id: i64,
+    pub repo_name: String,
+    /// Agent created for the batch in this repo, `None` if creating it failed.
+    pub agent_id: Option<i64>,
+    pub run_id: String,
+    pub branch_name: Option<String>,
+    /// `queued`, `running`, or the run's outcome once it is done.
+    pub status: String,
+    /// Why the run could not be started.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentBatchProgress {
+    pub total: u64,
+    pub queued: u64,
+    pub running: u64,
+    pub succeeded: u64,
+    /// Runs that are done without succeeding, including ones that never started.
+    pub failed: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentBatch {
+    pub batch_id: String,
+    pub group_id: i64,
+    pub prompt: String,
+    pub model: Option<String>,
+    pub fresh_branch: bool,
+    pub created_at: String,
+    pub progress: AgentBatchProgress,
+    pub runs: Vec<AgentBatchRun>,
+}
+
+struct BatchRepo {
+    id: i64,
+    path: String,
+}
+
+fn load_agent_batch(conn: &Connection, batch_id: &str) -> Result<AgentBatch, String> {
+    let mut batch = conn
+        .query_row(
+            "SELECT batch_id, group_id, prompt, model, fresh_branch, created_at
+             FROM agent_batches
+             WHERE batch_id = ?1",
+            rusqlite::params![batch_id],
+            |row| {
+                Ok(AgentBatch {
+                    batch_id: row.get(0)?,
+                    group_id: row.get(1)?,
+                    prompt: row.get(2)?,
+                    model: row.get(3)?,
+                    fresh_branch: row.get(4)?,
+                    created_at: row.get(5)?,
+                    progress: AgentBatchProgress::default(),
+                    runs: Vec::new(),
+                })
+            },
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Agent batch not found".to_string(),
+            other => other.to_string(),
+        })?;
+
+    // Until a run is done, its live status comes from the run's own row, which
+    // only exists once the scheduler has started it.
+    let mut stmt = conn
+        .prepare(
+            "SELECT agent_batch_runs.repo_id, COALESCE(repos.name, ''), agent_batch_runs.agent_id,
+                    agent_batch_runs.run_id, agents.branch_name, agent_batch_runs.error,
+                    CASE
+                        WHEN agent_batch_runs.status <> 'pending' THEN agent_batch_runs.status
+                        WHEN agent_runs.run_id IS NOT NULL THEN 'running'
+                        ELSE 'queued'
+                    END
+             FROM agent_batch_runs
+             LEFT JOIN repos ON repos.id = agent_batch_runs.repo_id
+             LEFT JOIN agents ON agents.id = agent_batch_runs.agent_id
+             LEFT JOIN agent_runs ON agent_runs.run_id = agent_batch_runs.run_id
+             WHERE agent_batch_runs.batch_id = ?1
+             ORDER BY repos.name ASC, agent_batch_runs.repo_id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    batch.runs = stmt
+        .query_map(rusqlite::params![batch_id], |row| {
+            Ok(AgentBatchRun {
+                repo_id: row.get(0)?,
+                repo_name: row.get(1)?,
+                agent_id: row.get(2)?,
+                run_id: row.get(3)?,
+                branch_name: row.get(4)?,
+                error: row.get(5)?,
+                status: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let progress = &mut batch.progress;
+    for run in &batch.runs {
+        progress.total += 1;
+        match run.status.as_str() {
+            "queued" => progress.queued += 1,
+            "running" => progress.running += 1,
+            "succeeded" => progress.succeeded += 1,
+            _ => progress.failed += 1,
+        }
+    }
+    Ok(batch)
+}
+
+fn set_batch_run_failed(conn: &Connection, run_id: &str, error: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE agent_batch_runs SET status = ?1, error = ?2 WHERE run_id = ?3",
+        rusqlite::params![AgentRunOutcome::Failed.as_str(), error, run_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Creates an agent for the batch in the repo, on a fresh worktree branch when asked.
+/// The agent is removed again if its worktree cannot be created.
+fn create_batch_agent(
+    host: &Host,
+    repo_id: i64,
+    name: &str,
+    fresh_branch: bool,
+) -> Result<i64, String> {
+    let agent_id = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO agents (repo_id, name) VALUES (?1, ?2)",
+            rusqlite::params![repo_id, name],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.last_insert_rowid()
+    };
+
+    if fresh_branch {
+        if let Err(error) = provision_agent_worktree(host, agent_id) {
+            let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+            let _ = conn.execute(
+                "DELETE FROM agents WHERE id = ?1",
+                rusqlite::params![agent_id],
+            );
+            return Err(error);
+        }
+    }
+    Ok(agent_id)
+}
+
+/// Stores the outcome of a batch run and reports the batch's progress. Runs that
+/// are not part of a batch are ignored.
+pub(crate) fn record_batch_run_done(host: &Host, payload: &AgentDonePayload) {
+    let batch = {
+        let Ok(conn) = host.db().conn.lock() else {
+            return;
+        };
+        let batch_id = conn.query_row(
+            "UPDATE agent_batch_runs SET status = ?1 WHERE run_id = ?2 RETURNING batch_id",
+            rusqlite::params![payload.outcome.as_str(), payload.run_id],
+            |row| row.get::<_, String>(0),
+        );
+        match batch_id {
+            Ok(batch_id) => load_agent_batch(&conn, &batch_id),
+            Err(rusqlite::Error::QueryReturnedNoRows) => return,
+            Err(error) => Err(error.to_string()),
+        }
+    };
+
+    match batch {
+        Ok(batch) => emit_runtime_event(host, "agent-batch-progress", batch),
+        Err(error) => eprintln!("Failed to record batch run {}: {}", payload.run_id, error),
+    }
+}
+
+/// Starts the same prompt in every repo of the group, each with an agent of its own,
+/// and tracks the runs as one batch. Repos where the run cannot be started are
+/// reported in the batch instead of failing it.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn run_group_agents(
+    host: Host,
+    group_id: i64,
+    prompt: String,
+    model: Option<String>,
+    fresh_branch: Option<bool>,
+    force_approve: Option<bool>,
+    simulate_mode: Option<bool>,
+) -> Result<AgentBatch, String> {
+    let trimmed_prompt = prompt.trim();
+    if trimmed_prompt.is_empty() {
+        return Err("Prompt is required".to_string());
+    }
+    let fresh_branch = fresh_branch.unwrap_or(false);
+    let batch_id = format!("{:016x}", rand::random::<u64>());
+    let agent_name = format!("Batch {}", &batch_id[..8]);
+
+    let repos = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        let group_exists: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM groups WHERE id = ?1)",
+                rusqlite::params![group_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !group_exists {
+            return Err("Group not found".to_string());
+        }
+
+        let mut stmt = conn
+            .prepare("SELECT id, path FROM repos WHERE group_id = ?1 ORDER BY name ASC")
+            .map_err(|e| e.to_string())?;
+        let repos = stmt
+            .query_map(rusqlite::params![group_id], |row| {
+                Ok(BatchRepo {
+                    id: row.get(0)?,
+                    path: row.get(1)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        if repos.is_empty() {
+            return Err("This group has no repositories".to_string());
+        }
+
+        conn.execute(
+            "INSERT INTO agent_batches (batch_id, group_id, prompt, model, fresh_branch)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![batch_id, group_id, trimmed_prompt, model, fresh_branch],
+        )
+        .map_err(|e| e.to_string())?;
+        repos
+    };
+
+    // Worktrees are created without the database lock, one repo at a time.
+    let mut requests = Vec::new();
+    for repo in repos {
+        let run_id = format!("{}-{}", batch_id, repo.id);
+        let agent = create_batch_agent(&host, repo.id, &agent_name, fresh_branch);
+        let (agent_id, status, error) = match &agent {
+            Ok(agent_id) => (Some(*agent_id), "pending", None),
+            Err(error) => (None, AgentRunOutcome::Failed.as_str(), Some(error)),
+        };
+        {
+            let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+            conn.execute(
+                "INSERT INTO agent_batch_runs (batch_id, repo_id, agent_id, run_id, status, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                rusqlite::params![batch_id, repo.id, agent_id, run_id, status, error],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        if let Some(agent_id) = agent_id {
+            requests.push(AgentPromptRequest {
+                run_id,
+                agent_id,
+                repo_id: repo.id,
+                repo_path: repo.path,
+                prompt: trimmed_prompt.to_string(),
+                model: model.clone(),
+                force_approve,
+                simulate_mode,
+                isolated_workspace: None,
+                resume: None,
+                limits: None,
+                priority: 0,
+                queued_at: current_time_ms(),
+            });
+        }
+    }
+
+    for request in requests {
+        let run_id = request.run_id.clone();
+        if let Err(error) = start_agent_run(&host, request) {
+            let batch = {
+                let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+                set_batch_run_failed(&conn, &run_id, &error)?;
+                load_agent_batch(&conn, &batch_id)?
+            };
+            emit_runtime_event(&host, "agent-batch-progress", batch);
+        }
+    }
+
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    load_agent_batch(&conn, &batch_id)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn get_agent_batch(host: Host, batch_id: String) -> Result<AgentBatch, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    load_agent_batch(&conn, &batch_id)
+}
+
+/// Deletes the batch together with the agents it created, their worktrees and
+/// branches. Fails while any of its runs is still going, or if an agent's worktree
+/// holds work that was never merged.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn delete_agent_batch(host: Host, batch_id: String) -> Result<(), String> {
+    let agent_ids = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        let batch_exists: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM agent_batches WHERE batch_id = ?1)",
+                rusqlite::params![batch_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !batch_exists {
+            return Err("Agent batch not found".to_string());
+        }
+
+        // Agents deleted on their own since are skipped.
+        let mut stmt = conn
+            .prepare(
+                "SELECT agents.id FROM agent_batch_runs
+                 JOIN agents ON agents.id = agent_batch_runs.agent_id
+                 WHERE agent_batch_runs.batch_id = ?1",
+            )
+            .map_err(|e| e.to_string())?;
+        let agent_ids = stmt
+            .query_map(rusqlite::params![batch_id], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        agent_ids
+    };
+
+    ensure_agents_idle(
+        &host,
+        &agent_ids,
+        "Stop the batch's runs before deleting it",
+    )?;
+    release_agent_worktrees(&host, &agent_ids)?;
+
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    for agent_id in &agent_ids {
+        delete_agent_rows(&conn, *agent_id)?;
+    }
+    conn.execute(
+        "DELETE FROM agent_batch_runs WHERE batch_id = ?1",
+        rusqlite::params![batch_id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_batches WHERE batch_id = ?1",
+        rusqlite::params![batch_id],
+    )
+    .map_err(|e| e.to_string())?;
+    drop(conn);
+    drop_queued_prompts(&host, &agent_ids);
+    Ok(())
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_agent_batches(host: Host, group_id: i64) -> Result<Vec<AgentBatch>, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT batch_id FROM agent_batches
+             WHERE group_id = ?1
+             ORDER BY created_at DESC, rowid DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let batch_ids = stmt
+        .query_map(rusqlite::params![group_id], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    batch_ids
+        .iter()
+        .map(|batch_id| load_agent_batch(&conn, batch_id))
+        .collect()
+}
@@ -0,0 +1,80 @@
+use crate::agent_stream::{AgentMessage, AgentMessageContent, AgentStreamEvent, MessageRecord};
+use crate::commands::{
+    append_agent_run_event, emit_runtime_event, AgentEventPayload, AgentRunState,
+};
+use crate::host::Host;
+use std::io::Write;
+use std::process::ChildStdin;
+use std::sync::{Arc, Mutex};
+
+/// Stdin of a running agent, shared so commands can write to it while the run's
+/// worker thread owns the process.
+pub(crate) type AgentStdin = Arc<Mutex<ChildStdin>>;
+
+/// Writes one line to the agent's stdin and records it in the run's transcript.
+pub(crate) fn write_agent_stdin_line(
+    host: &Host,
+    run_id: &str,
+    stdin: &AgentStdin,
+    line: &str,
+) -> Result<(), String> {
+    {
+        let mut stdin = stdin.lock().map_err(|e| e.to_string())?;
+        writeln!(stdin, "{}", line)
+            .and_then(|_| stdin.flush())
+            .map_err(|e| format!("Failed to write to the agent: {}", e))?;
+    }
+    append_agent_run_event(host, run_id, "stdin", line);
+    Ok(())
+}
+
+/// Sends a follow-up message to a running agent as a stream-json `user` record,
+/// e.g. to answer a clarifying question.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn send_agent_input(host: Host, agent_id: i64, text: String) -> Result<(), String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Input is required".to_string());
+    }
+
+    let (run_id, stdin) = {
+        let state = host.agent_runtime();
+        let process_guard = state
+            .processes_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+        let process = process_guard
+            .get(&agent_id)
+            .filter(|process| process.state == AgentRunState::Running)
+            .ok_or("Agent is not running".to_string())?;
+        let stdin = process
+            .stdin
+            .clone()
+            .filter(|_| process.accepts_input)
+            .ok_or("Agent is not accepting input".to_string())?;
+        (process.run_id.clone(), stdin)
+    };
+
+    let event = AgentStreamEvent::User(MessageRecord {
+        message: AgentMessage {
+            role: "user".to_string(),
+            content: vec![AgentMessageContent::Text {
+                text: text.to_string(),
+            }],
+        },
+        session_id: None,
+    });
+    let line = serde_json::to_string(&event).map_err(|e| e.to_string())?;
+    write_agent_stdin_line(&host, &run_id, &stdin, &line)?;
+
+    emit_runtime_event(
+        &host,
+        "repo-agent-event",
+        AgentEventPayload {
+            run_id,
+            agent_id,
+            event,
+        },
+    );
+    Ok(())
+}
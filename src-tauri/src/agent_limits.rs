@@ -0,0 +1,200 @@
+use crate::commands::{request_agent_stop, AgentRunState, AgentTerminationReason};
+use crate::host::Host;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Limits applied to an agent run. Every field is optional; a run's own limits take
+/// precedence over the limits stored on the agent.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunLimits {
+    /// Wall-clock time the run may take.
+    pub timeout_seconds: Option<u64>,
+    /// Time the run may go without writing to stdout.
+    pub idle_timeout_seconds: Option<u64>,
+    /// CPU time limit (`RLIMIT_CPU`), Linux only.
+    pub cpu_time_limit_seconds: Option<u64>,
+    /// Address space limit (`RLIMIT_AS`), Linux only.
+    pub memory_limit_mb: Option<u64>,
+    /// Open file descriptor limit (`RLIMIT_NOFILE`), Linux only.
+    pub open_files_limit: Option<u64>,
+}
+
+impl AgentRunLimits {
+    pub fn or(self, fallback: AgentRunLimits) -> AgentRunLimits {
+        AgentRunLimits {
+            timeout_seconds: self.timeout_seconds.or(fallback.timeout_seconds),
+            idle_timeout_seconds: self.idle_timeout_seconds.or(fallback.idle_timeout_seconds),
+            cpu_time_limit_seconds: self
+                .cpu_time_limit_seconds
+                .or(fallback.cpu_time_limit_seconds),
+            memory_limit_mb: self.memory_limit_mb.or(fallback.memory_limit_mb),
+            open_files_limit: self.open_files_limit.or(fallback.open_files_limit),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let values = [
+            self.timeout_seconds,
+            self.idle_timeout_seconds,
+            self.cpu_time_limit_seconds,
+            self.memory_limit_mb,
+            self.open_files_limit,
+        ];
+        if values.contains(&Some(0)) {
+            return Err("Run limits must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Applies the rlimits in `limits` to the process before it executes. Limits above
+/// the current hard limit are clamped, since an unprivileged process cannot raise it.
+#[cfg(target_os = "linux")]
+pub(crate) fn apply_resource_limits(process: &mut Command, limits: &AgentRunLimits) {
+    use std::os::unix::process::CommandExt;
+
+    let resource_limits = [
+        (libc::RLIMIT_CPU, limits.cpu_time_limit_seconds),
+        (
+            libc::RLIMIT_AS,
+            limits
+                .memory_limit_mb
+                .map(|megabytes| megabytes.saturating_mul(1024 * 1024)),
+        ),
+        (libc::RLIMIT_NOFILE, limits.open_files_limit),
+    ];
+    if resource_limits.iter().all(|(_, value)| value.is_none()) {
+        return;
+    }
+
+    // SAFETY: the closure runs between fork and exec and only calls the
+    // async-signal-safe getrlimit/setrlimit.
+    unsafe {
+        process.pre_exec(move || {
+            for (resource, value) in resource_limits {
+                let Some(value) = value else {
+                    continue;
+                };
+                let mut limit = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                if libc::getrlimit(resource, &mut limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let value = (value as libc::rlim_t).min(limit.rlim_max);
+                limit.rlim_cur = value;
+                // Keep headroom above the CPU soft limit so the agent receives SIGXCPU,
+                // which identifies the limit as the reason, instead of SIGKILL.
+                if resource == libc::RLIMIT_CPU {
+                    limit.rlim_max = value.saturating_add(5).min(limit.rlim_max);
+                } else {
+                    limit.rlim_max = value;
+                }
+                if libc::setrlimit(resource, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn apply_resource_limits(_process: &mut Command, _limits: &AgentRunLimits) {}
+
+/// Whether the process was killed for exceeding its CPU time limit.
+#[cfg(target_os = "linux")]
+pub(crate) fn exceeded_cpu_time_limit(status: &std::process::ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal() == Some(libc::SIGXCPU)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn exceeded_cpu_time_limit(_status: &std::process::ExitStatus) -> bool {
+    false
+}
+
+/// Tracks when the agent last wrote to stdout, for the idle timeout.
+#[derive(Clone)]
+pub(crate) struct AgentActivity(Arc<Mutex<Instant>>);
+
+impl AgentActivity {
+    pub fn new() -> Self {
+        AgentActivity(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    pub fn touch(&self) {
+        if let Ok(mut last_output_at) = self.0.lock() {
+            *last_output_at = Instant::now();
+        }
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.0
+            .lock()
+            .map(|last_output_at| last_output_at.elapsed())
+            .unwrap_or_default()
+    }
+}
+
+/// Stops the run once it exceeds its wall-clock or idle timeout. The watchdog
+/// exits as soon as the process is no longer tracked or is already stopping.
+/// Time spent waiting for tool call approvals counts towards the timeout only.
+pub(crate) fn spawn_run_watchdog(
+    host: &Host,
+    agent_id: i64,
+    run_id: &str,
+    limits: &AgentRunLimits,
+    activity: AgentActivity,
+) {
+    let timeout = limits.timeout_seconds.map(Duration::from_secs);
+    let idle_timeout = limits.idle_timeout_seconds.map(Duration::from_secs);
+    if timeout.is_none() && idle_timeout.is_none() {
+        return;
+    }
+
+    let host = host.clone();
+    let run_id = run_id.to_string();
+    let started_at = Instant::now();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(1));
+
+        let awaiting_approval = host
+            .agent_runtime()
+            .processes_by_agent_id
+            .lock()
+            .ok()
+            .and_then(|guard| {
+                guard
+                    .get(&agent_id)
+                    .filter(|process| {
+                        process.run_id == run_id && process.state == AgentRunState::Running
+                    })
+                    .map(|process| !process.pending_approvals.is_empty())
+            });
+        let Some(awaiting_approval) = awaiting_approval else {
+            return;
+        };
+        // Waiting for a human to approve a tool call does not count as idle.
+        if awaiting_approval {
+            activity.touch();
+        }
+
+        let reason = if timeout.is_some_and(|timeout| started_at.elapsed() >= timeout) {
+            AgentTerminationReason::Timeout
+        } else if idle_timeout.is_some_and(|idle_timeout| activity.idle_for() >= idle_timeout) {
+            AgentTerminationReason::IdleTimeout
+        } else {
+            continue;
+        };
+
+        if let Err(error) = request_agent_stop(&host, agent_id, Some(&run_id), reason, None) {
+            eprintln!("Failed to stop timed out agent {}: {}", agent_id, error);
+        }
+        return;
+    });
+}
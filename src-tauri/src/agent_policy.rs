@@ -0,0 +1,662 @@
+use crate::agent_approvals::{answer_approval_request, AgentApprovalDecision};
+use crate::agent_stream::{ApprovalRequestRecord, ToolCallRecord};
+use crate::commands::{emit_runtime_event, request_agent_stop, AgentTerminationReason};
+use crate::host::Host;
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+
+/// Location of the policy file, relative to the repository root.
+pub const POLICY_FILE_PATH: &str = ".symphony/policy.toml";
+
+/// Tools that change files. Only these are kept inside the repository, and away
+/// from the policy file.
+const WRITE_TOOLS: [&str; 3] = ["editToolCall", "writeToolCall", "deleteToolCall"];
+
+/// What happens when a tool call breaks the policy.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyViolationAction {
+    /// Stop the run.
+    #[default]
+    Stop,
+    /// Hold the tool call until a human approves or denies it. Runs that do not
+    /// ask for approvals are stopped instead, since their tool calls already ran.
+    Pause,
+    /// Deny the tool call and let the agent continue.
+    Deny,
+}
+
+impl PolicyViolationAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            PolicyViolationAction::Stop => "stop",
+            PolicyViolationAction::Pause => "pause",
+            PolicyViolationAction::Deny => "deny",
+        }
+    }
+}
+
+/// The contents of `.symphony/policy.toml`:
+///
+/// ```toml
+/// on_violation = "stop" # or "pause" / "deny"
+///
+/// [tools]
+/// auto_approve = ["readToolCall", "grepToolCall", "lsToolCall", "globToolCall"]
+/// deny = ["deleteToolCall"]
+///
+/// [paths]
+/// allow_outside_repo = false
+/// deny = [".env", ".env.*", "*.pem", "secrets/**"]
+///
+/// [shell]
+/// deny = ['\brm\s+-rf\b', '\bgit\s+push\b.*--force']
+/// ```
+///
+/// Tools are named by the key of the `tool_call` record. Path patterns without a
+/// `/` match the file name in any directory; other patterns match the path relative
+/// to the repository root. `allow_outside_repo` only concerns tools that write, and
+/// the policy file itself can never be written. Shell patterns are regular expressions matched against
+/// the command.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct PolicyFile {
+    on_violation: PolicyViolationAction,
+    tools: ToolRules,
+    paths: PathRules,
+    shell: ShellRules,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ToolRules {
+    auto_approve: Vec<String>,
+    deny: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct PathRules {
+    allow_outside_repo: bool,
+    deny: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ShellRules {
+    deny: Vec<String>,
+}
+
+/// A parsed policy, ready to be evaluated against the tool calls of one run.
+#[derive(Debug)]
+pub struct AgentPolicy {
+    on_violation: PolicyViolationAction,
+    auto_approved_tools: Vec<String>,
+    denied_tools: Vec<String>,
+    allow_outside_repo: bool,
+    denied_paths: Vec<Pattern>,
+    denied_commands: Vec<Regex>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDecision {
+    Approve,
+    /// The policy has no opinion; a human decides.
+    Ask,
+    Violation(PolicyViolation),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    /// The rule that was broken: `tools.deny`, `paths.outside_repo`,
+    /// `paths.policy_file`, `paths.deny` or `shell.deny`.
+    pub rule: &'static str,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentPolicyViolation {
+    pub run_id: String,
+    pub agent_id: i64,
+    pub call_id: String,
+    pub tool: String,
+    pub rule: String,
+    pub detail: String,
+    pub action: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentPolicyViolationPayload {
+    pub run_id: String,
+    pub agent_id: i64,
+    pub call_id: String,
+    pub tool: String,
+    pub rule: String,
+    pub detail: String,
+    pub action: PolicyViolationAction,
+}
+
+impl AgentPolicy {
+    /// Loads the policy of the repository at `repo_path`. Repositories without a
+    /// policy file have no policy; a policy file that cannot be read is an error,
+    /// so a broken policy never lets an agent run unchecked.
+    pub fn load(repo_path: &str) -> Result<Option<AgentPolicy>, String> {
+        let policy_path = Path::new(repo_path).join(POLICY_FILE_PATH);
+        if !policy_path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&policy_path)
+            .map_err(|e| format!("Failed to read {}: {}", POLICY_FILE_PATH, e))?;
+        AgentPolicy::parse(&contents).map(Some)
+    }
+
+    pub fn parse(contents: &str) -> Result<AgentPolicy, String> {
+        let file: PolicyFile =
+            toml::from_str(contents).map_err(|e| format!("Invalid {}: {}", POLICY_FILE_PATH, e))?;
+
+        let denied_paths = file
+            .paths
+            .deny
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern).map_err(|e| {
+                    format!(
+                        "Invalid path pattern {:?} in {}: {}",
+                        pattern, POLICY_FILE_PATH, e
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let denied_commands = file
+            .shell
+            .deny
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    format!(
+                        "Invalid shell pattern {:?} in {}: {}",
+                        pattern, POLICY_FILE_PATH, e
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(AgentPolicy {
+            on_violation: file.on_violation,
+            auto_approved_tools: file.tools.auto_approve,
+            denied_tools: file.tools.deny,
+            allow_outside_repo: file.paths.allow_outside_repo,
+            denied_paths,
+            denied_commands,
+        })
+    }
+
+    /// Evaluates a `tool_call` value against the policy. Deny rules are checked
+    /// before auto-approval, so an auto-approved tool still cannot read `.env`.
+    pub fn evaluate(&self, tool_call: &Value, repo_root: &Path) -> PolicyDecision {
+        let tool = tool_kind(tool_call).unwrap_or_default();
+        let args = tool_call.get(tool).and_then(|call| call.get("args"));
+
+        if self.denied_tools.iter().any(|denied| denied == tool) {
+            return PolicyDecision::Violation(PolicyViolation {
+                rule: "tools.deny",
+                detail: format!("{} is not allowed", tool),
+            });
+        }
+
+        let writes = WRITE_TOOLS.contains(&tool);
+        for path in args.map(tool_call_paths).unwrap_or_default() {
+            if let Some(violation) = self.check_path(path, repo_root, writes) {
+                return PolicyDecision::Violation(violation);
+            }
+        }
+
+        if let Some(command) = args
+            .and_then(|args| args.get("command"))
+            .and_then(Value::as_str)
+        {
+            if let Some(pattern) = self
+                .denied_commands
+                .iter()
+                .find(|pattern| pattern.is_match(command))
+            {
+                return PolicyDecision::Violation(PolicyViolation {
+                    rule: "shell.deny",
+                    detail: format!("Command {:?} matches {:?}", command, pattern.as_str()),
+                });
+            }
+        }
+
+        if self
+            .auto_approved_tools
+            .iter()
+            .any(|approved| approved == tool)
+        {
+            PolicyDecision::Approve
+        } else {
+            PolicyDecision::Ask
+        }
+    }
+
+    fn check_path(&self, path: &str, repo_root: &Path, writes: bool) -> Option<PolicyViolation> {
+        // Agents on Windows report paths with backslashes.
+        let path = path.replace('\\', "/");
+        let absolute_path = normalize_path(&repo_root.join(&path));
+        let Ok(relative_path) = absolute_path.strip_prefix(normalize_path(repo_root)) else {
+            return (writes && !self.allow_outside_repo).then(|| PolicyViolation {
+                rule: "paths.outside_repo",
+                detail: format!("{} is outside the repository", path),
+            });
+        };
+
+        let relative_path = relative_path.to_string_lossy().replace('\\', "/");
+        // Otherwise an unattended agent could rewrite its own policy.
+        if writes && relative_path == POLICY_FILE_PATH {
+            return Some(PolicyViolation {
+                rule: "paths.policy_file",
+                detail: format!("{} cannot be changed by agents", POLICY_FILE_PATH),
+            });
+        }
+        let file_name = relative_path.rsplit('/').next().unwrap_or_default();
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let pattern = self.denied_paths.iter().find(|pattern| {
+            if pattern.as_str().contains('/') {
+                pattern.matches_with(&relative_path, options)
+            } else {
+                pattern.matches_with(file_name, options)
+            }
+        })?;
+        Some(PolicyViolation {
+            rule: "paths.deny",
+            detail: format!("{} matches {:?}", relative_path, pattern.as_str()),
+        })
+    }
+}
+
+/// The tool kind of a `tool_call` value, e.g. `editToolCall`.
+fn tool_kind(tool_call: &Value) -> Option<&str> {
+    tool_call
+        .as_object()
+        .and_then(|call| call.keys().next())
+        .map(String::as_str)
+}
+
+fn tool_call_paths(args: &Value) -> Vec<&str> {
+    let mut paths: Vec<&str> = args
+        .get("path")
+        .and_then(Value::as_str)
+        .into_iter()
+        .collect();
+    if let Some(more_paths) = args.get("paths").and_then(Value::as_array) {
+        paths.extend(more_paths.iter().filter_map(Value::as_str));
+    }
+    paths
+}
+
+/// Resolves `.` and `..` without touching the file system, since the tool call may
+/// create the path.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Applies the policy to a tool call the agent is waiting on. Returns whether a
+/// human still needs to approve or deny it.
+pub(crate) fn review_approval_request(
+    host: &Host,
+    agent_id: i64,
+    run_id: &str,
+    working_directory: &str,
+    policy: &AgentPolicy,
+    record: &ApprovalRequestRecord,
+) -> bool {
+    let decision = policy.evaluate(&record.tool_call, Path::new(working_directory));
+    let (decision, reason) = match decision {
+        PolicyDecision::Ask => return true,
+        PolicyDecision::Approve => (
+            AgentApprovalDecision::Approved,
+            "Approved by policy".to_string(),
+        ),
+        PolicyDecision::Violation(violation) => {
+            let action = policy.on_violation;
+            record_policy_violation(
+                host,
+                agent_id,
+                run_id,
+                &record.call_id,
+                &record.tool_call,
+                &violation,
+                action,
+            );
+            if action == PolicyViolationAction::Pause {
+                return true;
+            }
+            if action == PolicyViolationAction::Stop {
+                stop_for_policy_violation(host, agent_id, run_id);
+            }
+            (AgentApprovalDecision::Denied, violation.detail)
+        }
+    };
+
+    if let Err(error) =
+        answer_approval_request(host, agent_id, &record.call_id, decision, Some(reason))
+    {
+        eprintln!(
+            "Failed to answer approval request {} of run {}: {}",
+            record.call_id, run_id, error
+        );
+    }
+    false
+}
+
+/// Checks a tool call that started without asking for approval. It cannot be held
+/// back any more, so any violation stops the run.
+pub(crate) fn review_started_tool_call(
+    host: &Host,
+    agent_id: i64,
+    run_id: &str,
+    working_directory: &str,
+    policy: &AgentPolicy,
+    record: &ToolCallRecord,
+) {
+    let PolicyDecision::Violation(violation) =
+        policy.evaluate(&record.tool_call, Path::new(working_directory))
+    else {
+        return;
+    };
+    record_policy_violation(
+        host,
+        agent_id,
+        run_id,
+        &record.call_id,
+        &record.tool_call,
+        &violation,
+        PolicyViolationAction::Stop,
+    );
+    stop_for_policy_violation(host, agent_id, run_id);
+}
+
+fn stop_for_policy_violation(host: &Host, agent_id: i64, run_id: &str) {
+    if let Err(error) = request_agent_stop(
+        host,
+        agent_id,
+        Some(run_id),
+        AgentTerminationReason::PolicyViolation,
+        None,
+    ) {
+        eprintln!(
+            "Failed to stop agent {} after a policy violation: {}",
+            agent_id, error
+        );
+    }
+}
+
+fn record_policy_violation(
+    host: &Host,
+    agent_id: i64,
+    run_id: &str,
+    call_id: &str,
+    tool_call: &Value,
+    violation: &PolicyViolation,
+    action: PolicyViolationAction,
+) {
+    let tool = tool_kind(tool_call).unwrap_or_default().to_string();
+    {
+        let result = host
+            .db()
+            .conn
+            .lock()
+            .map_err(|e| e.to_string())
+            .and_then(|conn| {
+                conn.execute(
+                    "INSERT INTO agent_policy_violations
+                     (run_id, agent_id, call_id, tool, rule, detail, action)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    rusqlite::params![
+                        run_id,
+                        agent_id,
+                        call_id,
+                        tool,
+                        violation.rule,
+                        violation.detail,
+                        action.as_str()
+                    ],
+                )
+                .map_err(|e| e.to_string())
+            });
+        if let Err(error) = result {
+            eprintln!(
+                "Failed to record policy violation of run {}: {}",
+                run_id, error
+            );
+        }
+    }
+
+    emit_runtime_event(
+        host,
+        "repo-agent-policy-violation",
+        AgentPolicyViolationPayload {
+            run_id: run_id.to_string(),
+            agent_id,
+            call_id: call_id.to_string(),
+            tool,
+            rule: violation.rule.to_string(),
+            detail: violation.detail.clone(),
+            action,
+        },
+    );
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_agent_policy_violations(
+    host: Host,
+    agent_id: i64,
+    limit: Option<u32>,
+) -> Result<Vec<AgentPolicyViolation>, String> {
+    let clamped_limit = limit.unwrap_or(50).clamp(1, 500);
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT run_id, agent_id, call_id, tool, rule, detail, action, created_at
+             FROM agent_policy_violations
+             WHERE agent_id = ?1
+             ORDER BY id DESC
+             LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let violations = stmt
+        .query_map(rusqlite::params![agent_id, clamped_limit], |row| {
+            Ok(AgentPolicyViolation {
+                run_id: row.get(0)?,
+                agent_id: row.get(1)?,
+                call_id: row.get(2)?,
+                tool: row.get(3)?,
+                rule: row.get(4)?,
+                detail: row.get(5)?,
+                action: row.get(6)?,
+                created_at: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const POLICY: &str = r#"
+        on_violation = "deny"
+
+        [tools]
+        auto_approve = ["readToolCall", "grepToolCall"]
+        deny = ["deleteToolCall"]
+
+        [paths]
+        deny = [".env", ".env.*", "secrets/**"]
+
+        [shell]
+        deny = ['\brm\s+-rf\b', '\bgit\s+push\b.*--force']
+    "#;
+
+    fn evaluate(tool_call: Value) -> PolicyDecision {
+        AgentPolicy::parse(POLICY)
+            .unwrap()
+            .evaluate(&tool_call, Path::new("/work/repo"))
+    }
+
+    fn violated_rule(tool_call: Value) -> Option<&'static str> {
+        match evaluate(tool_call) {
+            PolicyDecision::Violation(violation) => Some(violation.rule),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn auto_approves_listed_tools_and_asks_for_the_rest() {
+        assert_eq!(
+            evaluate(json!({"readToolCall": {"args": {"path": "src/main.rs"}}})),
+            PolicyDecision::Approve
+        );
+        assert_eq!(
+            evaluate(json!({"editToolCall": {"args": {"path": "/work/repo/src/main.rs"}}})),
+            PolicyDecision::Ask
+        );
+    }
+
+    #[test]
+    fn blocks_denied_tools() {
+        assert_eq!(
+            violated_rule(json!({"deleteToolCall": {"args": {"path": "README.md"}}})),
+            Some("tools.deny")
+        );
+    }
+
+    #[test]
+    fn blocks_paths_outside_the_repository() {
+        assert_eq!(
+            violated_rule(json!({"editToolCall": {"args": {"path": "/etc/hosts"}}})),
+            Some("paths.outside_repo")
+        );
+        assert_eq!(
+            violated_rule(json!({"editToolCall": {"args": {"path": "src/../../other/file"}}})),
+            Some("paths.outside_repo")
+        );
+        assert_eq!(
+            violated_rule(json!({"editToolCall": {"args": {"path": "/work/repository/file"}}})),
+            Some("paths.outside_repo")
+        );
+    }
+
+    #[test]
+    fn allows_reading_outside_the_repository() {
+        assert_eq!(
+            evaluate(json!({"readToolCall": {"args": {"path": "/home/user/.gitconfig"}}})),
+            PolicyDecision::Approve
+        );
+        assert_eq!(
+            evaluate(json!({"grepToolCall": {"args": {"paths": ["/usr/include/stdio.h"]}}})),
+            PolicyDecision::Approve
+        );
+    }
+
+    #[test]
+    fn blocks_writes_to_the_policy_file() {
+        assert_eq!(
+            violated_rule(json!({"editToolCall": {"args": {"path": ".symphony/policy.toml"}}})),
+            Some("paths.policy_file")
+        );
+        assert_eq!(
+            violated_rule(
+                json!({"writeToolCall": {"args": {"path": "/work/repo/src/../.symphony/policy.toml"}}})
+            ),
+            Some("paths.policy_file")
+        );
+        assert_eq!(
+            evaluate(json!({"readToolCall": {"args": {"path": ".symphony/policy.toml"}}})),
+            PolicyDecision::Approve
+        );
+    }
+
+    #[test]
+    fn blocks_denied_paths_even_for_auto_approved_tools() {
+        assert_eq!(
+            violated_rule(json!({"readToolCall": {"args": {"path": "config/.env"}}})),
+            Some("paths.deny")
+        );
+        assert_eq!(
+            violated_rule(json!({"editToolCall": {"args": {"path": ".env.local"}}})),
+            Some("paths.deny")
+        );
+        assert_eq!(
+            violated_rule(json!({"editToolCall": {"args": {"path": "/work/repo/secrets/key"}}})),
+            Some("paths.deny")
+        );
+        assert_eq!(
+            violated_rule(json!({"editToolCall": {"args": {"path": "src/secrets/key"}}})),
+            None
+        );
+        assert_eq!(
+            violated_rule(json!({"editToolCall": {"args": {"path": "src/environment.ts"}}})),
+            None
+        );
+    }
+
+    #[test]
+    fn blocks_denied_shell_commands() {
+        assert_eq!(
+            violated_rule(json!({"shellToolCall": {"args": {"command": "rm -rf build"}}})),
+            Some("shell.deny")
+        );
+        assert_eq!(
+            violated_rule(
+                json!({"shellToolCall": {"args": {"command": "git push origin main --force"}}})
+            ),
+            Some("shell.deny")
+        );
+        assert_eq!(
+            evaluate(json!({"shellToolCall": {"args": {"command": "git push origin main"}}})),
+            PolicyDecision::Ask
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_policies() {
+        assert!(AgentPolicy::parse("on_violation = \"explode\"").is_err());
+        assert!(AgentPolicy::parse("[shell]\ndeny = ['(']").is_err());
+        assert!(AgentPolicy::parse("[tools]\nallow = []").is_err());
+    }
+
+    #[test]
+    fn allows_outside_paths_when_configured() {
+        let policy = AgentPolicy::parse("[paths]\nallow_outside_repo = true").unwrap();
+        assert_eq!(
+            policy.evaluate(
+                &json!({"editToolCall": {"args": {"path": "/tmp/scratch"}}}),
+                Path::new("/work/repo")
+            ),
+            PolicyDecision::Ask
+        );
+    }
+}
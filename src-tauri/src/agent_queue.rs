@@ -0,0 +1,269 @@
+use crate::agent_limits::AgentRunLimits;
+use crate::agent_scheduler::queued_agent_process;
+use crate::commands::{
+    emit_agent_done, emit_runtime_event, start_agent_run, AgentDonePayload, AgentRunOutcome,
+    AgentStreamPayload,
+};
+use crate::host::Host;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// A prompt for an agent, either started right away or waiting in the agent's queue.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentPromptRequest {
+    pub run_id: String,
+    pub agent_id: i64,
+    /// The agent's repo, which the per-repo concurrency limit applies to.
+    pub repo_id: i64,
+    pub repo_path: String,
+    pub prompt: String,
+    pub model: Option<String>,
+    pub force_approve: Option<bool>,
+    pub simulate_mode: Option<bool>,
+    pub isolated_workspace: Option<bool>,
+    pub resume: Option<bool>,
+    pub limits: Option<AgentRunLimits>,
+    /// Runs with a higher priority are started first when runs wait for the
+    /// scheduler's concurrency limits.
+    pub priority: i64,
+    pub queued_at: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentQueueChangedPayload {
+    pub agent_id: i64,
+    pub queue: Vec<AgentPromptRequest>,
+}
+
+/// Queues the prompt when the agent is busy or already has prompts waiting, so
+/// prompts always run in the order they were sent. Otherwise claims the agent for
+/// the prompt and returns the request back, to be started immediately.
+pub(crate) fn queue_agent_prompt_if_busy(
+    host: &Host,
+    request: AgentPromptRequest,
+) -> Result<Option<AgentPromptRequest>, String> {
+    let state = host.agent_runtime();
+    let agent_id = request.agent_id;
+    let queue = {
+        let mut queue_guard = state
+            .queued_prompts_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+        let mut process_guard = state
+            .processes_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+
+        let has_waiting_prompts = queue_guard
+            .get(&agent_id)
+            .is_some_and(|queue| !queue.is_empty());
+        if !process_guard.contains_key(&agent_id) && !has_waiting_prompts {
+            process_guard.insert(agent_id, queued_agent_process(&request));
+            return Ok(Some(request));
+        }
+
+        let queue = queue_guard.entry(agent_id).or_default();
+        if queue.iter().any(|queued| queued.run_id == request.run_id) {
+            return Err("An agent run with this id already exists".to_string());
+        }
+        queue.push_back(request);
+        queue.iter().cloned().collect::<Vec<_>>()
+    };
+
+    emit_queue_changed(host, agent_id, queue);
+    Ok(None)
+}
+
+/// Starts the oldest queued prompt for the agent, if any. Prompts that fail to
+/// start are reported as finished runs and the next one is tried.
+pub(crate) fn start_next_queued_prompt(host: &Host, agent_id: i64) {
+    while let Some(next) = pop_queued_prompt(host, agent_id) {
+        let run_id = next.run_id.clone();
+        let Err(error) = start_agent_run(host, next) else {
+            return;
+        };
+        report_failed_start(host, agent_id, run_id, error);
+    }
+}
+
+/// Reports a run that could not be started, for prompts whose caller is gone.
+pub(crate) fn report_failed_start(host: &Host, agent_id: i64, run_id: String, error: String) {
+    emit_runtime_event(
+        host,
+        "repo-agent-stderr",
+        AgentStreamPayload {
+            run_id: run_id.clone(),
+            agent_id,
+            line: error,
+        },
+    );
+    emit_agent_done(
+        host,
+        AgentDonePayload {
+            run_id,
+            agent_id,
+            success: false,
+            outcome: AgentRunOutcome::Failed,
+            exit_code: None,
+            termination_reason: None,
+        },
+    );
+}
+
+/// Takes the oldest queued prompt and claims the agent for it, unless another run
+/// already claimed the agent; that run starts the queue once it finishes.
+fn pop_queued_prompt(host: &Host, agent_id: i64) -> Option<AgentPromptRequest> {
+    let state = host.agent_runtime();
+    let (next, queue) = {
+        let mut queue_guard = state.queued_prompts_by_agent_id.lock().ok()?;
+        let mut process_guard = state.processes_by_agent_id.lock().ok()?;
+        if process_guard.contains_key(&agent_id) {
+            return None;
+        }
+        let queue = queue_guard.get_mut(&agent_id)?;
+        let next = queue.pop_front()?;
+        process_guard.insert(agent_id, queued_agent_process(&next));
+        let remaining = queue.iter().cloned().collect::<Vec<_>>();
+        if queue.is_empty() {
+            queue_guard.remove(&agent_id);
+        }
+        (next, remaining)
+    };
+
+    emit_queue_changed(host, agent_id, queue);
+    Some(next)
+}
+
+/// Refuses while any of the agents has a run, including one still waiting for the
+/// scheduler, since those hold a claim on the agent too.
+pub(crate) fn ensure_agents_idle(
+    host: &Host,
+    agent_ids: &[i64],
+    busy_error: &str,
+) -> Result<(), String> {
+    let process_guard = host
+        .agent_runtime()
+        .processes_by_agent_id
+        .lock()
+        .map_err(|e| e.to_string())?;
+    if agent_ids
+        .iter()
+        .any(|agent_id| process_guard.contains_key(agent_id))
+    {
+        return Err(busy_error.to_string());
+    }
+    Ok(())
+}
+
+/// Drops the prompts still queued for deleted agents, so they do not fail later.
+pub(crate) fn drop_queued_prompts(host: &Host, agent_ids: &[i64]) {
+    let dropped_agent_ids: Vec<i64> = {
+        let Ok(mut queue_guard) = host.agent_runtime().queued_prompts_by_agent_id.lock() else {
+            return;
+        };
+        agent_ids
+            .iter()
+            .filter(|agent_id| queue_guard.remove(agent_id).is_some())
+            .copied()
+            .collect()
+    };
+    for agent_id in dropped_agent_ids {
+        emit_queue_changed(host, agent_id, Vec::new());
+    }
+}
+
+fn emit_queue_changed(host: &Host, agent_id: i64, queue: Vec<AgentPromptRequest>) {
+    emit_runtime_event(
+        host,
+        "repo-agent-queue-changed",
+        AgentQueueChangedPayload { agent_id, queue },
+    );
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_agent_queue(host: Host, agent_id: i64) -> Result<Vec<AgentPromptRequest>, String> {
+    let state = host.agent_runtime();
+    let queue_guard = state
+        .queued_prompts_by_agent_id
+        .lock()
+        .map_err(|e| e.to_string())?;
+    Ok(queue_guard
+        .get(&agent_id)
+        .map(|queue| queue.iter().cloned().collect())
+        .unwrap_or_default())
+}
+
+/// Reorders the agent's queue. `run_ids` must list every queued prompt exactly once.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn reorder_agent_queue(
+    host: Host,
+    agent_id: i64,
+    run_ids: Vec<String>,
+) -> Result<Vec<AgentPromptRequest>, String> {
+    let state = host.agent_runtime();
+    let queue = {
+        let mut queue_guard = state
+            .queued_prompts_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+        let Some(queue) = queue_guard.get_mut(&agent_id) else {
+            if run_ids.is_empty() {
+                return Ok(Vec::new());
+            }
+            return Err("No prompts are queued for this agent".to_string());
+        };
+
+        if run_ids.len() != queue.len() {
+            return Err("The new order must list every queued prompt exactly once".to_string());
+        }
+
+        let mut remaining = queue.clone();
+        let mut reordered = VecDeque::with_capacity(queue.len());
+        for run_id in &run_ids {
+            let position = remaining
+                .iter()
+                .position(|queued| &queued.run_id == run_id)
+                .ok_or(format!("Queued prompt {} not found", run_id))?;
+            reordered.extend(remaining.remove(position));
+        }
+        *queue = reordered;
+        queue.iter().cloned().collect::<Vec<_>>()
+    };
+
+    emit_queue_changed(&host, agent_id, queue.clone());
+    Ok(queue)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn cancel_queued_prompt(
+    host: Host,
+    agent_id: i64,
+    run_id: String,
+) -> Result<Vec<AgentPromptRequest>, String> {
+    let state = host.agent_runtime();
+    let queue = {
+        let mut queue_guard = state
+            .queued_prompts_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+        let queue = queue_guard
+            .get_mut(&agent_id)
+            .ok_or("No prompts are queued for this agent".to_string())?;
+        let position = queue
+            .iter()
+            .position(|queued| queued.run_id == run_id)
+            .ok_or("Queued prompt not found".to_string())?;
+        queue.remove(position);
+
+        let remaining = queue.iter().cloned().collect::<Vec<_>>();
+        if queue.is_empty() {
+            queue_guard.remove(&agent_id);
+        }
+        remaining
+    };
+
+    emit_queue_changed(&host, agent_id, queue.clone());
+    Ok(queue)
+}
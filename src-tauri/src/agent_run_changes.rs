@@ -0,0 +1,333 @@
+use crate::commands::run_git_command;
+use crate::host::Host;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunChangedFile {
+    pub path: String,
+    /// Single-letter `git diff --name-status` code: `A`, `M`, `D` or `T`.
+    pub status: String,
+    /// `None` for binary files.
+    pub additions: Option<u64>,
+    pub deletions: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunChanges {
+    pub run_id: String,
+    pub files: Vec<AgentRunChangedFile>,
+    pub patch: String,
+    pub reverted_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunChangesPayload {
+    pub run_id: String,
+    pub agent_id: i64,
+    pub files: Vec<AgentRunChangedFile>,
+}
+
+fn temporary_file_path(purpose: &str, extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "symphony-{}-{:016x}.{}",
+        purpose,
+        rand::random::<u64>(),
+        extension
+    ))
+}
+
+fn run_git_with_index(path: &str, index_file: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(path)
+        .env("GIT_INDEX_FILE", index_file)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if stderr.is_empty() {
+            "Git command failed".to_string()
+        } else {
+            stderr
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Writes the current working tree, including untracked but not ignored files, as a
+/// tree object and returns its id. A copy of the real index is used, so neither the
+/// index nor the working tree are touched and unchanged files are not rehashed.
+pub(crate) fn snapshot_working_tree(path: &str) -> Result<String, String> {
+    let real_index = run_git_command(
+        path,
+        &[
+            "rev-parse".to_string(),
+            "--git-path".to_string(),
+            "index".to_string(),
+        ],
+    )?;
+    let real_index = Path::new(path).join(real_index.trim());
+    let temporary_index = temporary_file_path("snapshot", "index");
+
+    if real_index.exists() {
+        fs::copy(&real_index, &temporary_index)
+            .map_err(|e| format!("Failed to copy git index: {}", e))?;
+    }
+
+    let tree = run_git_with_index(path, &temporary_index, &["add", "--all"])
+        .and_then(|_| run_git_with_index(path, &temporary_index, &["write-tree"]));
+    let _ = fs::remove_file(&temporary_index);
+
+    Ok(tree?.trim().to_string())
+}
+
+/// Diffs the working tree against the snapshot taken before the run and stores the
+/// changed files and patch on the run.
+pub(crate) fn record_agent_run_changes(
+    host: &Host,
+    run_id: &str,
+    working_directory: &str,
+    base_tree: &str,
+) -> Result<Vec<AgentRunChangedFile>, String> {
+    let result_tree = snapshot_working_tree(working_directory)?;
+
+    let diff_args = |format: &str| {
+        vec![
+            "diff".to_string(),
+            "--no-color".to_string(),
+            "--no-renames".to_string(),
+            format.to_string(),
+            base_tree.to_string(),
+            result_tree.clone(),
+        ]
+    };
+    let patch = run_git_command(working_directory, &diff_args("--binary"))?;
+    let name_status = run_git_command(working_directory, &diff_args("--name-status"))?;
+    let numstat = run_git_command(working_directory, &diff_args("--numstat"))?;
+    let files = parse_changed_files(&name_status, &numstat);
+
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE agent_runs SET result_tree = ?1, patch = ?2 WHERE run_id = ?3",
+        rusqlite::params![result_tree, patch, run_id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_run_changed_files WHERE run_id = ?1",
+        rusqlite::params![run_id],
+    )
+    .map_err(|e| e.to_string())?;
+    for file in &files {
+        conn.execute(
+            "INSERT INTO agent_run_changed_files (run_id, path, status, additions, deletions)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                run_id,
+                file.path,
+                file.status,
+                file.additions,
+                file.deletions
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    Ok(files)
+}
+
+fn parse_changed_files(name_status: &str, numstat: &str) -> Vec<AgentRunChangedFile> {
+    let line_counts: Vec<(Option<u64>, Option<u64>, &str)> = numstat
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let additions = parts.next()?.parse().ok();
+            let deletions = parts.next()?.parse().ok();
+            Some((additions, deletions, parts.next()?))
+        })
+        .collect();
+
+    name_status
+        .lines()
+        .filter_map(|line| {
+            let (status, path) = line.split_once('\t')?;
+            let (additions, deletions) = line_counts
+                .iter()
+                .find(|(_, _, counted_path)| *counted_path == path)
+                .map(|(additions, deletions, _)| (*additions, *deletions))
+                .unwrap_or_default();
+            Some(AgentRunChangedFile {
+                path: path.to_string(),
+                status: status.to_string(),
+                additions,
+                deletions,
+            })
+        })
+        .collect()
+}
+
+fn load_agent_run_changes(conn: &Connection, run_id: &str) -> Result<AgentRunChanges, String> {
+    let (patch, reverted_at): (Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT patch, reverted_at FROM agent_runs WHERE run_id = ?1",
+            rusqlite::params![run_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Agent run not found".to_string(),
+            other => other.to_string(),
+        })?;
+
+    let Some(patch) = patch else {
+        return Err("No changes were recorded for this run".to_string());
+    };
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT path, status, additions, deletions
+             FROM agent_run_changed_files
+             WHERE run_id = ?1
+             ORDER BY path ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let files = stmt
+        .query_map(rusqlite::params![run_id], |row| {
+            Ok(AgentRunChangedFile {
+                path: row.get(0)?,
+                status: row.get(1)?,
+                additions: row.get(2)?,
+                deletions: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(AgentRunChanges {
+        run_id: run_id.to_string(),
+        files,
+        patch,
+        reverted_at,
+    })
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn get_agent_run_changes(host: Host, run_id: String) -> Result<AgentRunChanges, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    load_agent_run_changes(&conn, &run_id)
+}
+
+/// Reverse-applies the run's patch to the working tree. Later edits to the same
+/// lines make the patch fail to apply, in which case nothing is changed.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn revert_agent_run(host: Host, run_id: String) -> Result<AgentRunChanges, String> {
+    let (changes, working_directory) = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        let changes = load_agent_run_changes(&conn, &run_id)?;
+        let working_directory: String = conn
+            .query_row(
+                "SELECT working_directory FROM agent_runs WHERE run_id = ?1",
+                rusqlite::params![run_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        (changes, working_directory)
+    };
+    if changes.reverted_at.is_some() {
+        return Err("This run has already been reverted".to_string());
+    }
+    if changes.patch.trim().is_empty() {
+        return Err("This run did not change any files".to_string());
+    }
+
+    let patch_path = temporary_file_path("revert", "patch");
+    fs::write(&patch_path, &changes.patch).map_err(|e| format!("Failed to write patch: {}", e))?;
+    let apply_result = run_git_command(
+        &working_directory,
+        &[
+            "apply".to_string(),
+            "--reverse".to_string(),
+            "--binary".to_string(),
+            patch_path.to_string_lossy().to_string(),
+        ],
+    );
+    let _ = fs::remove_file(&patch_path);
+    apply_result.map_err(|error| format!("Could not revert this run: {}", error))?;
+
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE agent_runs SET reverted_at = CURRENT_TIMESTAMP
+         WHERE run_id = ?1 AND reverted_at IS NULL",
+        rusqlite::params![run_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    load_agent_run_changes(&conn, &run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_statuses_with_line_counts() {
+        let files = parse_changed_files(
+            "M\tsrc/main.rs\nA\tsrc/new.rs\nD\told.txt\n",
+            "3\t1\tsrc/main.rs\n10\t0\tsrc/new.rs\n0\t4\told.txt\n",
+        );
+        let summary: Vec<_> = files
+            .iter()
+            .map(|file| {
+                (
+                    file.path.as_str(),
+                    file.status.as_str(),
+                    file.additions,
+                    file.deletions,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("src/main.rs", "M", Some(3), Some(1)),
+                ("src/new.rs", "A", Some(10), Some(0)),
+                ("old.txt", "D", Some(0), Some(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_line_counts_of_binary_files_empty() {
+        let files = parse_changed_files("M\tlogo.png\n", "-\t-\tlogo.png\n");
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].additions, None);
+        assert_eq!(files[0].deletions, None);
+    }
+
+    #[test]
+    fn keeps_files_without_line_counts() {
+        let files = parse_changed_files("M\tsrc/main.rs\n", "");
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "src/main.rs");
+        assert_eq!(files[0].additions, None);
+    }
+
+    #[test]
+    fn keeps_paths_with_spaces_and_skips_malformed_lines() {
+        let files = parse_changed_files(
+            "M\tdocs/read me.md\nnot a status line\n",
+            "2\t2\tdocs/read me.md\n",
+        );
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "docs/read me.md");
+        assert_eq!(files[0].additions, Some(2));
+    }
+}
@@ -0,0 +1,336 @@
+use crate::agent_backend::AgentStopSignal;
+use crate::agent_queue::{report_failed_start, start_next_queued_prompt, AgentPromptRequest};
+use crate::commands::{
+    emit_agent_done, emit_run_state, emit_runtime_event, get_repo_by_id, launch_agent_run,
+    AgentDonePayload, AgentRunOutcome, AgentRunState, AgentTerminationReason, Repo,
+    RunningAgentProcess,
+};
+use crate::host::Host;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+const MAX_CONCURRENT_RUNS_SETTING: &str = "max_concurrent_runs";
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SchedulerSettings {
+    /// Most agent runs that may run at once across all repos; unlimited when `None`.
+    pub max_concurrent_runs: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledRunsChangedPayload {
+    /// Runs waiting to start, in the order they will be considered.
+    pub runs: Vec<AgentPromptRequest>,
+}
+
+fn load_max_concurrent_runs(conn: &Connection) -> Result<Option<u32>, String> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            rusqlite::params![MAX_CONCURRENT_RUNS_SETTING],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(value.and_then(|value| value.parse().ok()))
+}
+
+fn load_repo_run_limits(conn: &Connection) -> Result<HashMap<i64, u32>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, max_concurrent_runs FROM repos WHERE max_concurrent_runs IS NOT NULL")
+        .map_err(|e| e.to_string())?;
+    let limits = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashMap<_, _>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(limits)
+}
+
+fn validate_run_limit(max_concurrent_runs: Option<u32>) -> Result<(), String> {
+    if max_concurrent_runs == Some(0) {
+        return Err("The concurrency limit must be greater than zero".to_string());
+    }
+    Ok(())
+}
+
+/// The entry that claims an agent for a run in the `Queued` state, so a concurrent
+/// prompt goes to the agent's own queue.
+pub(crate) fn queued_agent_process(request: &AgentPromptRequest) -> RunningAgentProcess {
+    RunningAgentProcess {
+        run_id: request.run_id.clone(),
+        repo_id: request.repo_id,
+        state: AgentRunState::Queued,
+        pid: None,
+        stop_signal: AgentStopSignal::Terminate,
+        stop_reason: None,
+        stdin: None,
+        accepts_input: false,
+        pending_approvals: Vec::new(),
+    }
+}
+
+/// Claims the agent for the run, unless the agent queue already claimed it for this
+/// run, and adds the run to the scheduler's waiting list.
+pub(crate) fn schedule_agent_run(host: &Host, request: AgentPromptRequest) -> Result<(), String> {
+    let agent_id = request.agent_id;
+    let run_id = request.run_id.clone();
+    let runs = {
+        let state = host.agent_runtime();
+        let mut scheduled_guard = state.scheduled_runs.lock().map_err(|e| e.to_string())?;
+        let mut process_guard = state
+            .processes_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+        match process_guard.get(&agent_id) {
+            Some(process)
+                if process.run_id == run_id
+                    && process.state == AgentRunState::Queued
+                    && !scheduled_guard.iter().any(|run| run.run_id == run_id) => {}
+            Some(_) => return Err("This agent is already running".to_string()),
+            None => {
+                process_guard.insert(agent_id, queued_agent_process(&request));
+            }
+        }
+        scheduled_guard.push(request);
+        sort_scheduled_runs(&mut scheduled_guard);
+        scheduled_guard.clone()
+    };
+
+    emit_run_state(host, agent_id, &run_id, AgentRunState::Queued);
+    emit_scheduled_runs_changed(host, runs);
+    Ok(())
+}
+
+fn sort_scheduled_runs(runs: &mut [AgentPromptRequest]) {
+    runs.sort_by_key(|run| (Reverse(run.priority), run.queued_at));
+}
+
+/// Starts waiting runs, highest priority first, for as long as the global and
+/// per-repo limits allow. Returns the error of `caller_run_id` if that run failed
+/// to start; other runs that fail are reported as finished runs.
+pub(crate) fn dispatch_scheduled_runs(
+    host: &Host,
+    caller_run_id: Option<&str>,
+) -> Result<(), String> {
+    let mut caller_result = Ok(());
+    loop {
+        let runs = take_startable_runs(host)?;
+        if runs.is_empty() {
+            return caller_result;
+        }
+
+        for request in runs {
+            let agent_id = request.agent_id;
+            let run_id = request.run_id.clone();
+            emit_run_state(host, agent_id, &run_id, AgentRunState::Starting);
+            let Err(error) = launch_agent_run(host, request) else {
+                continue;
+            };
+            if caller_run_id == Some(run_id.as_str()) {
+                caller_result = Err(error);
+            } else {
+                report_failed_start(host, agent_id, run_id, error);
+            }
+            // Prompts may have queued behind the run while it waited.
+            start_next_queued_prompt(host, agent_id);
+        }
+    }
+}
+
+/// Moves the waiting runs that fit within the limits to `Starting` and removes them
+/// from the waiting list.
+fn take_startable_runs(host: &Host) -> Result<Vec<AgentPromptRequest>, String> {
+    let (max_concurrent_runs, repo_run_limits) = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        (
+            load_max_concurrent_runs(&conn)?,
+            load_repo_run_limits(&conn)?,
+        )
+    };
+
+    let (startable, remaining) = {
+        let state = host.agent_runtime();
+        let mut scheduled_guard = state.scheduled_runs.lock().map_err(|e| e.to_string())?;
+        let mut process_guard = state
+            .processes_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+        if scheduled_guard.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut active_runs = 0;
+        let mut active_runs_by_repo: HashMap<i64, u32> = HashMap::new();
+        for process in process_guard.values() {
+            if process.state != AgentRunState::Queued {
+                active_runs += 1;
+                *active_runs_by_repo.entry(process.repo_id).or_default() += 1;
+            }
+        }
+
+        let mut startable = Vec::new();
+        let mut remaining = Vec::new();
+        for request in std::mem::take(&mut *scheduled_guard) {
+            let has_global_capacity = max_concurrent_runs.is_none_or(|limit| active_runs < limit);
+            let repo_active_runs = active_runs_by_repo
+                .get(&request.repo_id)
+                .copied()
+                .unwrap_or_default();
+            let has_repo_capacity = repo_run_limits
+                .get(&request.repo_id)
+                .is_none_or(|limit| repo_active_runs < *limit);
+            if !has_global_capacity || !has_repo_capacity {
+                remaining.push(request);
+                continue;
+            }
+
+            // Runs cancelled while waiting are no longer tracked and are dropped.
+            let Some(process) = process_guard.get_mut(&request.agent_id).filter(|process| {
+                process.run_id == request.run_id && process.state == AgentRunState::Queued
+            }) else {
+                continue;
+            };
+            process.state = AgentRunState::Starting;
+            active_runs += 1;
+            *active_runs_by_repo.entry(request.repo_id).or_default() += 1;
+            startable.push(request);
+        }
+        *scheduled_guard = remaining.clone();
+        (startable, remaining)
+    };
+
+    if !startable.is_empty() {
+        emit_scheduled_runs_changed(host, remaining);
+    }
+    Ok(startable)
+}
+
+/// Cancels the agent's run if it is waiting to be scheduled. Returns whether it was.
+pub(crate) fn cancel_scheduled_run(
+    host: &Host,
+    agent_id: i64,
+    expected_run_id: Option<&str>,
+    reason: AgentTerminationReason,
+) -> Result<bool, String> {
+    let (run_id, runs) = {
+        let state = host.agent_runtime();
+        let mut scheduled_guard = state.scheduled_runs.lock().map_err(|e| e.to_string())?;
+        let mut process_guard = state
+            .processes_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+        let is_queued = process_guard.get(&agent_id).is_some_and(|process| {
+            process.state == AgentRunState::Queued
+                && expected_run_id.is_none_or(|run_id| process.run_id == run_id)
+        });
+        if !is_queued {
+            return Ok(false);
+        }
+        let Some(process) = process_guard.remove(&agent_id) else {
+            return Ok(false);
+        };
+        scheduled_guard.retain(|run| run.run_id != process.run_id);
+        (process.run_id, scheduled_guard.clone())
+    };
+
+    emit_scheduled_runs_changed(host, runs);
+    emit_run_state(host, agent_id, &run_id, AgentRunState::Finished);
+    emit_agent_done(
+        host,
+        AgentDonePayload {
+            run_id,
+            agent_id,
+            success: false,
+            outcome: AgentRunOutcome::Cancelled,
+            exit_code: None,
+            termination_reason: Some(reason),
+        },
+    );
+    start_next_queued_prompt(host, agent_id);
+    Ok(true)
+}
+
+fn emit_scheduled_runs_changed(host: &Host, runs: Vec<AgentPromptRequest>) {
+    emit_runtime_event(
+        host,
+        "repo-agent-scheduled-runs-changed",
+        ScheduledRunsChangedPayload { runs },
+    );
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_scheduled_runs(host: Host) -> Result<Vec<AgentPromptRequest>, String> {
+    let scheduled_guard = host
+        .agent_runtime()
+        .scheduled_runs
+        .lock()
+        .map_err(|e| e.to_string())?;
+    Ok(scheduled_guard.clone())
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn get_scheduler_settings(host: Host) -> Result<SchedulerSettings, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    Ok(SchedulerSettings {
+        max_concurrent_runs: load_max_concurrent_runs(&conn)?,
+    })
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn set_scheduler_settings(
+    host: Host,
+    max_concurrent_runs: Option<u32>,
+) -> Result<SchedulerSettings, String> {
+    validate_run_limit(max_concurrent_runs)?;
+    {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        match max_concurrent_runs {
+            Some(limit) => conn.execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                rusqlite::params![MAX_CONCURRENT_RUNS_SETTING, limit.to_string()],
+            ),
+            None => conn.execute(
+                "DELETE FROM settings WHERE key = ?1",
+                rusqlite::params![MAX_CONCURRENT_RUNS_SETTING],
+            ),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+
+    // A raised limit may let waiting runs start.
+    dispatch_scheduled_runs(&host, None)?;
+    Ok(SchedulerSettings {
+        max_concurrent_runs,
+    })
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn set_repo_run_limit(
+    host: Host,
+    repo_id: i64,
+    max_concurrent_runs: Option<u32>,
+) -> Result<Repo, String> {
+    validate_run_limit(max_concurrent_runs)?;
+    let repo = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        let updated_rows = conn
+            .execute(
+                "UPDATE repos SET max_concurrent_runs = ?1 WHERE id = ?2",
+                rusqlite::params![max_concurrent_runs, repo_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated_rows == 0 {
+            return Err("Repository not found".to_string());
+        }
+        get_repo_by_id(&conn, repo_id)?
+    };
+
+    dispatch_scheduled_runs(&host, None)?;
+    Ok(repo)
+}
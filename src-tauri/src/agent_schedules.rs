@@ -0,0 +1,491 @@
+use crate::agent_queue::{queue_agent_prompt_if_busy, report_failed_start, AgentPromptRequest};
+use crate::commands::{current_time_ms, emit_runtime_event, start_agent_run};
+use crate::host::Host;
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use croner::Cron;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const SCHEDULE_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+const SCHEDULE_COLUMNS: &str = "id, agent_id, cron, prompt, model, enabled, missed_run_policy, \
+     last_run_at, last_run_id, last_error, next_run_at, created_at";
+
+/// What happens to runs a schedule missed while Symphony was not running.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedRunPolicy {
+    /// Run once on startup, however many runs were missed.
+    #[default]
+    RunOnce,
+    /// Wait for the next scheduled time.
+    Skip,
+}
+
+impl MissedRunPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MissedRunPolicy::RunOnce => "run_once",
+            MissedRunPolicy::Skip => "skip",
+        }
+    }
+
+    fn from_db(value: &str) -> Self {
+        match value {
+            "skip" => MissedRunPolicy::Skip,
+            _ => MissedRunPolicy::RunOnce,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentSchedule {
+    pub id: i64,
+    pub agent_id: i64,
+    /// Five-field cron expression, evaluated in the local time zone.
+    pub cron: String,
+    pub prompt: String,
+    pub model: Option<String>,
+    pub enabled: bool,
+    pub missed_run_policy: MissedRunPolicy,
+    /// RFC 3339 times in UTC.
+    pub last_run_at: Option<String>,
+    pub last_run_id: Option<String>,
+    /// Why the last run could not be started.
+    pub last_error: Option<String>,
+    /// `None` while the schedule is disabled.
+    pub next_run_at: Option<String>,
+    pub created_at: String,
+}
+
+fn parse_cron(expression: &str) -> Result<Cron, String> {
+    Cron::new(expression.trim())
+        .parse()
+        .map_err(|e| format!("Invalid cron expression '{}': {}", expression.trim(), e))
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn next_run_after(cron: &Cron, after: DateTime<Utc>) -> Result<String, String> {
+    cron.find_next_occurrence(&after.with_timezone(&Local), false)
+        .map(|time| format_time(time.with_timezone(&Utc)))
+        .map_err(|e| format!("Could not compute the next run time: {}", e))
+}
+
+fn map_schedule_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<AgentSchedule> {
+    let missed_run_policy: String = row.get(6)?;
+    Ok(AgentSchedule {
+        id: row.get(0)?,
+        agent_id: row.get(1)?,
+        cron: row.get(2)?,
+        prompt: row.get(3)?,
+        model: row.get(4)?,
+        enabled: row.get(5)?,
+        missed_run_policy: MissedRunPolicy::from_db(&missed_run_policy),
+        last_run_at: row.get(7)?,
+        last_run_id: row.get(8)?,
+        last_error: row.get(9)?,
+        next_run_at: row.get(10)?,
+        created_at: row.get(11)?,
+    })
+}
+
+fn get_schedule_by_id(conn: &Connection, schedule_id: i64) -> Result<AgentSchedule, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM agent_schedules WHERE id = ?1",
+            SCHEDULE_COLUMNS
+        ),
+        rusqlite::params![schedule_id],
+        map_schedule_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => "Schedule not found".to_string(),
+        other => other.to_string(),
+    })
+}
+
+/// Applies each schedule's missed-run policy and starts the background task that
+/// runs schedules when they are due.
+pub(crate) fn start_schedule_runner(host: Host) {
+    if let Err(error) = skip_missed_runs(&host) {
+        eprintln!("Failed to skip missed agent schedule runs: {}", error);
+    }
+
+    std::thread::spawn(move || loop {
+        if let Err(error) = run_due_schedules(&host) {
+            eprintln!("Failed to run agent schedules: {}", error);
+        }
+        std::thread::sleep(SCHEDULE_POLL_INTERVAL);
+    });
+}
+
+fn skip_missed_runs(host: &Host) -> Result<(), String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    skip_missed_runs_at(&conn, Utc::now())
+}
+
+/// Moves overdue schedules that skip missed runs to their next time. Overdue
+/// schedules that run once are left due, so the first poll runs them.
+fn skip_missed_runs_at(conn: &Connection, now: DateTime<Utc>) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, cron FROM agent_schedules
+             WHERE enabled = 1 AND missed_run_policy = ?1 AND next_run_at <= ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    let overdue = stmt
+        .query_map(
+            rusqlite::params![MissedRunPolicy::Skip.as_str(), format_time(now)],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    for (schedule_id, expression) in overdue {
+        let next_run_at = parse_cron(&expression).and_then(|cron| next_run_after(&cron, now))?;
+        conn.execute(
+            "UPDATE agent_schedules SET next_run_at = ?1 WHERE id = ?2",
+            rusqlite::params![next_run_at, schedule_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn run_due_schedules(host: &Host) -> Result<(), String> {
+    let now = Utc::now();
+    let due = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        load_due_schedules(&conn, now)?
+    };
+
+    for schedule in due {
+        run_schedule(host, &schedule, now)?;
+    }
+    Ok(())
+}
+
+fn load_due_schedules(conn: &Connection, now: DateTime<Utc>) -> Result<Vec<AgentSchedule>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_schedules
+             WHERE enabled = 1 AND next_run_at <= ?1
+             ORDER BY next_run_at ASC",
+            SCHEDULE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let due = stmt
+        .query_map(rusqlite::params![format_time(now)], map_schedule_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(due)
+}
+
+/// Starts the schedule's prompt the same way `run_repo_agent` does, queued behind
+/// the agent's current run if it is busy, and moves the schedule to its next time.
+fn run_schedule(host: &Host, schedule: &AgentSchedule, now: DateTime<Utc>) -> Result<(), String> {
+    let run_id = format!("schedule-{}-{:016x}", schedule.id, rand::random::<u64>());
+    let result = start_scheduled_prompt(host, schedule, &run_id);
+    if let Err(error) = &result {
+        report_failed_start(host, schedule.agent_id, run_id.clone(), error.clone());
+    }
+
+    let next_run_at = parse_cron(&schedule.cron).and_then(|cron| next_run_after(&cron, now));
+    let updated = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE agent_schedules
+             SET last_run_at = ?1, last_run_id = ?2, last_error = ?3, next_run_at = ?4
+             WHERE id = ?5",
+            rusqlite::params![
+                format_time(now),
+                run_id,
+                result.err().or(next_run_at.clone().err()),
+                next_run_at.ok(),
+                schedule.id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        get_schedule_by_id(&conn, schedule.id)?
+    };
+
+    emit_runtime_event(host, "agent-schedule-updated", updated);
+    Ok(())
+}
+
+fn start_scheduled_prompt(
+    host: &Host,
+    schedule: &AgentSchedule,
+    run_id: &str,
+) -> Result<(), String> {
+    let (repo_id, repo_path): (i64, String) = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT repos.id, repos.path FROM agents JOIN repos ON repos.id = agents.repo_id
+             WHERE agents.id = ?1",
+            rusqlite::params![schedule.agent_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Agent not found".to_string(),
+            other => other.to_string(),
+        })?
+    };
+
+    let request = AgentPromptRequest {
+        run_id: run_id.to_string(),
+        agent_id: schedule.agent_id,
+        repo_id,
+        repo_path,
+        prompt: schedule.prompt.clone(),
+        model: schedule.model.clone(),
+        force_approve: None,
+        simulate_mode: None,
+        isolated_workspace: None,
+        resume: None,
+        limits: None,
+        priority: 0,
+        queued_at: current_time_ms(),
+    };
+    match queue_agent_prompt_if_busy(host, request)? {
+        Some(request) => start_agent_run(host, request),
+        None => Ok(()),
+    }
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_agent_schedules(host: Host, agent_id: i64) -> Result<Vec<AgentSchedule>, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_schedules WHERE agent_id = ?1 ORDER BY id ASC",
+            SCHEDULE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let schedules = stmt
+        .query_map(rusqlite::params![agent_id], map_schedule_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(schedules)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn create_agent_schedule(
+    host: Host,
+    agent_id: i64,
+    cron: String,
+    prompt: String,
+    model: Option<String>,
+    missed_run_policy: Option<MissedRunPolicy>,
+) -> Result<AgentSchedule, String> {
+    let trimmed_prompt = prompt.trim();
+    if trimmed_prompt.is_empty() {
+        return Err("Prompt is required".to_string());
+    }
+    let expression = cron.trim();
+    let next_run_at = next_run_after(&parse_cron(expression)?, Utc::now())?;
+
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let agent_exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM agents WHERE id = ?1)",
+            rusqlite::params![agent_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !agent_exists {
+        return Err("Agent not found".to_string());
+    }
+
+    conn.execute(
+        "INSERT INTO agent_schedules (agent_id, cron, prompt, model, missed_run_policy, next_run_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+            agent_id,
+            expression,
+            trimmed_prompt,
+            model,
+            missed_run_policy.unwrap_or_default().as_str(),
+            next_run_at
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    get_schedule_by_id(&conn, conn.last_insert_rowid())
+}
+
+/// Enabling a schedule starts counting from now, so runs missed while it was
+/// disabled are never made up.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn set_agent_schedule_enabled(
+    host: Host,
+    schedule_id: i64,
+    enabled: bool,
+) -> Result<AgentSchedule, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let schedule = get_schedule_by_id(&conn, schedule_id)?;
+    let next_run_at = if enabled {
+        Some(next_run_after(&parse_cron(&schedule.cron)?, Utc::now())?)
+    } else {
+        None
+    };
+    conn.execute(
+        "UPDATE agent_schedules SET enabled = ?1, next_run_at = ?2 WHERE id = ?3",
+        rusqlite::params![enabled, next_run_at, schedule_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    get_schedule_by_id(&conn, schedule_id)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn delete_agent_schedule(host: Host, schedule_id: i64) -> Result<(), String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let deleted_rows = conn
+        .execute(
+            "DELETE FROM agent_schedules WHERE id = ?1",
+            rusqlite::params![schedule_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+    if deleted_rows == 0 {
+        return Err("Schedule not found".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 4, hour, minute, second)
+            .unwrap()
+    }
+
+    fn schedules_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE agent_schedules (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                agent_id INTEGER NOT NULL,
+                cron TEXT NOT NULL,
+                prompt TEXT NOT NULL,
+                model TEXT,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                missed_run_policy TEXT NOT NULL DEFAULT 'run_once',
+                last_run_at TEXT,
+                last_run_id TEXT,
+                last_error TEXT,
+                next_run_at TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn insert_schedule(
+        conn: &Connection,
+        policy: MissedRunPolicy,
+        enabled: bool,
+        next_run_at: DateTime<Utc>,
+    ) -> i64 {
+        conn.execute(
+            "INSERT INTO agent_schedules (agent_id, cron, prompt, enabled, missed_run_policy, next_run_at)
+             VALUES (1, '*/15 * * * *', 'check', ?1, ?2, ?3)",
+            rusqlite::params![enabled, policy.as_str(), format_time(next_run_at)],
+        )
+        .unwrap();
+        conn.last_insert_rowid()
+    }
+
+    fn next_run_at(conn: &Connection, schedule_id: i64) -> String {
+        get_schedule_by_id(conn, schedule_id)
+            .unwrap()
+            .next_run_at
+            .unwrap()
+    }
+
+    // Quarter-hour crons fall on the same instants in every time zone offset in
+    // use, so these results do not depend on the machine's local zone.
+    #[test]
+    fn next_run_is_the_first_occurrence_after_now() {
+        let cron = parse_cron("*/15 * * * *").unwrap();
+        assert_eq!(
+            next_run_after(&cron, at(10, 7, 30)).unwrap(),
+            "2026-03-04T10:15:00Z"
+        );
+        assert_eq!(
+            next_run_after(&cron, at(10, 59, 59)).unwrap(),
+            "2026-03-04T11:00:00Z"
+        );
+    }
+
+    #[test]
+    fn next_run_excludes_now_itself() {
+        let cron = parse_cron("*/15 * * * *").unwrap();
+        assert_eq!(
+            next_run_after(&cron, at(10, 15, 0)).unwrap(),
+            "2026-03-04T10:30:00Z"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_cron_expressions() {
+        let error = parse_cron(" not a cron ").unwrap_err();
+        assert!(error.starts_with("Invalid cron expression 'not a cron'"));
+    }
+
+    #[test]
+    fn skip_policy_moves_overdue_schedules_past_now() {
+        let conn = schedules_db();
+        let now = at(10, 7, 30);
+        let overdue = insert_schedule(&conn, MissedRunPolicy::Skip, true, at(6, 0, 0));
+
+        skip_missed_runs_at(&conn, now).unwrap();
+
+        assert_eq!(next_run_at(&conn, overdue), "2026-03-04T10:15:00Z");
+        assert!(load_due_schedules(&conn, now).unwrap().is_empty());
+    }
+
+    #[test]
+    fn run_once_policy_leaves_overdue_schedules_due_once() {
+        let conn = schedules_db();
+        let now = at(10, 7, 30);
+        let overdue = insert_schedule(&conn, MissedRunPolicy::RunOnce, true, at(6, 0, 0));
+
+        skip_missed_runs_at(&conn, now).unwrap();
+
+        assert_eq!(next_run_at(&conn, overdue), "2026-03-04T06:00:00Z");
+        let due: Vec<_> = load_due_schedules(&conn, now)
+            .unwrap()
+            .into_iter()
+            .map(|schedule| schedule.id)
+            .collect();
+        assert_eq!(due, vec![overdue]);
+    }
+
+    #[test]
+    fn skip_policy_leaves_future_and_disabled_schedules_alone() {
+        let conn = schedules_db();
+        let now = at(10, 7, 30);
+        let future = insert_schedule(&conn, MissedRunPolicy::Skip, true, at(12, 0, 0));
+        let disabled = insert_schedule(&conn, MissedRunPolicy::Skip, false, at(6, 0, 0));
+
+        skip_missed_runs_at(&conn, now).unwrap();
+
+        assert_eq!(next_run_at(&conn, future), "2026-03-04T12:00:00Z");
+        assert_eq!(next_run_at(&conn, disabled), "2026-03-04T06:00:00Z");
+        assert!(load_due_schedules(&conn, now).unwrap().is_empty());
+    }
+}
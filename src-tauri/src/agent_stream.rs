@@ -0,0 +1,379 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A single record of the `--output-format stream-json` output of the agent CLI.
+///
+/// Records whose `type` is not recognised, or that no longer match the expected
+/// shape, are kept verbatim in [`AgentStreamEvent::Unknown`] so newer CLI
+/// versions degrade gracefully instead of being dropped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentStreamEvent {
+    System(SystemRecord),
+    User(MessageRecord),
+    Assistant(MessageRecord),
+    Thinking(ThinkingRecord),
+    ToolCall(ToolCallRecord),
+    ApprovalRequest(ApprovalRequestRecord),
+    Result(ResultRecord),
+    #[serde(untagged)]
+    Unknown(Value),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemRecord {
+    pub subtype: String,
+    #[serde(rename = "session_id", skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permission_mode: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageRecord {
+    pub message: AgentMessage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentMessage {
+    pub role: String,
+    #[serde(default)]
+    pub content: Vec<AgentMessageContent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentMessageContent {
+    Text {
+        text: String,
+    },
+    #[serde(untagged)]
+    Unknown(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThinkingSubtype {
+    Delta,
+    Completed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThinkingRecord {
+    pub subtype: ThinkingSubtype,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallSubtype {
+    Started,
+    Completed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub subtype: ToolCallSubtype,
+    pub call_id: String,
+    /// Keyed by the tool kind, e.g. `{"editToolCall": {"args": {...}, "result": {...}}}`.
+    pub tool_call: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_call_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<i64>,
+}
+
+/// Asks for permission to run a tool call. The agent waits for an approval response
+/// on stdin before it starts the call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalRequestRecord {
+    pub call_id: String,
+    /// Same shape as [`ToolCallRecord::tool_call`], without a result.
+    pub tool_call: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultRecord {
+    pub subtype: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_api_ms: Option<u64>,
+    #[serde(default)]
+    pub is_error: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_cost_usd: Option<f64>,
+}
+
+/// Token counts reported with a `result` record. CLIs that report them in
+/// camelCase are accepted too.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(alias = "inputTokens", skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u64>,
+    #[serde(alias = "outputTokens", skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<u64>,
+    #[serde(alias = "cacheReadTokens", skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u64>,
+    #[serde(alias = "cacheWriteTokens", skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u64>,
+}
+
+/// Parses one stdout line from the agent CLI. Lines that are not JSON (banners,
+/// progress output) yield `None`.
+pub fn parse_agent_stream_line(line: &str) -> Option<AgentStreamEvent> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    serde_json::from_str(trimmed).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SIMULATOR_STREAM: &str = include_str!("../scripts/fixtures/agent-simulator-stream.jsonl");
+    const SESSION_ID: &str = "29c7275b-dbcc-4a5f-aaff-05b6c93cb038";
+
+    fn simulator_events() -> Vec<AgentStreamEvent> {
+        SIMULATOR_STREAM
+            .lines()
+            .map(|line| parse_agent_stream_line(line).expect("fixture line should parse"))
+            .collect()
+    }
+
+    #[test]
+    fn parses_every_simulator_record_into_a_known_variant() {
+        let kinds: Vec<&str> = simulator_events()
+            .iter()
+            .map(|event| match event {
+                AgentStreamEvent::System(_) => "system",
+                AgentStreamEvent::User(_) => "user",
+                AgentStreamEvent::Assistant(_) => "assistant",
+                AgentStreamEvent::Thinking(_) => "thinking",
+                AgentStreamEvent::ToolCall(_) => "tool_call",
+                AgentStreamEvent::ApprovalRequest(_) => "approval_request",
+                AgentStreamEvent::Result(_) => "result",
+                AgentStreamEvent::Unknown(_) => "unknown",
+            })
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                "system",
+                "user",
+                "thinking",
+                "thinking",
+                "thinking",
+                "thinking",
+                "thinking",
+                "tool_call",
+                "tool_call",
+                "assistant",
+                "result",
+            ]
+        );
+        assert!(simulator_events().iter().all(|event| {
+            serde_json::to_value(event).unwrap()["session_id"] == json!(SESSION_ID)
+        }));
+    }
+
+    #[test]
+    fn parses_system_init_record() {
+        let events = simulator_events();
+        let AgentStreamEvent::System(init) = &events[0] else {
+            panic!("expected system record, got {:?}", events[0]);
+        };
+        assert_eq!(init.subtype, "init");
+        assert_eq!(init.model.as_deref(), Some("Claude 4.6 Opus (Thinking)"));
+        assert_eq!(init.api_key_source.as_deref(), Some("login"));
+        assert_eq!(init.permission_mode.as_deref(), Some("default"));
+    }
+
+    #[test]
+    fn joins_thinking_deltas() {
+        let events = simulator_events();
+        let thinking: Vec<&ThinkingRecord> = events
+            .iter()
+            .filter_map(|event| match event {
+                AgentStreamEvent::Thinking(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+
+        let text: String = thinking
+            .iter()
+            .filter(|record| record.subtype == ThinkingSubtype::Delta)
+            .filter_map(|record| record.text.as_deref())
+            .collect();
+        assert_eq!(
+            text,
+            "The user wants a new hello world script in TypeScript."
+        );
+        assert_eq!(
+            thinking.last().map(|record| record.subtype),
+            Some(ThinkingSubtype::Completed)
+        );
+    }
+
+    #[test]
+    fn exposes_tool_call_lifecycle() {
+        let tool_calls: Vec<ToolCallRecord> = simulator_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                AgentStreamEvent::ToolCall(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(tool_calls.len(), 2);
+        let (started, completed) = (&tool_calls[0], &tool_calls[1]);
+        assert_eq!(started.subtype, ToolCallSubtype::Started);
+        assert_eq!(completed.subtype, ToolCallSubtype::Completed);
+        assert_eq!(started.call_id, completed.call_id);
+        assert!(started
+            .tool_call
+            .pointer("/editToolCall/args/path")
+            .and_then(Value::as_str)
+            .is_some_and(|path| path.ends_with("hello.ts")));
+        assert!(started.tool_call.pointer("/editToolCall/result").is_none());
+        assert_eq!(
+            completed
+                .tool_call
+                .pointer("/editToolCall/result/success/linesAdded"),
+            Some(&json!(1))
+        );
+    }
+
+    #[test]
+    fn parses_approval_request() {
+        let line = r#"{"type":"approval_request","call_id":"toolu_1","tool_call":{"shellToolCall":{"args":{"command":"rm -rf dist"}}},"session_id":"abc"}"#;
+        let Some(AgentStreamEvent::ApprovalRequest(request)) = parse_agent_stream_line(line) else {
+            panic!("expected approval request");
+        };
+        assert_eq!(request.call_id, "toolu_1");
+        assert_eq!(
+            request.tool_call.pointer("/shellToolCall/args/command"),
+            Some(&json!("rm -rf dist"))
+        );
+        assert_eq!(
+            serde_json::to_value(AgentStreamEvent::ApprovalRequest(request)).unwrap(),
+            serde_json::from_str::<Value>(line).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_assistant_message_and_result() {
+        let events = simulator_events();
+        let AgentStreamEvent::Assistant(assistant) = &events[9] else {
+            panic!("expected assistant record, got {:?}", events[9]);
+        };
+        assert_eq!(assistant.message.role, "assistant");
+        assert!(matches!(
+            assistant.message.content.first(),
+            Some(AgentMessageContent::Text { text }) if text.starts_with("Created `hello.ts`")
+        ));
+
+        let AgentStreamEvent::Result(result) = &events[10] else {
+            panic!("expected result record, got {:?}", events[10]);
+        };
+        assert_eq!(result.subtype, "success");
+        assert!(!result.is_error);
+        assert_eq!(result.duration_ms, Some(20656));
+        assert_eq!(
+            result.request_id.as_deref(),
+            Some("9bbaedf1-917d-4671-a362-4805c54171ba")
+        );
+    }
+
+    #[test]
+    fn parses_result_usage_and_cost() {
+        let line = r#"{"type":"result","subtype":"success","is_error":false,"usage":{"input_tokens":1200,"output_tokens":340,"cache_read_input_tokens":800},"total_cost_usd":0.0123}"#;
+        let Some(AgentStreamEvent::Result(result)) = parse_agent_stream_line(line) else {
+            panic!("expected result record");
+        };
+        let usage = result.usage.expect("usage should parse");
+        assert_eq!(usage.input_tokens, Some(1200));
+        assert_eq!(usage.output_tokens, Some(340));
+        assert_eq!(usage.cache_read_input_tokens, Some(800));
+        assert_eq!(usage.cache_creation_input_tokens, None);
+        assert_eq!(result.total_cost_usd, Some(0.0123));
+
+        let camel_case =
+            r#"{"type":"result","subtype":"success","usage":{"inputTokens":5,"outputTokens":7}}"#;
+        let Some(AgentStreamEvent::Result(result)) = parse_agent_stream_line(camel_case) else {
+            panic!("expected result record");
+        };
+        assert_eq!(
+            result
+                .usage
+                .map(|usage| (usage.input_tokens, usage.output_tokens)),
+            Some((Some(5), Some(7)))
+        );
+    }
+
+    #[test]
+    fn preserves_unknown_records() {
+        let line = r#"{"type":"interaction_query","subtype":"request","query":{"id":1}}"#;
+        let event = parse_agent_stream_line(line).expect("JSON line should parse");
+        assert_eq!(
+            event,
+            AgentStreamEvent::Unknown(json!({
+                "type": "interaction_query",
+                "subtype": "request",
+                "query": { "id": 1 }
+            }))
+        );
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::from_str::<Value>(line).unwrap()
+        );
+    }
+
+    #[test]
+    fn falls_back_to_unknown_when_a_known_record_changes_shape() {
+        let line = r#"{"type":"tool_call","subtype":"paused","call_id":"abc","tool_call":{}}"#;
+        assert!(matches!(
+            parse_agent_stream_line(line),
+            Some(AgentStreamEvent::Unknown(_))
+        ));
+    }
+
+    #[test]
+    fn ignores_non_json_lines() {
+        assert_eq!(parse_agent_stream_line(""), None);
+        assert_eq!(parse_agent_stream_line("   "), None);
+        assert_eq!(parse_agent_stream_line("Loading models..."), None);
+    }
+}
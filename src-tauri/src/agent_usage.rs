@@ -0,0 +1,145 @@
+use crate::agent_stream::ResultRecord;
+use crate::host::Host;
+use rusqlite::Connection;
+use serde::Serialize;
+
+/// What a run cost, as far as the agent CLI reported it. `duration_ms` is measured
+/// by Symphony and is known for every finished run.
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunUsage {
+    /// Model named in the agent's `system/init` record.
+    pub reported_model: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub api_duration_ms: Option<u64>,
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    pub cache_read_tokens: Option<u64>,
+    pub cache_write_tokens: Option<u64>,
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunStats {
+    pub runs: u64,
+    pub finished_runs: u64,
+    pub succeeded_runs: u64,
+    /// Share of finished runs that succeeded, `None` before any run finished.
+    pub success_rate: Option<f64>,
+    pub total_duration_ms: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub cost_usd: f64,
+}
+
+pub(crate) fn record_reported_model(host: &Host, run_id: &str, model: &str) {
+    let Ok(conn) = host.db().conn.lock() else {
+        return;
+    };
+    if let Err(error) = conn.execute(
+        "UPDATE agent_runs SET reported_model = ?1 WHERE run_id = ?2",
+        rusqlite::params![model, run_id],
+    ) {
+        eprintln!("Failed to record model of agent run {}: {}", run_id, error);
+    }
+}
+
+/// Stores the API time, token usage and cost from the run's `result` record.
+pub(crate) fn record_run_result(host: &Host, run_id: &str, result: &ResultRecord) {
+    let usage = result.usage.clone().unwrap_or_default();
+    let Ok(conn) = host.db().conn.lock() else {
+        return;
+    };
+    if let Err(error) = conn.execute(
+        "UPDATE agent_runs
+         SET api_duration_ms = ?1,
+             input_tokens = ?2,
+             output_tokens = ?3,
+             cache_read_tokens = ?4,
+             cache_write_tokens = ?5,
+             cost_usd = ?6
+         WHERE run_id = ?7",
+        rusqlite::params![
+            result.duration_api_ms,
+            usage.input_tokens,
+            usage.output_tokens,
+            usage.cache_read_input_tokens,
+            usage.cache_creation_input_tokens,
+            result.total_cost_usd,
+            run_id
+        ],
+    ) {
+        eprintln!("Failed to record usage of agent run {}: {}", run_id, error);
+    }
+}
+
+/// Aggregates the runs of the agents matched by `agent_filter`, an SQL condition
+/// on `agents` with one parameter.
+fn load_run_stats(
+    conn: &Connection,
+    agent_filter: &str,
+    filter_value: i64,
+) -> Result<AgentRunStats, String> {
+    conn.query_row(
+        &format!(
+            "SELECT
+                 COUNT(*),
+                 COALESCE(SUM(agent_runs.status <> 'running'), 0),
+                 COALESCE(SUM(agent_runs.status = 'succeeded'), 0),
+                 COALESCE(SUM(agent_runs.duration_ms), 0),
+                 COALESCE(SUM(agent_runs.input_tokens), 0),
+                 COALESCE(SUM(agent_runs.output_tokens), 0),
+                 COALESCE(SUM(agent_runs.cache_read_tokens), 0),
+                 COALESCE(SUM(agent_runs.cache_write_tokens), 0),
+                 COALESCE(SUM(agent_runs.cost_usd), 0)
+             FROM agent_runs
+             JOIN agents ON agents.id = agent_runs.agent_id
+             WHERE {}",
+            agent_filter
+        ),
+        rusqlite::params![filter_value],
+        |row| {
+            let finished_runs: u64 = row.get(1)?;
+            let succeeded_runs: u64 = row.get(2)?;
+            Ok(AgentRunStats {
+                runs: row.get(0)?,
+                finished_runs,
+                succeeded_runs,
+                success_rate: (finished_runs > 0)
+                    .then(|| succeeded_runs as f64 / finished_runs as f64),
+                total_duration_ms: row.get(3)?,
+                input_tokens: row.get(4)?,
+                output_tokens: row.get(5)?,
+                cache_read_tokens: row.get(6)?,
+                cache_write_tokens: row.get(7)?,
+                cost_usd: row.get(8)?,
+            })
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn get_agent_stats(host: Host, agent_id: i64) -> Result<AgentRunStats, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    load_run_stats(&conn, "agents.id = ?1", agent_id)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn get_repo_stats(host: Host, repo_id: i64) -> Result<AgentRunStats, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    load_run_stats(&conn, "agents.repo_id = ?1", repo_id)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn get_group_stats(host: Host, group_id: i64) -> Result<AgentRunStats, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    load_run_stats(
+        &conn,
+        "agents.repo_id IN (SELECT id FROM repos WHERE group_id = ?1)",
+        group_id,
+    )
+}
@@ -0,0 +1,37 @@
+//! The async runtime the host bridge runs on: Tauri's own in the desktop app, and a
+//! Tokio runtime of the host's own when it is built without the `desktop` feature.
+
+#[cfg(feature = "desktop")]
+pub use tauri::async_runtime::{block_on, spawn, spawn_blocking, JoinHandle};
+
+#[cfg(not(feature = "desktop"))]
+pub use tokio::task::JoinHandle;
+
+#[cfg(not(feature = "desktop"))]
+fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: std::sync::OnceLock<tokio::runtime::Runtime> = std::sync::OnceLock::new();
+    RUNTIME.get_or_init(|| tokio::runtime::Runtime::new().expect("Failed to start async runtime"))
+}
+
+#[cfg(not(feature = "desktop"))]
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    runtime().spawn(future)
+}
+
+#[cfg(not(feature = "desktop"))]
+pub fn spawn_blocking<F, R>(function: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    runtime().spawn_blocking(function)
+}
+
+#[cfg(not(feature = "desktop"))]
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    runtime().block_on(future)
+}
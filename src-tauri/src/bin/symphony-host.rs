@@ -0,0 +1,3 @@
+fn main() {
+    symphony_lib::run_headless()
+}
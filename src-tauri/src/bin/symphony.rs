@@ -0,0 +1,6 @@
+//! The `symphony` command line. It only talks to a running host over HTTP; build it
+//! with `--no-default-features --features cli` to leave out the webview.
+
+fn main() {
+    std::process::exit(symphony_lib::run_cli())
+}
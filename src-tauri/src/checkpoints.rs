@@ -0,0 +1,217 @@
+use crate::commands::{get_agent_by_id, run_git_command, Agent};
+use crate::host::Host;
+use rusqlite::Connection;
+use serde::Serialize;
+
+const CHECKPOINT_REF_PREFIX: &str = "refs/symphony/checkpoints";
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCheckpoint {
+    pub run_id: String,
+    pub ref_name: String,
+    pub commit: String,
+    pub created_at: String,
+    pub subject: String,
+}
+
+fn checkpoint_ref_name(agent_id: i64, run_id: &str) -> Result<String, String> {
+    let is_valid_ref_component = !run_id.is_empty()
+        && run_id
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || matches!(character, '-' | '_'));
+    if !is_valid_ref_component {
+        return Err(format!(
+            "Run id {} cannot be used in a checkpoint ref",
+            run_id
+        ));
+    }
+    Ok(format!("{}/{}/{}", CHECKPOINT_REF_PREFIX, agent_id, run_id))
+}
+
+fn load_agent_repo_path(conn: &Connection, agent_id: i64) -> Result<String, String> {
+    conn.query_row(
+        "SELECT repos.path FROM agents JOIN repos ON repos.id = agents.repo_id WHERE agents.id = ?1",
+        rusqlite::params![agent_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => "Agent not found".to_string(),
+        other => other.to_string(),
+    })
+}
+
+fn git(path: &str, args: &[&str]) -> Result<String, String> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    run_git_command(path, &args).map(|output| output.trim().to_string())
+}
+
+/// Records the working tree and index of `working_directory` as a checkpoint commit
+/// for the run. Like `git stash`, the commit's tree is the working tree (including
+/// untracked files) and its second parent holds the index; `worktree_tree` is the
+/// snapshot already taken for the run's diff.
+pub(crate) fn create_agent_checkpoint(
+    working_directory: &str,
+    agent_id: i64,
+    run_id: &str,
+    prompt: &str,
+    worktree_tree: &str,
+) -> Result<String, String> {
+    let ref_name = checkpoint_ref_name(agent_id, run_id)?;
+    let head = git(
+        working_directory,
+        &["rev-parse", "--verify", "--quiet", "HEAD"],
+    )
+    .ok();
+    let index_tree = git(working_directory, &["write-tree"])?;
+
+    let identity = [
+        "-c",
+        "user.name=Symphony",
+        "-c",
+        "user.email=symphony@localhost",
+    ];
+    let commit_tree = |tree: &str, parents: &[&str], message: &str| {
+        let mut args = identity.to_vec();
+        args.extend(["commit-tree", tree, "-m", message]);
+        for parent in parents {
+            args.extend(["-p", parent]);
+        }
+        git(working_directory, &args)
+    };
+
+    let head_parents: Vec<&str> = head.as_deref().into_iter().collect();
+    let index_commit = commit_tree(
+        &index_tree,
+        &head_parents,
+        &format!("Index before run {}", run_id),
+    )?;
+    let mut checkpoint_parents = head_parents.clone();
+    checkpoint_parents.push(&index_commit);
+    let summary = prompt.lines().next().unwrap_or_default();
+    let checkpoint_commit = commit_tree(
+        worktree_tree,
+        &checkpoint_parents,
+        &format!("Checkpoint before run {}\n\n{}", run_id, summary),
+    )?;
+
+    git(
+        working_directory,
+        &["update-ref", &ref_name, &checkpoint_commit],
+    )?;
+    Ok(ref_name)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn set_agent_checkpoints(host: Host, agent_id: i64, enabled: bool) -> Result<Agent, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let updated_rows = conn
+        .execute(
+            "UPDATE agents SET checkpoints_enabled = ?1 WHERE id = ?2",
+            rusqlite::params![enabled, agent_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+    if updated_rows == 0 {
+        return Err("Agent not found".to_string());
+    }
+
+    get_agent_by_id(&conn, agent_id)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_agent_checkpoints(host: Host, agent_id: i64) -> Result<Vec<AgentCheckpoint>, String> {
+    let repo_path = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        load_agent_repo_path(&conn, agent_id)?
+    };
+
+    let prefix = format!("{}/{}/", CHECKPOINT_REF_PREFIX, agent_id);
+    let output = git(
+        &repo_path,
+        &[
+            "for-each-ref",
+            "--sort=-creatordate",
+            "--format=%(refname)%09%(objectname)%09%(creatordate:iso-strict)%09%(contents:subject)",
+            &prefix,
+        ],
+    )?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(4, '\t');
+            let ref_name = parts.next()?;
+            Some(AgentCheckpoint {
+                run_id: ref_name.strip_prefix(&prefix)?.to_string(),
+                ref_name: ref_name.to_string(),
+                commit: parts.next()?.to_string(),
+                created_at: parts.next()?.to_string(),
+                subject: parts.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Restores the working tree and index of the run's working directory to the
+/// checkpoint taken before the run. Files created since then are removed unless
+/// they are ignored; `HEAD` is left where it is.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn restore_agent_checkpoint(host: Host, agent_id: i64, run_id: String) -> Result<(), String> {
+    let working_directory: String = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT working_directory FROM agent_runs WHERE run_id = ?1 AND agent_id = ?2",
+            rusqlite::params![run_id, agent_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Agent run not found".to_string(),
+            other => other.to_string(),
+        })?
+    };
+
+    let ref_name = checkpoint_ref_name(agent_id, &run_id)?;
+    let worktree_tree = git(
+        &working_directory,
+        &["rev-parse", "--verify", &format!("{}^{{tree}}", ref_name)],
+    )
+    .map_err(|_| "No checkpoint exists for this run".to_string())?;
+    let parent_count = git(
+        &working_directory,
+        &["rev-list", "--parents", "-n", "1", &ref_name],
+    )?
+    .split_whitespace()
+    .count()
+        - 1;
+    // The index commit is the last parent: the only one when HEAD was unborn.
+    let index_tree = git(
+        &working_directory,
+        &[
+            "rev-parse",
+            "--verify",
+            &format!("{}^{}^{{tree}}", ref_name, parent_count),
+        ],
+    )?;
+
+    // Staging everything first makes files created since the checkpoint known to
+    // git, so resetting to the checkpoint tree removes them.
+    git(&working_directory, &["add", "--all"])?;
+    git(
+        &working_directory,
+        &["read-tree", "--reset", "-u", &worktree_tree],
+    )?;
+    git(&working_directory, &["read-tree", &index_tree])?;
+    Ok(())
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn delete_agent_checkpoint(host: Host, agent_id: i64, run_id: String) -> Result<(), String> {
+    let repo_path = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        load_agent_repo_path(&conn, agent_id)?
+    };
+    let ref_name = checkpoint_ref_name(agent_id, &run_id)?;
+    git(&repo_path, &["update-ref", "-d", &ref_name])?;
+    Ok(())
+}
@@ -0,0 +1,717 @@
+use crate::agent_stream::{AgentMessageContent, AgentStreamEvent, ToolCallSubtype};
+use clap::{Parser, Subcommand};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_HOST_URL: &str = "http://127.0.0.1:48678";
+const DEFAULT_AGENT_NAME: &str = "CLI";
+
+/// Script a running Symphony host through its bridge.
+#[derive(Debug, Parser)]
+#[command(name = "symphony")]
+struct Cli {
+    /// Host bridge URL. Falls back to the config file, then the local host.
+    #[arg(long, global = true, env = "SYMPHONY_HOST_URL")]
+    url: Option<String>,
+    /// Host access token. Falls back to the config file.
+    #[arg(
+        long,
+        global = true,
+        env = "SYMPHONY_HOST_TOKEN",
+        hide_env_values = true
+    )]
+    token: Option<String>,
+    /// Print JSON instead of human-readable output.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: CliCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+    /// Manage repositories.
+    #[command(subcommand, visible_alias = "repos")]
+    Repo(RepoCommand),
+    /// Run and stop agents.
+    #[command(subcommand, visible_alias = "agents")]
+    Agent(AgentCommand),
+    /// Manage access tokens for other people and devices.
+    #[command(subcommand, visible_alias = "tokens")]
+    Token(TokenCommand),
+    /// Print a short-lived code for pairing a phone or browser with the host.
+    Pair,
+}
+
+#[derive(Debug, Subcommand)]
+enum TokenCommand {
+    /// List access tokens.
+    List,
+    /// Create a token and print its secret and access URL.
+    Create {
+        name: String,
+        /// What the token may do; repeat for several scopes.
+        #[arg(
+            long = "scope",
+            default_value = "read",
+            value_parser = ["read", "run_agents", "manage_repos", "admin"]
+        )]
+        scopes: Vec<String>,
+        /// Days until the token expires. Never expires when not given.
+        #[arg(long)]
+        expires_in_days: Option<u32>,
+    },
+    /// Revoke a token.
+    Revoke { token_id: i64 },
+}
+
+#[derive(Debug, Subcommand)]
+enum RepoCommand {
+    /// List repositories.
+    List,
+    /// Pull a repository, or every repository with `--all`.
+    Pull {
+        /// Repository id, name or path.
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        repo: Option<String>,
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum AgentCommand {
+    /// List a repository's agents.
+    List {
+        /// Repository id, name or path.
+        repo: String,
+    },
+    /// Start a run. Uses the repository's most recent agent unless `--agent` is
+    /// given, and creates one when the repository has none.
+    Run {
+        /// Repository id, name or path.
+        repo: String,
+        prompt: String,
+        /// Agent id or name.
+        #[arg(long)]
+        agent: Option<String>,
+        #[arg(long)]
+        model: Option<String>,
+        /// Approve every tool call without asking.
+        #[arg(long, conflicts_with = "ask")]
+        force_approve: bool,
+        /// Ask before each tool call; calls are approved or denied from the app.
+        #[arg(long)]
+        ask: bool,
+        /// Stream the run's output and exit with its outcome.
+        #[arg(long)]
+        follow: bool,
+    },
+    /// Stop an agent's run.
+    Stop {
+        /// Repository id, name or path.
+        repo: String,
+        /// Agent id or name. Defaults to the repository's most recent agent.
+        #[arg(long)]
+        agent: Option<String>,
+        /// How long the agent gets to exit before it is killed.
+        #[arg(long)]
+        grace_period_ms: Option<u64>,
+    },
+}
+
+/// `~/.symphony/cli.toml`, used for settings not given as flags or environment
+/// variables.
+#[derive(Debug, Default, Deserialize)]
+struct CliConfig {
+    url: Option<String>,
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InvokeResponse {
+    ok: bool,
+    data: Option<Value>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BridgeEvent {
+    event: String,
+    payload: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct CliRepo {
+    id: i64,
+    name: String,
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CliAccessToken {
+    id: i64,
+    name: String,
+    kind: String,
+    scopes: Vec<String>,
+    expires_at: Option<String>,
+    last_used_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CliAgent {
+    id: i64,
+    name: String,
+    branch_name: Option<String>,
+    #[serde(default)]
+    supports_tool_approval: bool,
+}
+
+struct HostClient {
+    base_url: String,
+    token: String,
+    json: bool,
+}
+
+impl HostClient {
+    fn invoke<T: DeserializeOwned>(&self, command: &str, args: Value) -> Result<T, String> {
+        let response = ureq::post(&format!("{}/api/invoke", self.base_url))
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .send_json(json!({ "command": command, "args": args }));
+        // The bridge answers errors with the same body, so the message is read from it.
+        let response = match response {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, response)) => {
+                let body: Option<InvokeResponse> = response.into_json().ok();
+                return Err(body
+                    .and_then(|body| body.error)
+                    .unwrap_or_else(|| format!("The host answered with HTTP {}", status)));
+            }
+            Err(error) => return Err(format!("Could not reach {}: {}", self.base_url, error)),
+        };
+
+        let body: InvokeResponse = response.into_json().map_err(|e| e.to_string())?;
+        if !body.ok {
+            return Err(body.error.unwrap_or_else(|| format!("{} failed", command)));
+        }
+        serde_json::from_value(body.data.unwrap_or(Value::Null)).map_err(|e| e.to_string())
+    }
+
+    /// Opens the host's event stream for a run. Events sent before this returns are
+    /// missed.
+    fn run_events(&self, run_id: &str) -> Result<impl Iterator<Item = BridgeEvent>, String> {
+        let response = ureq::get(&format!("{}/api/events", self.base_url))
+            .query("runId", run_id)
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .call()
+            .map_err(|error| match error {
+                ureq::Error::Status(status, _) => {
+                    format!("Could not subscribe to events: HTTP {}", status)
+                }
+                error => format!("Could not reach {}: {}", self.base_url, error),
+            })?;
+        Ok(read_bridge_events(response.into_reader()))
+    }
+
+    fn print<T: serde::Serialize>(&self, value: &T, human: impl FnOnce(&T)) {
+        if self.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(value).unwrap_or_else(|_| "null".to_string())
+            );
+        } else {
+            human(value);
+        }
+    }
+}
+
+/// Parses the `data:` lines of a server-sent event stream into bridge events.
+fn read_bridge_events(reader: impl Read) -> impl Iterator<Item = BridgeEvent> {
+    let mut data = String::new();
+    BufReader::new(reader)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(move |line| {
+            if let Some(chunk) = line.strip_prefix("data:") {
+                if !data.is_empty() {
+                    data.push('\n');
+                }
+                data.push_str(chunk.strip_prefix(' ').unwrap_or(chunk));
+                return None;
+            }
+            if !line.is_empty() || data.is_empty() {
+                return None;
+            }
+            serde_json::from_str(&std::mem::take(&mut data)).ok()
+        })
+}
+
+fn load_cli_config() -> Result<CliConfig, String> {
+    let Some(path) = dirs::home_dir().map(|home| home.join(".symphony").join("cli.toml")) else {
+        return Ok(CliConfig::default());
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(CliConfig::default()),
+        Err(error) => Err(format!(
+            "Could not read config file {}: {}",
+            path.display(),
+            error
+        )),
+    }
+}
+
+fn connect(cli: &Cli) -> Result<HostClient, String> {
+    let config = load_cli_config()?;
+    let base_url = cli
+        .url
+        .clone()
+        .or(config.url)
+        .unwrap_or_else(|| DEFAULT_HOST_URL.to_string());
+    let token = cli.token.clone().or(config.token).ok_or_else(|| {
+        "No access token; set SYMPHONY_HOST_TOKEN or add `token` to ~/.symphony/cli.toml"
+            .to_string()
+    })?;
+    Ok(HostClient {
+        base_url: base_url.trim_end_matches('/').to_string(),
+        token,
+        json: cli.json,
+    })
+}
+
+fn same_path(left: &Path, right: &Path) -> bool {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    canonical(left) == canonical(right)
+}
+
+/// Finds a repository by id, name or path.
+fn resolve_repo(client: &HostClient, query: &str) -> Result<CliRepo, String> {
+    let repos: Vec<CliRepo> = client.invoke("list_repos", json!({}))?;
+    let query_path = PathBuf::from(query);
+    let mut matches: Vec<CliRepo> = repos
+        .into_iter()
+        .filter(|repo| {
+            repo.id.to_string() == query
+                || repo.name == query
+                || same_path(Path::new(&repo.path), &query_path)
+        })
+        .collect();
+    match matches.len() {
+        0 => Err(format!("No repository matches '{}'", query)),
+        1 => Ok(matches.remove(0)),
+        _ => Err(format!(
+            "'{}' matches several repositories; use its id or path",
+            query
+        )),
+    }
+}
+
+/// Finds an agent of the repository by id or name, or its most recent agent.
+fn resolve_agent(
+    client: &HostClient,
+    repo: &CliRepo,
+    query: Option<&str>,
+) -> Result<Option<CliAgent>, String> {
+    let agents: Vec<CliAgent> = client.invoke("list_agents", json!({ "repoId": repo.id }))?;
+    let Some(query) = query else {
+        return Ok(agents.into_iter().next());
+    };
+    agents
+        .into_iter()
+        .find(|agent| agent.id.to_string() == query || agent.name == query)
+        .map(Some)
+        .ok_or_else(|| format!("No agent of {} matches '{}'", repo.name, query))
+}
+
+fn list_repos(client: &HostClient) -> Result<i32, String> {
+    let repos: Value = client.invoke("list_repos", json!({}))?;
+    client.print(&repos, |repos| {
+        let repos: Vec<CliRepo> = serde_json::from_value(repos.clone()).unwrap_or_default();
+        for repo in repos {
+            println!("{}\t{}\t{}", repo.id, repo.name, repo.path);
+        }
+    });
+    Ok(0)
+}
+
+fn pull_repos(client: &HostClient, repo: Option<&str>) -> Result<i32, String> {
+    let repos = match repo {
+        Some(query) => vec![resolve_repo(client, query)?],
+        None => client.invoke("list_repos", json!({}))?,
+    };
+
+    let mut results = Vec::new();
+    let mut exit_code = 0;
+    for repo in repos {
+        let result: Result<String, String> =
+            client.invoke("pull_repo", json!({ "path": repo.path }));
+        if result.is_err() {
+            exit_code = 1;
+        }
+        if !client.json {
+            match &result {
+                Ok(output) => println!("{}: {}", repo.name, output.trim()),
+                Err(error) => eprintln!("{}: {}", repo.name, error),
+            }
+        }
+        results.push(json!({
+            "repoId": repo.id,
+            "name": repo.name,
+            "ok": result.is_ok(),
+            "output": result.as_ref().ok(),
+            "error": result.as_ref().err(),
+        }));
+    }
+    if client.json {
+        client.print(&results, |_| {});
+    }
+    Ok(exit_code)
+}
+
+fn list_agents(client: &HostClient, repo: &str) -> Result<i32, String> {
+    let repo = resolve_repo(client, repo)?;
+    let agents: Value = client.invoke("list_agents", json!({ "repoId": repo.id }))?;
+    client.print(&agents, |agents| {
+        let agents: Vec<CliAgent> = serde_json::from_value(agents.clone()).unwrap_or_default();
+        for agent in agents {
+            println!(
+                "{}\t{}\t{}",
+                agent.id,
+                agent.name,
+                agent.branch_name.unwrap_or_default()
+            );
+        }
+    });
+    Ok(0)
+}
+
+struct RunOptions<'a> {
+    repo: &'a str,
+    prompt: &'a str,
+    agent: Option<&'a str>,
+    model: Option<&'a str>,
+    /// The host's default applies when `None`.
+    force_approve: Option<bool>,
+    follow: bool,
+}
+
+fn run_agent(client: &HostClient, options: RunOptions) -> Result<i32, String> {
+    let repo = resolve_repo(client, options.repo)?;
+    let agent = match resolve_agent(client, &repo, options.agent)? {
+        Some(agent) => agent,
+        None => client.invoke(
+            "create_agent",
+            json!({ "repoId": repo.id, "name": DEFAULT_AGENT_NAME }),
+        )?,
+    };
+    if options.force_approve == Some(false) && !agent.supports_tool_approval {
+        return Err(format!(
+            "{} cannot ask before tool calls; run it without --ask",
+            agent.name
+        ));
+    }
+    let run_id = format!("cli-{:016x}", rand::random::<u64>());
+
+    // The host starts the stream at its latest event before answering, so opening it
+    // before the run starts means none of the run's output is missed.
+    let events = if options.follow {
+        Some(client.run_events(&run_id)?)
+    } else {
+        None
+    };
+    let mut args = json!({
+        "repoPath": repo.path,
+        "prompt": options.prompt,
+        "agentId": agent.id,
+        "runId": run_id,
+        "options": { "model": options.model },
+    });
+    if let Some(force_approve) = options.force_approve {
+        args["options"]["forceApprove"] = json!(force_approve);
+    }
+    let _: Value = client.invoke("run_repo_agent", args)?;
+
+    let Some(events) = events else {
+        let started = json!({ "runId": run_id, "agentId": agent.id, "repoId": repo.id });
+        client.print(&started, |_| {
+            println!("Started run {} on {} ({})", run_id, agent.name, repo.name)
+        });
+        return Ok(0);
+    };
+    follow_run(client, events, &run_id)
+}
+
+/// Prints the run's events until it is done. Exits non-zero unless it succeeded.
+fn follow_run(
+    client: &HostClient,
+    events: impl Iterator<Item = BridgeEvent>,
+    run_id: &str,
+) -> Result<i32, String> {
+    for event in events {
+        if event.payload.get("runId").and_then(Value::as_str) != Some(run_id) {
+            continue;
+        }
+        if client.json {
+            println!(
+                "{}",
+                json!({ "event": event.event, "payload": event.payload })
+            );
+        }
+
+        match event.event.as_str() {
+            "repo-agent-done" => {
+                let outcome = event
+                    .payload
+                    .get("outcome")
+                    .and_then(Value::as_str)
+                    .unwrap_or("failed");
+                if !client.json {
+                    eprintln!("Run {}", outcome);
+                }
+                return Ok(if outcome == "succeeded" { 0 } else { 1 });
+            }
+            _ if client.json => {}
+            "repo-agent-event" => {
+                if let Some(event) = event
+                    .payload
+                    .get("event")
+                    .and_then(|event| serde_json::from_value(event.clone()).ok())
+                {
+                    print_stream_event(&event);
+                }
+            }
+            "repo-agent-stderr" => {
+                if let Some(line) = event.payload.get("line").and_then(Value::as_str) {
+                    eprintln!("{}", line);
+                }
+            }
+            "repo-agent-approval-request" => {
+                let tool = tool_name(event.payload.get("toolCall"));
+                eprintln!("Waiting for approval of {} in the Symphony app", tool);
+            }
+            _ => {}
+        }
+    }
+    Err("The host closed the event stream before the run finished".to_string())
+}
+
+fn tool_name(tool_call: Option<&Value>) -> &str {
+    tool_call
+        .and_then(Value::as_object)
+        .and_then(|call| call.keys().next())
+        .map(String::as_str)
+        .unwrap_or("a tool call")
+}
+
+fn print_stream_event(event: &AgentStreamEvent) {
+    match event {
+        AgentStreamEvent::Assistant(record) => {
+            for content in &record.message.content {
+                if let AgentMessageContent::Text { text } = content {
+                    println!("{}", text);
+                }
+            }
+        }
+        AgentStreamEvent::ToolCall(record) if record.subtype == ToolCallSubtype::Started => {
+            eprintln!("> {}", tool_name(Some(&record.tool_call)));
+        }
+        AgentStreamEvent::Result(record) if record.is_error => {
+            if let Some(result) = &record.result {
+                eprintln!("{}", result);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn stop_agent(
+    client: &HostClient,
+    repo: &str,
+    agent: Option<&str>,
+    grace_period_ms: Option<u64>,
+) -> Result<i32, String> {
+    let repo = resolve_repo(client, repo)?;
+    let agent = resolve_agent(client, &repo, agent)?
+        .ok_or_else(|| format!("{} has no agents", repo.name))?;
+    let _: Value = client.invoke(
+        "stop_repo_agent",
+        json!({ "agentId": agent.id, "gracePeriodMs": grace_period_ms }),
+    )?;
+    let stopped = json!({ "agentId": agent.id });
+    client.print(&stopped, |_| {
+        println!("Stopping {} ({})", agent.name, repo.name)
+    });
+    Ok(0)
+}
+
+fn list_tokens(client: &HostClient) -> Result<i32, String> {
+    let tokens: Value = client.invoke("list_access_tokens", json!({}))?;
+    client.print(&tokens, |tokens| {
+        let tokens: Vec<CliAccessToken> =
+            serde_json::from_value(tokens.clone()).unwrap_or_default();
+        for token in tokens {
+            println!(
+                "{}\t{}\t{}\t{}\texpires {}\tlast used {}",
+                token.id,
+                token.name,
+                token.kind,
+                token.scopes.join(","),
+                token.expires_at.as_deref().unwrap_or("never"),
+                token.last_used_at.as_deref().unwrap_or("never")
+            );
+        }
+    });
+    Ok(0)
+}
+
+fn create_token(
+    client: &HostClient,
+    name: &str,
+    scopes: &[String],
+    expires_in_days: Option<u32>,
+) -> Result<i32, String> {
+    let created: Value = client.invoke(
+        "create_access_token",
+        json!({ "name": name, "scopes": scopes, "expiresInDays": expires_in_days }),
+    )?;
+    client.print(&created, |created| {
+        let field = |name: &str| created.get(name).and_then(Value::as_str).unwrap_or("");
+        println!("Token: {}", field("secret"));
+        println!("Access URL: {}", field("accessUrl"));
+        eprintln!("The token is only shown once.");
+    });
+    Ok(0)
+}
+
+fn start_pairing(client: &HostClient) -> Result<i32, String> {
+    let pairing: Value = client.invoke("start_device_pairing", json!({}))?;
+    client.print(&pairing, |pairing| {
+        let field = |name: &str| pairing.get(name).and_then(Value::as_str).unwrap_or("");
+        println!("Pairing code: {}", field("code"));
+        println!("Pairing URL: {}", field("pairingUrl"));
+        eprintln!("The code works once and expires at {}.", field("expiresAt"));
+    });
+    Ok(0)
+}
+
+/// Runs the `symphony` command line and returns the process exit code.
+pub fn run_cli() -> i32 {
+    let cli = Cli::parse();
+    let result = connect(&cli).and_then(|client| match &cli.command {
+        CliCommand::Repo(RepoCommand::List) => list_repos(&client),
+        CliCommand::Repo(RepoCommand::Pull { repo, .. }) => pull_repos(&client, repo.as_deref()),
+        CliCommand::Agent(AgentCommand::List { repo }) => list_agents(&client, repo),
+        CliCommand::Agent(AgentCommand::Run {
+            repo,
+            prompt,
+            agent,
+            model,
+            force_approve,
+            ask,
+            follow,
+        }) => run_agent(
+            &client,
+            RunOptions {
+                repo,
+                prompt,
+                agent: agent.as_deref(),
+                model: model.as_deref(),
+                force_approve: match (*force_approve, *ask) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+                follow: *follow,
+            },
+        ),
+        CliCommand::Agent(AgentCommand::Stop {
+            repo,
+            agent,
+            grace_period_ms,
+        }) => stop_agent(&client, repo, agent.as_deref(), *grace_period_ms),
+        CliCommand::Token(TokenCommand::List) => list_tokens(&client),
+        CliCommand::Token(TokenCommand::Create {
+            name,
+            scopes,
+            expires_in_days,
+        }) => create_token(&client, name, scopes, *expires_in_days),
+        CliCommand::Token(TokenCommand::Revoke { token_id }) => {
+            let _: Value = client.invoke("revoke_access_token", json!({ "tokenId": token_id }))?;
+            let revoked = json!({ "tokenId": token_id });
+            client.print(&revoked, |_| println!("Revoked token {}", token_id));
+            Ok(0)
+        }
+        CliCommand::Pair => start_pairing(&client),
+    });
+
+    match result {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_events(stream: &str) -> Vec<(String, Value)> {
+        read_bridge_events(stream.as_bytes())
+            .map(|event| (event.event, event.payload))
+            .collect()
+    }
+
+    #[test]
+    fn reads_events_separated_by_blank_lines() {
+        let stream = "id: 1f-1\nevent: repo-agent-stdout\ndata: {\"event\":\"repo-agent-stdout\",\"payload\":{\"line\":\"a\"}}\n\n\
+                      id: 1f-2\nevent: repo-agent-done\ndata: {\"event\":\"repo-agent-done\",\"payload\":{\"success\":true}}\n\n";
+        assert_eq!(
+            read_events(stream),
+            vec![
+                ("repo-agent-stdout".to_string(), json!({ "line": "a" })),
+                ("repo-agent-done".to_string(), json!({ "success": true })),
+            ]
+        );
+    }
+
+    #[test]
+    fn joins_multi_line_data() {
+        let stream = "data: {\"event\":\"repos-changed\",\ndata:\"payload\":null}\n\n";
+        assert_eq!(
+            read_events(stream),
+            vec![("repos-changed".to_string(), Value::Null)]
+        );
+    }
+
+    #[test]
+    fn handles_crlf_line_endings_and_comments() {
+        let stream =
+            ": keep-alive\r\n\r\ndata: {\"event\":\"repos-changed\",\"payload\":1}\r\n\r\n";
+        assert_eq!(
+            read_events(stream),
+            vec![("repos-changed".to_string(), json!(1))]
+        );
+    }
+
+    #[test]
+    fn skips_events_that_are_not_bridge_events() {
+        let stream = "data: not json\n\ndata: {\"event\":\"repos-changed\",\"payload\":{}}\n\n";
+        assert_eq!(
+            read_events(stream),
+            vec![("repos-changed".to_string(), json!({}))]
+        );
+    }
+
+    #[test]
+    fn drops_an_event_cut_off_by_the_end_of_the_stream() {
+        let stream = "data: {\"event\":\"repos-changed\",\"payload\":{}}\n\ndata: {\"event\":";
+        assert_eq!(read_events(stream).len(), 1);
+    }
+}
@@ -1,15 +1,41 @@
-use crate::db::Database;
-use crate::host_api::HostBridgeState;
+use crate::agent_approvals::register_approval_request;
+use crate::agent_backend::{
+    create_agent_backend, load_agent_backend, AgentBackendKind, AgentCommandRequest,
+    AgentStopSignal,
+};
+use crate::agent_batches::record_batch_run_done;
+use crate::agent_input::AgentStdin;
+use crate::agent_limits::{
+    apply_resource_limits, exceeded_cpu_time_limit, spawn_run_watchdog, AgentActivity,
+    AgentRunLimits,
+};
+use crate::agent_policy::{review_started_tool_call, AgentPolicy};
+use crate::agent_queue::{
+    drop_queued_prompts, ensure_agents_idle, queue_agent_prompt_if_busy, start_next_queued_prompt,
+    AgentPromptRequest,
+};
+use crate::agent_run_changes::{
+    record_agent_run_changes, snapshot_working_tree, AgentRunChangesPayload,
+};
+use crate::agent_scheduler::{cancel_scheduled_run, dispatch_scheduled_runs, schedule_agent_run};
+use crate::agent_stream::{AgentStreamEvent, SystemRecord, ToolCallSubtype};
+use crate::agent_usage::{record_reported_model, record_run_result, AgentRunUsage};
+use crate::checkpoints::create_agent_checkpoint;
+use crate::host::Host;
+use crate::worktrees::{
+    provision_agent_worktree, release_agent_worktrees, resolve_agent_working_directory,
+};
 use rusqlite::Connection;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::to_value;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, VecDeque};
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 use std::process::Command;
 use std::process::Stdio;
-use std::sync::Mutex;
-use tauri::{AppHandle, Emitter, Manager, State};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "desktop")]
+use tauri::Emitter;
 
 #[derive(Debug, Serialize, Clone)]
 pub struct Repo {
@@ -17,9 +43,13 @@ pub struct Repo {
     pub name: String,
     pub path: String,
     pub group_id: Option<i64>,
+    /// Most runs of this repo's agents that may run at once; unlimited when `None`.
+    pub max_concurrent_runs: Option<u32>,
     pub created_at: String,
 }
 
+const REPO_COLUMNS: &str = "id, name, path, group_id, max_concurrent_runs, created_at";
+
 #[derive(Debug, Serialize, Clone)]
 pub struct Group {
     pub id: i64,
@@ -33,9 +63,23 @@ pub struct Agent {
     pub id: i64,
     pub repo_id: i64,
     pub name: String,
+    pub worktree_path: Option<String>,
+    pub branch_name: Option<String>,
+    pub backend: String,
+    pub backend_command: Option<String>,
+    pub session_id: Option<String>,
+    pub run_limits: AgentRunLimits,
+    pub checkpoints_enabled: bool,
+    /// Whether runs can pause to ask before tool calls, see
+    /// `AgentBackend::supports_tool_approval`.
+    pub supports_tool_approval: bool,
     pub created_at: String,
 }
 
+const AGENT_COLUMNS: &str = "id, repo_id, name, worktree_path, branch_name, backend, \
+     backend_command, session_id, timeout_seconds, idle_timeout_seconds, \
+     cpu_time_limit_seconds, memory_limit_mb, open_files_limit, checkpoints_enabled, created_at";
+
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentModelOption {
@@ -43,10 +87,49 @@ pub struct AgentModelOption {
     pub name: String,
 }
 
+#[derive(Default)]
 pub struct AgentRuntimeState {
-    pub pids_by_agent_id: Mutex<HashMap<i64, u32>>,
+    pub processes_by_agent_id: Mutex<HashMap<i64, RunningAgentProcess>>,
+    pub queued_prompts_by_agent_id: Mutex<HashMap<i64, VecDeque<AgentPromptRequest>>>,
+    /// Runs waiting for the concurrency limits to allow them to start.
+    pub scheduled_runs: Mutex<Vec<AgentPromptRequest>>,
+}
+
+/// Lifecycle of an agent run. Every run moves forward through these states only;
+/// `Finished` is reached exactly once, by whoever emits the run's `repo-agent-done`.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentRunState {
+    /// Waiting for the scheduler's concurrency limits.
+    Queued,
+    Starting,
+    Running,
+    Stopping,
+    Finished,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunningAgentProcess {
+    pub run_id: String,
+    pub repo_id: i64,
+    pub state: AgentRunState,
+    /// Set once the process has been spawned.
+    pub pid: Option<u32>,
+    pub stop_signal: AgentStopSignal,
+    pub stop_reason: Option<AgentTerminationReason>,
+    /// Set once the process has been spawned if the backend reads follow-up input
+    /// or the run asks for tool call approvals.
+    pub stdin: Option<AgentStdin>,
+    /// Whether follow-up messages may be written to `stdin`.
+    pub accepts_input: bool,
+    /// Call ids of `approval_request`s the agent is waiting on.
+    pub pending_approvals: Vec<String>,
 }
 
+/// How long a stopped agent gets to exit after the stop signal before its process
+/// group is killed.
+const DEFAULT_STOP_GRACE_PERIOD_MS: u64 = 5_000;
+
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentStreamPayload {
@@ -55,47 +138,157 @@ pub struct AgentStreamPayload {
     pub line: String,
 }
 
+/// Sent when a run with checkpoints enabled starts without one.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCheckpointFailedPayload {
+    pub run_id: String,
+    pub agent_id: i64,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentEventPayload {
+    pub run_id: String,
+    pub agent_id: i64,
+    pub event: AgentStreamEvent,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunStatePayload {
+    pub run_id: String,
+    pub agent_id: i64,
+    pub state: AgentRunState,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentForceStopPayload {
+    pub run_id: String,
+    pub agent_id: i64,
+    pub reason: AgentTerminationReason,
+}
+
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentDonePayload {
     pub run_id: String,
     pub agent_id: i64,
     pub success: bool,
+    pub outcome: AgentRunOutcome,
+    pub exit_code: Option<i32>,
+    pub termination_reason: Option<AgentTerminationReason>,
 }
 
-fn emit_runtime_event<T: Serialize + Clone>(
-    app: &AppHandle,
-    event_name: &str,
-    payload: T,
-    bridge_state: Option<&HostBridgeState>,
-) {
-    let _ = app.emit(event_name, payload.clone());
-    if let Some(bridge) = bridge_state {
-        let json_payload = to_value(payload).unwrap_or(serde_json::Value::Null);
-        bridge.send_event(event_name, json_payload);
-    } else if let Some(bridge) = app.try_state::<HostBridgeState>() {
-        let json_payload = to_value(payload).unwrap_or(serde_json::Value::Null);
-        bridge.send_event(event_name, json_payload);
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentRunOutcome {
+    Succeeded,
+    Failed,
+    Cancelled,
+    TimedOut,
+    /// The agent was terminated by a signal nobody asked for.
+    Crashed,
+}
+
+impl AgentRunOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AgentRunOutcome::Succeeded => "succeeded",
+            AgentRunOutcome::Failed => "failed",
+            AgentRunOutcome::Cancelled => "cancelled",
+            AgentRunOutcome::TimedOut => "timed_out",
+            AgentRunOutcome::Crashed => "crashed",
+        }
     }
 }
 
-#[tauri::command]
-pub fn list_repos(db: State<'_, Database>) -> Result<Vec<Repo>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+/// Why an agent run was ended before it finished on its own.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentTerminationReason {
+    Cancelled,
+    Timeout,
+    IdleTimeout,
+    CpuTimeLimit,
+    PolicyViolation,
+}
+
+impl AgentTerminationReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AgentTerminationReason::Cancelled => "cancelled",
+            AgentTerminationReason::Timeout => "timeout",
+            AgentTerminationReason::IdleTimeout => "idle_timeout",
+            AgentTerminationReason::CpuTimeLimit => "cpu_time_limit",
+            AgentTerminationReason::PolicyViolation => "policy_violation",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRun {
+    pub run_id: String,
+    pub agent_id: i64,
+    pub prompt: String,
+    pub model: Option<String>,
+    pub working_directory: String,
+    pub status: String,
+    pub exit_code: Option<i32>,
+    pub termination_reason: Option<String>,
+    pub session_id: Option<String>,
+    pub checkpoint_ref: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub usage: AgentRunUsage,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunEvent {
+    pub sequence: i64,
+    pub stream: String,
+    pub line: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunTranscript {
+    pub run: AgentRun,
+    pub events: Vec<AgentRunEvent>,
+}
+
+const AGENT_RUN_COLUMNS: &str = "run_id, agent_id, prompt, model, working_directory, status, \
+     exit_code, termination_reason, session_id, checkpoint_ref, started_at, finished_at, \
+     reported_model, duration_ms, api_duration_ms, input_tokens, output_tokens, \
+     cache_read_tokens, cache_write_tokens, cost_usd";
+
+/// Sends the event to host bridge clients and, in the desktop app, to the webview.
+pub(crate) fn emit_runtime_event<T: Serialize + Clone>(host: &Host, event_name: &str, payload: T) {
+    #[cfg(feature = "desktop")]
+    if let Some(app) = host.app() {
+        let _ = app.emit(event_name, payload.clone());
+    }
+    let json_payload = to_value(payload).unwrap_or(serde_json::Value::Null);
+    host.bridge().send_event(event_name, json_payload);
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_repos(host: Host) -> Result<Vec<Repo>, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
     let mut stmt = conn
-        .prepare("SELECT id, name, path, group_id, created_at FROM repos ORDER BY name ASC")
+        .prepare(&format!(
+            "SELECT {} FROM repos ORDER BY name ASC",
+            REPO_COLUMNS
+        ))
         .map_err(|e| e.to_string())?;
 
     let repos = stmt
-        .query_map([], |row| {
-            Ok(Repo {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                path: row.get(2)?,
-                group_id: row.get(3)?,
-                created_at: row.get(4)?,
-            })
-        })
+        .query_map([], map_repo_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -103,22 +296,18 @@ pub fn list_repos(db: State<'_, Database>) -> Result<Vec<Repo>, String> {
     Ok(repos)
 }
 
-#[tauri::command]
-pub fn add_repo(
-    db: State<'_, Database>,
-    path: String,
-    group_id: Option<i64>,
-) -> Result<Repo, String> {
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn add_repo(host: Host, path: String, group_id: Option<i64>) -> Result<Repo, String> {
     let repo_path = Path::new(&path);
     let name = validate_git_repo(repo_path)?;
 
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
     insert_repo(&conn, &name, &path, group_id)
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn clone_repo(
-    db: State<'_, Database>,
+    host: Host,
     url: String,
     destination_parent: String,
     group_id: Option<i64>,
@@ -162,13 +351,72 @@ pub fn clone_repo(
 
     validate_git_repo(&destination_path)?;
     let destination = destination_path.to_string_lossy().to_string();
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
     insert_repo(&conn, &repo_name, &destination, group_id)
 }
 
-#[tauri::command]
-pub fn remove_repo(db: State<'_, Database>, id: i64) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn remove_repo(host: Host, id: i64) -> Result<(), String> {
+    let agent_ids = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT id FROM agents WHERE repo_id = ?1")
+            .map_err(|e| e.to_string())?;
+        let agent_ids = stmt
+            .query_map(rusqlite::params![id], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        agent_ids
+    };
+    ensure_agents_idle(
+        &host,
+        &agent_ids,
+        "Stop the repo's agents before removing it",
+    )?;
+    release_agent_worktrees(&host, &agent_ids)?;
+
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_run_events WHERE run_id IN (
+             SELECT agent_runs.run_id FROM agent_runs
+             JOIN agents ON agents.id = agent_runs.agent_id
+             WHERE agents.repo_id = ?1
+         )",
+        rusqlite::params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_run_changed_files WHERE run_id IN (
+             SELECT agent_runs.run_id FROM agent_runs
+             JOIN agents ON agents.id = agent_runs.agent_id
+             WHERE agents.repo_id = ?1
+         )",
+        rusqlite::params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_policy_violations
+         WHERE agent_id IN (SELECT id FROM agents WHERE repo_id = ?1)",
+        rusqlite::params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_batch_runs WHERE repo_id = ?1",
+        rusqlite::params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_schedules
+         WHERE agent_id IN (SELECT id FROM agents WHERE repo_id = ?1)",
+        rusqlite::params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_runs WHERE agent_id IN (SELECT id FROM agents WHERE repo_id = ?1)",
+        rusqlite::params![id],
+    )
+    .map_err(|e| e.to_string())?;
     conn.execute(
         "DELETE FROM agents WHERE repo_id = ?1",
         rusqlite::params![id],
@@ -176,30 +424,26 @@ pub fn remove_repo(db: State<'_, Database>, id: i64) -> Result<(), String> {
     .map_err(|e| e.to_string())?;
     conn.execute("DELETE FROM repos WHERE id = ?1", rusqlite::params![id])
         .map_err(|e| e.to_string())?;
+    drop(conn);
+    drop_queued_prompts(&host, &agent_ids);
     Ok(())
 }
 
-#[tauri::command]
-pub fn list_agents(db: State<'_, Database>, repo_id: i64) -> Result<Vec<Agent>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_agents(host: Host, repo_id: i64) -> Result<Vec<Agent>, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
     let mut stmt = conn
-        .prepare(
-            "SELECT id, repo_id, name, created_at
+        .prepare(&format!(
+            "SELECT {}
              FROM agents
              WHERE repo_id = ?1
              ORDER BY created_at DESC, id DESC",
-        )
+            AGENT_COLUMNS
+        ))
         .map_err(|e| e.to_string())?;
 
     let agents = stmt
-        .query_map(rusqlite::params![repo_id], |row| {
-            Ok(Agent {
-                id: row.get(0)?,
-                repo_id: row.get(1)?,
-                name: row.get(2)?,
-                created_at: row.get(3)?,
-            })
-        })
+        .query_map(rusqlite::params![repo_id], map_agent_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -207,65 +451,253 @@ pub fn list_agents(db: State<'_, Database>, repo_id: i64) -> Result<Vec<Agent>,
     Ok(agents)
 }
 
-#[tauri::command]
-pub fn create_agent(db: State<'_, Database>, repo_id: i64, name: String) -> Result<Agent, String> {
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn create_agent(
+    host: Host,
+    repo_id: i64,
+    name: String,
+    isolated_workspace: Option<bool>,
+    backend: Option<String>,
+    backend_command: Option<String>,
+) -> Result<Agent, String> {
     let trimmed_name = name.trim();
     if trimmed_name.is_empty() {
         return Err("Agent name is required".to_string());
     }
+    let (backend_kind, backend_command) =
+        validate_agent_backend(backend.as_deref(), backend_command)?;
+
+    let id = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO agents (repo_id, name, backend, backend_command) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![
+                repo_id,
+                trimmed_name,
+                backend_kind.as_str(),
+                backend_command
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.last_insert_rowid()
+    };
 
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let provisioned = if isolated_workspace.unwrap_or(false) {
+        provision_agent_worktree(&host, id)
+    } else {
+        Ok(())
+    };
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    if let Err(error) = provisioned {
+        let _ = conn.execute("DELETE FROM agents WHERE id = ?1", rusqlite::params![id]);
+        return Err(error);
+    }
 
-    conn.execute(
-        "INSERT INTO agents (repo_id, name) VALUES (?1, ?2)",
-        rusqlite::params![repo_id, trimmed_name],
-    )
-    .map_err(|e| e.to_string())?;
+    get_agent_by_id(&conn, id)
+}
 
-    let id = conn.last_insert_rowid();
-    let mut stmt = conn
-        .prepare("SELECT id, repo_id, name, created_at FROM agents WHERE id = ?1")
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn set_agent_backend(
+    host: Host,
+    agent_id: i64,
+    backend: String,
+    backend_command: Option<String>,
+) -> Result<Agent, String> {
+    let (backend_kind, backend_command) = validate_agent_backend(Some(&backend), backend_command)?;
+
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let updated_rows = conn
+        .execute(
+            // A session id only means something to the CLI that created it.
+            "UPDATE agents
+             SET session_id = CASE
+                     WHEN backend IS ?1 AND backend_command IS ?2 THEN session_id
+                 END,
+                 backend = ?1,
+                 backend_command = ?2
+             WHERE id = ?3",
+            rusqlite::params![backend_kind.as_str(), backend_command, agent_id],
+        )
         .map_err(|e| e.to_string())?;
 
-    let agent = stmt
-        .query_row(rusqlite::params![id], |row| {
-            Ok(Agent {
-                id: row.get(0)?,
-                repo_id: row.get(1)?,
-                name: row.get(2)?,
-                created_at: row.get(3)?,
-            })
-        })
+    if updated_rows == 0 {
+        return Err("Agent not found".to_string());
+    }
+
+    get_agent_by_id(&conn, agent_id)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn set_agent_run_limits(
+    host: Host,
+    agent_id: i64,
+    limits: AgentRunLimits,
+) -> Result<Agent, String> {
+    limits.validate()?;
+
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let updated_rows = conn
+        .execute(
+            "UPDATE agents
+             SET timeout_seconds = ?1, idle_timeout_seconds = ?2, cpu_time_limit_seconds = ?3,
+                 memory_limit_mb = ?4, open_files_limit = ?5
+             WHERE id = ?6",
+            rusqlite::params![
+                limits.timeout_seconds,
+                limits.idle_timeout_seconds,
+                limits.cpu_time_limit_seconds,
+                limits.memory_limit_mb,
+                limits.open_files_limit,
+                agent_id
+            ],
+        )
         .map_err(|e| e.to_string())?;
 
-    Ok(agent)
+    if updated_rows == 0 {
+        return Err("Agent not found".to_string());
+    }
+
+    get_agent_by_id(&conn, agent_id)
 }
 
-#[tauri::command]
-pub fn delete_agent(db: State<'_, Database>, agent_id: i64) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let deleted_rows = conn
+/// Forgets the stored conversation so the next resumed run starts a new session.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn reset_agent_session(host: Host, agent_id: i64) -> Result<Agent, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let updated_rows = conn
         .execute(
-            "DELETE FROM agents WHERE id = ?1",
+            "UPDATE agents SET session_id = NULL WHERE id = ?1",
             rusqlite::params![agent_id],
         )
         .map_err(|e| e.to_string())?;
 
-    if deleted_rows == 0 {
+    if updated_rows == 0 {
         return Err("Agent not found".to_string());
     }
 
+    get_agent_by_id(&conn, agent_id)
+}
+
+fn validate_agent_backend(
+    backend: Option<&str>,
+    backend_command: Option<String>,
+) -> Result<(AgentBackendKind, Option<String>), String> {
+    let backend_kind = AgentBackendKind::parse(backend.unwrap_or_default())?;
+    let backend_command = backend_command
+        .map(|command| command.trim().to_string())
+        .filter(|command| !command.is_empty());
+
+    // Building the backend validates the command template up front.
+    create_agent_backend(backend_kind, backend_command.as_deref())?;
+    Ok((backend_kind, backend_command))
+}
+
+pub(crate) fn get_agent_by_id(conn: &Connection, agent_id: i64) -> Result<Agent, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
+        rusqlite::params![agent_id],
+        map_agent_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => "Agent not found".to_string(),
+        other => other.to_string(),
+    })
+}
+
+fn map_agent_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Agent> {
+    let backend: String = row.get(5)?;
+    let backend_command: Option<String> = row.get(6)?;
+    let supports_tool_approval = AgentBackendKind::parse(&backend)
+        .and_then(|kind| create_agent_backend(kind, backend_command.as_deref()))
+        .is_ok_and(|backend| backend.supports_tool_approval());
+    Ok(Agent {
+        id: row.get(0)?,
+        repo_id: row.get(1)?,
+        name: row.get(2)?,
+        worktree_path: row.get(3)?,
+        branch_name: row.get(4)?,
+        backend,
+        backend_command,
+        session_id: row.get(7)?,
+        run_limits: AgentRunLimits {
+            timeout_seconds: row.get(8)?,
+            idle_timeout_seconds: row.get(9)?,
+            cpu_time_limit_seconds: row.get(10)?,
+            memory_limit_mb: row.get(11)?,
+            open_files_limit: row.get(12)?,
+        },
+        checkpoints_enabled: row.get(13)?,
+        supports_tool_approval,
+        created_at: row.get(14)?,
+    })
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn delete_agent(host: Host, agent_id: i64) -> Result<(), String> {
+    ensure_agents_idle(&host, &[agent_id], "Stop the agent before deleting it")?;
+    release_agent_worktrees(&host, &[agent_id])?;
+
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    if !delete_agent_rows(&conn, agent_id)? {
+        return Err("Agent not found".to_string());
+    }
+    drop(conn);
+    drop_queued_prompts(&host, &[agent_id]);
     Ok(())
 }
 
-#[tauri::command]
-pub fn rename_agent(db: State<'_, Database>, agent_id: i64, name: String) -> Result<(), String> {
+/// Deletes the agent with its runs, their transcripts and its schedules. Returns
+/// whether the agent existed.
+pub(crate) fn delete_agent_rows(conn: &Connection, agent_id: i64) -> Result<bool, String> {
+    let deleted_rows = conn
+        .execute(
+            "DELETE FROM agents WHERE id = ?1",
+            rusqlite::params![agent_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if deleted_rows == 0 {
+        return Ok(false);
+    }
+
+    conn.execute(
+        "DELETE FROM agent_run_events
+         WHERE run_id IN (SELECT run_id FROM agent_runs WHERE agent_id = ?1)",
+        rusqlite::params![agent_id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_run_changed_files
+         WHERE run_id IN (SELECT run_id FROM agent_runs WHERE agent_id = ?1)",
+        rusqlite::params![agent_id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_policy_violations WHERE agent_id = ?1",
+        rusqlite::params![agent_id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_schedules WHERE agent_id = ?1",
+        rusqlite::params![agent_id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM agent_runs WHERE agent_id = ?1",
+        rusqlite::params![agent_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn rename_agent(host: Host, agent_id: i64, name: String) -> Result<(), String> {
     let trimmed_name = name.trim();
     if trimmed_name.is_empty() {
         return Err("Agent name is required".to_string());
     }
 
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
     let updated_rows = conn
         .execute(
             "UPDATE agents SET name = ?1 WHERE id = ?2",
@@ -314,21 +746,30 @@ fn insert_repo(
         }
     })?;
 
-    let id = conn.last_insert_rowid();
-    let mut stmt = conn
-        .prepare("SELECT id, name, path, group_id, created_at FROM repos WHERE id = ?1")
-        .map_err(|e| e.to_string())?;
+    get_repo_by_id(conn, conn.last_insert_rowid())
+}
 
-    stmt.query_row(rusqlite::params![id], |row| {
-        Ok(Repo {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            path: row.get(2)?,
-            group_id: row.get(3)?,
-            created_at: row.get(4)?,
-        })
+pub(crate) fn get_repo_by_id(conn: &Connection, repo_id: i64) -> Result<Repo, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM repos WHERE id = ?1", REPO_COLUMNS),
+        rusqlite::params![repo_id],
+        map_repo_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => "Repository not found".to_string(),
+        other => other.to_string(),
+    })
+}
+
+fn map_repo_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Repo> {
+    Ok(Repo {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        path: row.get(2)?,
+        group_id: row.get(3)?,
+        max_concurrent_runs: row.get(4)?,
+        created_at: row.get(5)?,
     })
-    .map_err(|e| e.to_string())
 }
 
 fn extract_repo_name_from_url(url: &str) -> Result<String, String> {
@@ -395,7 +836,7 @@ pub struct RepoWorkingTreeStatus {
     pub has_untracked_changes: bool,
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn get_remote_url(path: String) -> Result<Option<RemoteInfo>, String> {
     let output = std::process::Command::new("git")
         .args(["remote", "get-url", "origin"])
@@ -416,7 +857,7 @@ pub fn get_remote_url(path: String) -> Result<Option<RemoteInfo>, String> {
     Ok(parse_remote_url(&remote_url))
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn get_current_branch(path: String) -> Result<String, String> {
     let branch = run_git_command(&path, &["branch".to_string(), "--show-current".to_string()])?
         .trim()
@@ -440,7 +881,7 @@ pub fn get_current_branch(path: String) -> Result<String, String> {
     Ok(format!("detached@{}", short_head))
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn list_local_branches(path: String) -> Result<Vec<LocalBranch>, String> {
     let output = run_git_command(
         &path,
@@ -473,13 +914,13 @@ pub fn list_local_branches(path: String) -> Result<Vec<LocalBranch>, String> {
     Ok(branches)
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn get_repo_working_tree_status(path: String) -> Result<RepoWorkingTreeStatus, String> {
     let output = run_git_command(&path, &["status".to_string(), "--porcelain".to_string()])?;
     Ok(parse_working_tree_status_output(&output))
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn switch_branch(
     path: String,
     target_branch: String,
@@ -527,7 +968,7 @@ pub fn switch_branch(
     Ok(format!("Switched to '{}'.", target))
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn create_local_branch(path: String, name: String) -> Result<String, String> {
     let trimmed_name = name.trim();
     if trimmed_name.is_empty() {
@@ -544,7 +985,7 @@ pub fn create_local_branch(path: String, name: String) -> Result<String, String>
     Ok(format!("Created branch '{}'.", trimmed_name))
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn delete_local_branch(
     path: String,
     branch_name: String,
@@ -572,7 +1013,7 @@ pub fn delete_local_branch(
     Ok(format!("Deleted branch '{}'.", trimmed_name))
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn list_git_history(path: String, limit: Option<u32>) -> Result<Vec<GitCommit>, String> {
     let clamped_limit = limit.unwrap_or(50).clamp(1, 200);
     let output = run_git_command(
@@ -615,7 +1056,7 @@ pub fn list_git_history(path: String, limit: Option<u32>) -> Result<Vec<GitCommi
     Ok(commits)
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn get_commit_changes(path: String, commit: String) -> Result<Vec<GitCommitFileDiff>, String> {
     let trimmed_commit = commit.trim();
     if trimmed_commit.is_empty() {
@@ -637,7 +1078,7 @@ pub fn get_commit_changes(path: String, commit: String) -> Result<Vec<GitCommitF
     Ok(parse_commit_file_diffs(&output))
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn list_working_tree_changes(path: String) -> Result<Vec<GitWorkingTreeFileChange>, String> {
     let output = run_git_command(
         &path,
@@ -650,7 +1091,7 @@ pub fn list_working_tree_changes(path: String) -> Result<Vec<GitWorkingTreeFileC
     Ok(parse_working_tree_changes(&output))
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn get_working_tree_file_diff(path: String, file_path: String) -> Result<String, String> {
     let trimmed_file_path = file_path.trim();
     if trimmed_file_path.is_empty() {
@@ -699,7 +1140,7 @@ pub fn get_working_tree_file_diff(path: String, file_path: String) -> Result<Str
     Ok(head_diff)
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn commit_working_tree(
     path: String,
     message: String,
@@ -754,7 +1195,7 @@ pub fn commit_working_tree(
     }
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn get_repo_sync_status(path: String, fetch: Option<bool>) -> Result<RepoSyncStatus, String> {
     let mut status = RepoSyncStatus {
         has_remote: false,
@@ -821,7 +1262,7 @@ pub fn get_repo_sync_status(path: String, fetch: Option<bool>) -> Result<RepoSyn
     Ok(status)
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn pull_repo(path: String) -> Result<String, String> {
     let output = run_git_command(
         &path,
@@ -868,7 +1309,7 @@ fn parse_remote_url(remote_url: &str) -> Option<RemoteInfo> {
     }
 }
 
-fn run_git_command(path: &str, args: &[String]) -> Result<String, String> {
+pub(crate) fn run_git_command(path: &str, args: &[String]) -> Result<String, String> {
     let output = std::process::Command::new("git")
         .args(args.iter().map(String::as_str))
         .current_dir(path)
@@ -891,7 +1332,7 @@ fn run_git_command(path: &str, args: &[String]) -> Result<String, String> {
     Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
-fn run_git_status_command(path: &str, args: &[&str]) -> Result<bool, String> {
+pub(crate) fn run_git_status_command(path: &str, args: &[&str]) -> Result<bool, String> {
     let output = std::process::Command::new("git")
         .args(args)
         .current_dir(path)
@@ -1052,9 +1493,9 @@ fn parse_path_from_diff_header(header_line: &str) -> String {
     }
 }
 
-#[tauri::command]
-pub fn list_groups(db: State<'_, Database>) -> Result<Vec<Group>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_groups(host: Host) -> Result<Vec<Group>, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
             "SELECT id, name, sort_order, created_at FROM groups ORDER BY sort_order ASC, name ASC",
@@ -1077,9 +1518,9 @@ pub fn list_groups(db: State<'_, Database>) -> Result<Vec<Group>, String> {
     Ok(groups)
 }
 
-#[tauri::command]
-pub fn create_group(db: State<'_, Database>, name: String) -> Result<Group, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn create_group(host: Host, name: String) -> Result<Group, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
 
     // Get the next sort_order
     let max_order: i64 = conn
@@ -1116,9 +1557,9 @@ pub fn create_group(db: State<'_, Database>, name: String) -> Result<Group, Stri
     Ok(group)
 }
 
-#[tauri::command]
-pub fn rename_group(db: State<'_, Database>, id: i64, name: String) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn rename_group(host: Host, id: i64, name: String) -> Result<(), String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
     conn.execute(
         "UPDATE groups SET name = ?1 WHERE id = ?2",
         rusqlite::params![name, id],
@@ -1127,9 +1568,9 @@ pub fn rename_group(db: State<'_, Database>, id: i64, name: String) -> Result<()
     Ok(())
 }
 
-#[tauri::command]
-pub fn delete_group(db: State<'_, Database>, id: i64) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn delete_group(host: Host, id: i64) -> Result<(), String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
     // Unassign repos from this group first (ON DELETE SET NULL handles this, but be explicit)
     conn.execute(
         "UPDATE repos SET group_id = NULL WHERE group_id = ?1",
@@ -1141,13 +1582,9 @@ pub fn delete_group(db: State<'_, Database>, id: i64) -> Result<(), String> {
     Ok(())
 }
 
-#[tauri::command]
-pub fn move_repo_to_group(
-    db: State<'_, Database>,
-    repo_id: i64,
-    group_id: Option<i64>,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn move_repo_to_group(host: Host, repo_id: i64, group_id: Option<i64>) -> Result<(), String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
     conn.execute(
         "UPDATE repos SET group_id = ?1 WHERE id = ?2",
         rusqlite::params![group_id, repo_id],
@@ -1156,47 +1593,265 @@ pub fn move_repo_to_group(
     Ok(())
 }
 
-#[tauri::command]
+/// How `run_repo_agent` runs a prompt. Unset fields fall back to the host's and the
+/// agent's defaults.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunOptions {
+    pub force_approve: Option<bool>,
+    pub simulate_mode: Option<bool>,
+    pub model: Option<String>,
+    pub isolated_workspace: Option<bool>,
+    pub resume: Option<bool>,
+    pub limits: Option<AgentRunLimits>,
+    pub priority: Option<i64>,
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn run_repo_agent(
-    app: AppHandle,
-    state: State<'_, AgentRuntimeState>,
+    host: Host,
     repo_path: String,
     prompt: String,
     agent_id: i64,
     run_id: String,
-    force_approve: Option<bool>,
-    simulate_mode: Option<bool>,
-    model: Option<String>,
+    options: Option<AgentRunOptions>,
 ) -> Result<(), String> {
+    let options = options.unwrap_or_default();
     let trimmed_prompt = prompt.trim();
     if trimmed_prompt.is_empty() {
         return Err("Prompt is required".to_string());
     }
+    if let Some(limits) = &options.limits {
+        limits.validate()?;
+    }
 
-    {
-        let pid_guard = state.pids_by_agent_id.lock().map_err(|e| e.to_string())?;
-        if pid_guard.contains_key(&agent_id) {
-            return Err("This agent is already running".to_string());
+    let repo = Path::new(&repo_path);
+    if !repo.exists() || !repo.is_dir() {
+        return Err("Repository path does not exist".to_string());
+    }
+
+    let repo_id: i64 = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT repo_id FROM agents WHERE id = ?1",
+            rusqlite::params![agent_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Agent not found".to_string(),
+            other => other.to_string(),
+        })?
+    };
+
+    let request = AgentPromptRequest {
+        run_id,
+        agent_id,
+        repo_id,
+        repo_path,
+        prompt: trimmed_prompt.to_string(),
+        model: options.model,
+        force_approve: options.force_approve,
+        simulate_mode: options.simulate_mode,
+        isolated_workspace: options.isolated_workspace,
+        resume: options.resume,
+        limits: options.limits,
+        priority: options.priority.unwrap_or_default(),
+        queued_at: current_time_ms(),
+    };
+
+    match queue_agent_prompt_if_busy(&host, request)? {
+        Some(request) => start_agent_run(&host, request),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn current_time_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Hands a prompt to the scheduler, which starts it right away unless the
+/// concurrency limits are reached. Errors starting this prompt are returned.
+pub(crate) fn start_agent_run(host: &Host, request: AgentPromptRequest) -> Result<(), String> {
+    let run_id = request.run_id.clone();
+    schedule_agent_run(host, request)?;
+    dispatch_scheduled_runs(host, Some(&run_id))
+}
+
+/// Spawns the agent process for a run the scheduler moved to `Starting` and streams
+/// its output until it exits, then starts whatever is waiting for the agent.
+pub(crate) fn launch_agent_run(host: &Host, request: AgentPromptRequest) -> Result<(), String> {
+    let agent_id = request.agent_id;
+    let run_id = request.run_id.clone();
+    spawn_agent_run(host, request).inspect_err(|_| {
+        finish_agent_run_state(host, agent_id, &run_id);
+    })
+}
+
+/// Moves a spawned run from `Starting` to `Running`. A stop requested while the
+/// process was starting is delivered now that there is a process to signal.
+fn mark_agent_run_spawned(
+    host: &Host,
+    agent_id: i64,
+    run_id: &str,
+    pid: u32,
+    stop_signal: AgentStopSignal,
+    stdin: Option<AgentStdin>,
+    accepts_input: bool,
+) -> Result<(), String> {
+    let pending_stop = {
+        let state = host.agent_runtime();
+        let mut process_guard = state
+            .processes_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+        let process = process_guard
+            .get_mut(&agent_id)
+            .filter(|process| process.run_id == run_id)
+            .ok_or("Agent run is no longer tracked".to_string())?;
+        process.pid = Some(pid);
+        process.stop_signal = stop_signal;
+        process.stdin = stdin;
+        process.accepts_input = accepts_input;
+        match process.state {
+            AgentRunState::Starting => {
+                process.state = AgentRunState::Running;
+                false
+            }
+            AgentRunState::Stopping => true,
+            AgentRunState::Queued | AgentRunState::Running | AgentRunState::Finished => false,
         }
+    };
+
+    if pending_stop {
+        signal_agent_run(host, agent_id, run_id, pid, stop_signal, None);
+    } else {
+        emit_run_state(host, agent_id, run_id, AgentRunState::Running);
     }
+    Ok(())
+}
+
+/// Removes the run from the runtime state and returns why it was stopped, if it
+/// was. Only the caller that gets `Some` back may emit the run's terminal event.
+fn finish_agent_run_state(
+    host: &Host,
+    agent_id: i64,
+    run_id: &str,
+) -> Option<Option<AgentTerminationReason>> {
+    let process = {
+        let state = host.agent_runtime();
+        let mut process_guard = state.processes_by_agent_id.lock().ok()?;
+        if process_guard
+            .get(&agent_id)
+            .is_none_or(|process| process.run_id != run_id)
+        {
+            return None;
+        }
+        process_guard.remove(&agent_id)?
+    };
+
+    emit_run_state(host, agent_id, run_id, AgentRunState::Finished);
+    Some(process.stop_reason)
+}
+
+/// Emits the run's terminal event, after recording the outcome of batch runs.
+pub(crate) fn emit_agent_done(host: &Host, payload: AgentDonePayload) {
+    record_batch_run_done(host, &payload);
+    emit_runtime_event(host, "repo-agent-done", payload);
+}
+
+pub(crate) fn emit_run_state(host: &Host, agent_id: i64, run_id: &str, state: AgentRunState) {
+    emit_runtime_event(
+        host,
+        "repo-agent-state",
+        AgentRunStatePayload {
+            run_id: run_id.to_string(),
+            agent_id,
+            state,
+        },
+    );
+}
+
+fn spawn_agent_run(host: &Host, request: AgentPromptRequest) -> Result<(), String> {
+    let AgentPromptRequest {
+        run_id,
+        agent_id,
+        repo_path,
+        prompt,
+        model,
+        force_approve,
+        simulate_mode,
+        isolated_workspace,
+        resume,
+        limits,
+        ..
+    } = request;
+    let trimmed_prompt = prompt.as_str();
 
     let repo = Path::new(&repo_path);
     if !repo.exists() || !repo.is_dir() {
         return Err("Repository path does not exist".to_string());
     }
+    let policy = AgentPolicy::load(&repo_path)?;
 
-    let use_simulator = simulate_mode.unwrap_or(false);
-    let mut process = if use_simulator {
-        create_simulator_agent_command(trimmed_prompt, &repo_path)?
-    } else {
-        create_cursor_agent_command(
-            trimmed_prompt,
-            force_approve.unwrap_or(true),
-            model.as_deref(),
-        )?
+    let working_directory = resolve_agent_working_directory(
+        host,
+        agent_id,
+        &repo_path,
+        isolated_workspace.unwrap_or(false),
+    )?;
+    // The stored session belongs to the agent's own backend, so a simulated run
+    // neither resumes it nor replaces it.
+    let is_simulated = simulate_mode.unwrap_or(false);
+    let (backend, resume_session_id, limits, checkpoints_enabled) = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        let backend = if is_simulated {
+            create_agent_backend(AgentBackendKind::Simulator, None)?
+        } else {
+            load_agent_backend(&conn, agent_id)?
+        };
+        let agent = get_agent_by_id(&conn, agent_id)?;
+        // Backends without a resume flag, or agents without a stored session, start fresh.
+        let resume_session_id =
+            if resume.unwrap_or(false) && backend.supports_resume() && !is_simulated {
+                agent.session_id
+            } else {
+                None
+            };
+        let limits = limits.unwrap_or_default().or(agent.run_limits);
+        (
+            backend,
+            resume_session_id,
+            limits,
+            agent.checkpoints_enabled,
+        )
     };
-    process.current_dir(repo);
-    process.stdin(Stdio::null());
+
+    let force_approve = force_approve.unwrap_or(true);
+    // Without a way to ask, the agent would run its tools unreviewed anyway.
+    if !force_approve && !backend.supports_tool_approval() {
+        return Err(format!(
+            "The {} cannot ask for approval of tool calls; run it with force approve",
+            backend.display_name()
+        ));
+    }
+    let mut process = backend.build_command(&AgentCommandRequest {
+        prompt: trimmed_prompt,
+        working_directory: &working_directory,
+        model: model.as_deref(),
+        force_approve,
+        resume_session_id: resume_session_id.as_deref(),
+    })?;
+    process.current_dir(&working_directory);
+    let asks_for_approval = !force_approve;
+    let accepts_input = backend.supports_input();
+    process.stdin(if accepts_input || asks_for_approval {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    });
     process.stdout(Stdio::piped());
     process.stderr(Stdio::piped());
 
@@ -1206,334 +1861,685 @@ pub fn run_repo_agent(
         process.creation_flags(0x0800_0000); // CREATE_NO_WINDOW
     }
 
-    let mut child = process.spawn().map_err(|e| {
-        if use_simulator {
-            format!("Failed to start simulator agent: {}", e)
-        } else {
-            format!("Failed to start Cursor agent: {}", e)
+    // Run the agent in its own process group so stopping it also reaches the
+    // shells and servers its tools started.
+    #[cfg(not(target_os = "windows"))]
+    {
+        use std::os::unix::process::CommandExt;
+        process.process_group(0);
+    }
+    apply_resource_limits(&mut process, &limits);
+
+    // Snapshot the tree before the agent touches it, so its changes can be told apart
+    // from edits that were already there. Without checkpoints, a failed snapshot only
+    // costs the diff.
+    let base_tree = snapshot_working_tree(&working_directory);
+    let checkpoint = match &base_tree {
+        Ok(base_tree) if checkpoints_enabled => create_agent_checkpoint(
+            &working_directory,
+            agent_id,
+            &run_id,
+            trimmed_prompt,
+            base_tree,
+        )
+        .map(Some),
+        Ok(_) => Ok(None),
+        Err(error) if checkpoints_enabled => Err(error.clone()),
+        Err(error) => {
+            eprintln!("Failed to snapshot {}: {}", working_directory, error);
+            Ok(None)
         }
-    })?;
-
-    let stdout = child
-        .stdout
-        .take()
-        .ok_or("Failed to capture Cursor agent stdout".to_string())?;
-    let stderr = child
-        .stderr
-        .take()
-        .ok_or("Failed to capture Cursor agent stderr".to_string())?;
+    };
+    let (checkpoint_ref, checkpoint_error) = match checkpoint {
+        Ok(checkpoint_ref) => (checkpoint_ref, None),
+        // Runs that edit without asking would have no way back.
+        Err(error) if force_approve => {
+            return Err(format!(
+                "Could not create a checkpoint, so the run was not started: {}",
+                error
+            ))
+        }
+        Err(error) => (None, Some(error)),
+    };
+    let base_tree = base_tree.ok();
 
     {
-        let mut pid_guard = state.pids_by_agent_id.lock().map_err(|e| e.to_string())?;
-        pid_guard.insert(agent_id, child.id());
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        insert_agent_run(
+            &conn,
+            &NewAgentRun {
+                run_id: &run_id,
+                agent_id,
+                prompt: trimmed_prompt,
+                model: model.as_deref(),
+                working_directory: &working_directory,
+                base_tree: base_tree.as_deref(),
+                checkpoint_ref: checkpoint_ref.as_deref(),
+            },
+        )?;
     }
+    if let Some(error) = checkpoint_error {
+        append_agent_run_event(host, &run_id, "checkpoint_error", &error);
+        emit_runtime_event(
+            host,
+            "repo-agent-checkpoint-failed",
+            AgentCheckpointFailedPayload {
+                run_id: run_id.clone(),
+                agent_id,
+                error,
+            },
+        );
+    }
+
+    let started_at = std::time::Instant::now();
+    let mut child = match process.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            finish_agent_run(host, &run_id, "failed", None, None, None);
+            return Err(format!("Failed to start {}: {}", backend.display_name(), e));
+        }
+    };
 
-    let app_for_worker = app.clone();
-    let bridge_state = app.try_state::<HostBridgeState>().map(|s| (&*s).clone());
-    let bridge_for_stderr = bridge_state.clone();
+    let stdout = child.stdout.take().ok_or(format!(
+        "Failed to capture {} stdout",
+        backend.display_name()
+    ))?;
+    let stderr = child.stderr.take().ok_or(format!(
+        "Failed to capture {} stderr",
+        backend.display_name()
+    ))?;
+
+    let stdin = child.stdin.take().map(|stdin| Arc::new(Mutex::new(stdin)));
+
+    mark_agent_run_spawned(
+        host,
+        agent_id,
+        &run_id,
+        child.id(),
+        backend.stop_signal(),
+        stdin,
+        accepts_input,
+    )?;
+
+    let activity = AgentActivity::new();
+    spawn_run_watchdog(host, agent_id, &run_id, &limits, activity.clone());
+
+    let host_for_worker = host.clone();
     std::thread::spawn(move || {
         let run_id_for_stderr = run_id.clone();
-        let app_for_stderr = app_for_worker.clone();
-        let bridge_ref = bridge_state.as_ref();
+        let host_for_stderr = host_for_worker.clone();
         let stderr_handle = std::thread::spawn(move || {
             let reader = BufReader::new(stderr);
-            let bridge_ref_inner = bridge_for_stderr.as_ref();
             for line in reader.lines().map_while(Result::ok) {
+                append_agent_run_event(&host_for_stderr, &run_id_for_stderr, "stderr", &line);
                 emit_runtime_event(
-                    &app_for_stderr,
+                    &host_for_stderr,
                     "repo-agent-stderr",
                     AgentStreamPayload {
                         run_id: run_id_for_stderr.clone(),
                         agent_id,
                         line,
                     },
-                    bridge_ref_inner,
                 );
             }
         });
 
         let reader = BufReader::new(stdout);
         for line in reader.lines().map_while(Result::ok) {
+            activity.touch();
+            append_agent_run_event(&host_for_worker, &run_id, "stdout", &line);
+            let parsed_event = backend.parse_output_line(&line);
+            match &parsed_event {
+                Some(AgentStreamEvent::System(SystemRecord {
+                    session_id, model, ..
+                })) => {
+                    if let Some(session_id) = session_id.as_ref().filter(|_| !is_simulated) {
+                        record_agent_session(&host_for_worker, agent_id, &run_id, session_id);
+                    }
+                    if let Some(model) = model {
+                        record_reported_model(&host_for_worker, &run_id, model);
+                    }
+                }
+                Some(AgentStreamEvent::Result(result)) => {
+                    record_run_result(&host_for_worker, &run_id, result);
+                }
+                Some(AgentStreamEvent::ApprovalRequest(record)) => register_approval_request(
+                    &host_for_worker,
+                    agent_id,
+                    &run_id,
+                    &working_directory,
+                    policy.as_ref(),
+                    record,
+                ),
+                Some(AgentStreamEvent::ToolCall(record))
+                    if !asks_for_approval && record.subtype == ToolCallSubtype::Started =>
+                {
+                    if let Some(policy) = &policy {
+                        review_started_tool_call(
+                            &host_for_worker,
+                            agent_id,
+                            &run_id,
+                            &working_directory,
+                            policy,
+                            record,
+                        );
+                    }
+                }
+                _ => {}
+            }
             emit_runtime_event(
-                &app_for_worker,
+                &host_for_worker,
                 "repo-agent-stdout",
                 AgentStreamPayload {
                     run_id: run_id.clone(),
                     agent_id,
                     line,
                 },
-                bridge_ref,
             );
+            if let Some(event) = parsed_event {
+                emit_runtime_event(
+                    &host_for_worker,
+                    "repo-agent-event",
+                    AgentEventPayload {
+                        run_id: run_id.clone(),
+                        agent_id,
+                        event,
+                    },
+                );
+            }
         }
 
         let _ = stderr_handle.join();
-        let success = child.wait().map(|status| status.success()).unwrap_or(false);
-
-        if let Ok(mut pid_guard) = app_for_worker
-            .state::<AgentRuntimeState>()
-            .pids_by_agent_id
-            .lock()
-        {
-            pid_guard.remove(&agent_id);
+        let exit_status = child.wait().ok();
+        let exit_code = exit_status.and_then(|status| status.code());
+        let duration_ms = started_at.elapsed().as_millis() as u64;
+
+        // Recorded before the agent is released, so a queued prompt cannot start
+        // editing the tree while it is being diffed.
+        if let Some(base_tree) = &base_tree {
+            match record_agent_run_changes(&host_for_worker, &run_id, &working_directory, base_tree)
+            {
+                Ok(files) => emit_runtime_event(
+                    &host_for_worker,
+                    "repo-agent-run-changes",
+                    AgentRunChangesPayload {
+                        run_id: run_id.clone(),
+                        agent_id,
+                        files,
+                    },
+                ),
+                Err(error) => {
+                    eprintln!(
+                        "Failed to record changes of agent run {}: {}",
+                        run_id, error
+                    )
+                }
+            }
         }
 
-        emit_runtime_event(
-            &app_for_worker,
-            "repo-agent-done",
+        let Some(stop_reason) = finish_agent_run_state(&host_for_worker, agent_id, &run_id) else {
+            return;
+        };
+
+        let (outcome, termination_reason) = match (stop_reason, exit_status) {
+            (Some(AgentTerminationReason::Cancelled), _) => {
+                (AgentRunOutcome::Cancelled, stop_reason)
+            }
+            (Some(AgentTerminationReason::PolicyViolation), _) => {
+                (AgentRunOutcome::Failed, stop_reason)
+            }
+            (Some(_), _) => (AgentRunOutcome::TimedOut, stop_reason),
+            (None, Some(status)) if status.success() => (AgentRunOutcome::Succeeded, None),
+            (None, Some(status)) if exceeded_cpu_time_limit(&status) => (
+                AgentRunOutcome::Crashed,
+                Some(AgentTerminationReason::CpuTimeLimit),
+            ),
+            (None, Some(status)) if terminated_by_signal(&status) => {
+                (AgentRunOutcome::Crashed, None)
+            }
+            _ => (AgentRunOutcome::Failed, None),
+        };
+        finish_agent_run(
+            &host_for_worker,
+            &run_id,
+            outcome.as_str(),
+            exit_code,
+            termination_reason.map(AgentTerminationReason::as_str),
+            Some(duration_ms),
+        );
+
+        emit_agent_done(
+            &host_for_worker,
             AgentDonePayload {
                 run_id,
                 agent_id,
-                success,
+                success: outcome == AgentRunOutcome::Succeeded,
+                outcome,
+                exit_code,
+                termination_reason,
             },
-            bridge_ref,
         );
+
+        start_next_queued_prompt(&host_for_worker, agent_id);
+        if let Err(error) = dispatch_scheduled_runs(&host_for_worker, None) {
+            eprintln!("Failed to start scheduled agent runs: {}", error);
+        }
     });
 
     Ok(())
 }
 
-#[tauri::command]
-pub fn stop_repo_agent(
-    app: AppHandle,
-    state: State<'_, AgentRuntimeState>,
-    agent_id: i64,
-) -> Result<(), String> {
-    let pid = {
-        let guard = state.pids_by_agent_id.lock().map_err(|e| e.to_string())?;
-        guard.get(&agent_id).copied()
-    };
+#[cfg(not(target_os = "windows"))]
+fn terminated_by_signal(status: &std::process::ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal().is_some()
+}
 
-    let Some(pid) = pid else {
-        return Err("No process is currently running for this agent".to_string());
-    };
+#[cfg(target_os = "windows")]
+fn terminated_by_signal(_status: &std::process::ExitStatus) -> bool {
+    false
+}
 
-    #[cfg(target_os = "windows")]
-    {
-        use std::os::windows::process::CommandExt;
-        let output = std::process::Command::new("taskkill")
-            .args(["/F", "/T", "/PID", &pid.to_string()])
-            .creation_flags(0x0800_0000) // CREATE_NO_WINDOW
-            .output()
-            .map_err(|e| format!("Failed to run taskkill: {}", e))?;
+struct NewAgentRun<'a> {
+    run_id: &'a str,
+    agent_id: i64,
+    prompt: &'a str,
+    model: Option<&'a str>,
+    working_directory: &'a str,
+    base_tree: Option<&'a str>,
+    checkpoint_ref: Option<&'a str>,
+}
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-            return Err(if stderr.is_empty() {
-                "Failed to stop running agent".to_string()
-            } else {
-                stderr
-            });
+fn insert_agent_run(conn: &Connection, run: &NewAgentRun<'_>) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO agent_runs
+             (run_id, agent_id, prompt, model, working_directory, base_tree, checkpoint_ref)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            run.run_id,
+            run.agent_id,
+            run.prompt,
+            run.model,
+            run.working_directory,
+            run.base_tree,
+            run.checkpoint_ref
+        ],
+    )
+    .map_err(|e| {
+        if e.to_string().contains("UNIQUE") {
+            "An agent run with this id already exists".to_string()
+        } else {
+            e.to_string()
         }
+    })?;
+    Ok(())
+}
+
+pub(crate) fn append_agent_run_event(host: &Host, run_id: &str, stream: &str, line: &str) {
+    let Ok(conn) = host.db().conn.lock() else {
+        return;
+    };
+    // The sequence is derived while holding the connection lock, so stdout and
+    // stderr lines written from separate threads still get a single ordering.
+    if let Err(error) = conn.execute(
+        "INSERT INTO agent_run_events (run_id, sequence, stream, line)
+         VALUES (
+             ?1,
+             (SELECT COALESCE(MAX(sequence), 0) + 1 FROM agent_run_events WHERE run_id = ?1),
+             ?2,
+             ?3
+         )",
+        rusqlite::params![run_id, stream, line],
+    ) {
+        eprintln!(
+            "Failed to persist agent run event for {}: {}",
+            run_id, error
+        );
     }
+}
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        let _ = std::process::Command::new("kill")
-            .args(["-9", &pid.to_string()])
-            .output();
+fn record_agent_session(host: &Host, agent_id: i64, run_id: &str, session_id: &str) {
+    let Ok(conn) = host.db().conn.lock() else {
+        return;
+    };
+    let result = conn
+        .execute(
+            "UPDATE agents SET session_id = ?1 WHERE id = ?2",
+            rusqlite::params![session_id, agent_id],
+        )
+        .and_then(|_| {
+            conn.execute(
+                "UPDATE agent_runs SET session_id = ?1 WHERE run_id = ?2",
+                rusqlite::params![session_id, run_id],
+            )
+        });
+    if let Err(error) = result {
+        eprintln!(
+            "Failed to record agent session for run {}: {}",
+            run_id, error
+        );
     }
+}
 
-    {
-        let mut guard = state.pids_by_agent_id.lock().map_err(|e| e.to_string())?;
-        guard.remove(&agent_id);
+fn finish_agent_run(
+    host: &Host,
+    run_id: &str,
+    status: &str,
+    exit_code: Option<i32>,
+    termination_reason: Option<&str>,
+    duration_ms: Option<u64>,
+) {
+    let Ok(conn) = host.db().conn.lock() else {
+        return;
+    };
+    if let Err(error) = conn.execute(
+        "UPDATE agent_runs
+         SET status = ?1,
+             exit_code = ?2,
+             termination_reason = ?3,
+             duration_ms = ?4,
+             finished_at = CURRENT_TIMESTAMP
+         WHERE run_id = ?5",
+        rusqlite::params![status, exit_code, termination_reason, duration_ms, run_id],
+    ) {
+        eprintln!("Failed to finish agent run {}: {}", run_id, error);
     }
+}
 
-    emit_runtime_event(&app, "repo-agent-force-stop", true, None);
-    Ok(())
+fn map_agent_run_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<AgentRun> {
+    Ok(AgentRun {
+        run_id: row.get(0)?,
+        agent_id: row.get(1)?,
+        prompt: row.get(2)?,
+        model: row.get(3)?,
+        working_directory: row.get(4)?,
+        status: row.get(5)?,
+        exit_code: row.get(6)?,
+        termination_reason: row.get(7)?,
+        session_id: row.get(8)?,
+        checkpoint_ref: row.get(9)?,
+        started_at: row.get(10)?,
+        finished_at: row.get(11)?,
+        usage: AgentRunUsage {
+            reported_model: row.get(12)?,
+            duration_ms: row.get(13)?,
+            api_duration_ms: row.get(14)?,
+            input_tokens: row.get(15)?,
+            output_tokens: row.get(16)?,
+            cache_read_tokens: row.get(17)?,
+            cache_write_tokens: row.get(18)?,
+            cost_usd: row.get(19)?,
+        },
+    })
 }
 
-#[tauri::command]
-pub fn list_agent_models() -> Result<Vec<AgentModelOption>, String> {
-    let output = Command::new("agent")
-        .arg("models")
-        .output()
-        .map_err(|e| format!("Failed to run agent models: {}", e))?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        let message = if !stderr.is_empty() {
-            stderr
-        } else if !stdout.is_empty() {
-            stdout
-        } else {
-            "Failed to list models from agent CLI".to_string()
-        };
-        return Err(message);
-    }
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_agent_runs(
+    host: Host,
+    agent_id: i64,
+    limit: Option<u32>,
+) -> Result<Vec<AgentRun>, String> {
+    let clamped_limit = limit.unwrap_or(50).clamp(1, 500);
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}
+             FROM agent_runs
+             WHERE agent_id = ?1
+             ORDER BY started_at DESC, rowid DESC
+             LIMIT ?2",
+            AGENT_RUN_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let cleaned_output = strip_ansi_codes(&stdout);
-    let mut seen: HashSet<String> = HashSet::new();
-    let mut models: Vec<AgentModelOption> = Vec::new();
+    let runs = stmt
+        .query_map(
+            rusqlite::params![agent_id, clamped_limit],
+            map_agent_run_row,
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
 
-    for line in cleaned_output.lines() {
-        let trimmed_line = line.trim();
-        if trimmed_line.is_empty() {
-            continue;
-        }
-        if trimmed_line.starts_with("Loading models") {
-            continue;
-        }
-        if trimmed_line.starts_with("Available models") {
-            continue;
-        }
-        if trimmed_line.starts_with("Tip: use --model") {
-            continue;
-        }
+    Ok(runs)
+}
 
-        let (model_id, display_name) = if let Some((id_part, name_part)) = trimmed_line.split_once(" - ")
-        {
-            let model_id = id_part.trim();
-            if model_id.is_empty() {
-                continue;
-            }
-            let name_part = name_part.trim();
-            let display_name = if name_part.is_empty() {
-                model_id.to_string()
-            } else {
-                name_part.to_string()
-            };
-            (model_id, display_name)
-        } else {
-            let model_id = trimmed_line;
-            if model_id.is_empty() {
-                continue;
-            }
-            (model_id, model_id.to_string())
-        };
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn get_agent_run_transcript(host: Host, run_id: String) -> Result<AgentRunTranscript, String> {
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    let run = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM agent_runs WHERE run_id = ?1",
+                AGENT_RUN_COLUMNS
+            ),
+            rusqlite::params![run_id],
+            map_agent_run_row,
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Agent run not found".to_string(),
+            other => other.to_string(),
+        })?;
 
-        if !model_id
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
-        {
-            continue;
-        }
-        let id_owned = model_id.to_string();
-        if seen.insert(id_owned.clone()) {
-            models.push(AgentModelOption {
-                id: id_owned,
-                name: display_name,
-            });
-        }
-    }
+    let mut stmt = conn
+        .prepare(
+            "SELECT sequence, stream, line, created_at
+             FROM agent_run_events
+             WHERE run_id = ?1
+             ORDER BY sequence ASC",
+        )
+        .map_err(|e| e.to_string())?;
 
-    if models.is_empty() {
-        return Err("No models were returned by the agent CLI".to_string());
-    }
+    let events = stmt
+        .query_map(rusqlite::params![run_id], |row| {
+            Ok(AgentRunEvent {
+                sequence: row.get(0)?,
+                stream: row.get(1)?,
+                line: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
 
-    Ok(models)
+    Ok(AgentRunTranscript { run, events })
 }
 
-fn create_cursor_agent_command(
-    prompt: &str,
-    force_approve: bool,
-    model: Option<&str>,
-) -> Result<std::process::Command, String> {
-    #[cfg(target_os = "windows")]
-    {
-        let local_app_data =
-            std::env::var("LOCALAPPDATA").map_err(|_| "LOCALAPPDATA env var not found")?;
-        let agent_path = Path::new(&local_app_data)
-            .join("cursor-agent")
-            .join("agent.CMD");
-        if !agent_path.exists() {
-            return Err(format!("agent.CMD not found at {}", agent_path.display()));
-        }
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn stop_repo_agent(
+    host: Host,
+    agent_id: i64,
+    grace_period_ms: Option<u64>,
+) -> Result<(), String> {
+    request_agent_stop(
+        &host,
+        agent_id,
+        None,
+        AgentTerminationReason::Cancelled,
+        grace_period_ms,
+    )
+}
 
-        let mut command = std::process::Command::new("cmd");
-        command.args(["/C", agent_path.to_string_lossy().as_ref()]);
-        command.arg(prompt);
-        command.args(["--output-format", "stream-json", "--print"]);
-        if let Some(trimmed_model) = model.map(str::trim).filter(|value| !value.is_empty()) {
-            command.args(["--model", trimmed_model]);
-        }
-        if force_approve {
-            command.arg("--force");
-        }
-        return Ok(command);
+/// Cancels the agent's run if it is still waiting to be scheduled. Otherwise moves
+/// it to `Stopping` and asks it to stop with its backend's stop signal. When
+/// `expected_run_id` is set, only that run is stopped, so a late request cannot
+/// hit a newer run. Stopping a run that is already stopping is a no-op.
+pub(crate) fn request_agent_stop(
+    host: &Host,
+    agent_id: i64,
+    expected_run_id: Option<&str>,
+    reason: AgentTerminationReason,
+    grace_period_ms: Option<u64>,
+) -> Result<(), String> {
+    if cancel_scheduled_run(host, agent_id, expected_run_id, reason)? {
+        return Ok(());
     }
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        let mut command = std::process::Command::new("cursor-agent");
-        command.arg(prompt);
-        command.args(["--output-format", "stream-json", "--print"]);
-        if let Some(trimmed_model) = model.map(str::trim).filter(|value| !value.is_empty()) {
-            command.args(["--model", trimmed_model]);
-        }
-        if force_approve {
-            command.arg("--force");
+    let process = {
+        let state = host.agent_runtime();
+        let mut guard = state
+            .processes_by_agent_id
+            .lock()
+            .map_err(|e| e.to_string())?;
+        let process = guard
+            .get_mut(&agent_id)
+            .filter(|process| expected_run_id.is_none_or(|run_id| process.run_id == run_id))
+            .ok_or("No process is currently running for this agent".to_string())?;
+        match process.state {
+            AgentRunState::Starting | AgentRunState::Running => {
+                process.state = AgentRunState::Stopping;
+                process.stop_reason = Some(reason);
+                process.clone()
+            }
+            AgentRunState::Queued | AgentRunState::Stopping | AgentRunState::Finished => {
+                return Ok(())
+            }
         }
-        Ok(command)
-    }
-}
-
-fn create_simulator_agent_command(prompt: &str, repo_path: &str) -> Result<Command, String> {
-    let script_path = Path::new(env!("CARGO_MANIFEST_DIR"))
-        .join("scripts")
-        .join("agent-simulator.mjs");
-    if !script_path.exists() {
-        return Err(format!(
-            "Simulator script not found at {}",
-            script_path.display()
-        ));
-    }
+    };
 
-    let script_path_string = script_path.to_string_lossy().to_string();
-    if command_exists("bun") {
-        let mut command = Command::new("bun");
-        command.args([script_path_string.as_str(), prompt, repo_path]);
-        return Ok(command);
+    emit_run_state(host, agent_id, &process.run_id, AgentRunState::Stopping);
+    emit_runtime_event(
+        host,
+        "repo-agent-force-stop",
+        AgentForceStopPayload {
+            run_id: process.run_id.clone(),
+            agent_id,
+            reason,
+        },
+    );
+
+    // A run that is still starting is signalled once its process has been spawned.
+    if let Some(pid) = process.pid {
+        signal_agent_run(
+            host,
+            agent_id,
+            &process.run_id,
+            pid,
+            process.stop_signal,
+            grace_period_ms,
+        );
     }
+    Ok(())
+}
 
-    if command_exists("node") {
-        let mut command = Command::new("node");
-        command.args([script_path_string.as_str(), prompt, repo_path]);
-        return Ok(command);
+/// Sends the stop signal to a run, then kills its whole process group if anything
+/// in it is still alive after the grace period.
+fn signal_agent_run(
+    host: &Host,
+    agent_id: i64,
+    run_id: &str,
+    pid: u32,
+    stop_signal: AgentStopSignal,
+    grace_period_ms: Option<u64>,
+) {
+    if let Err(error) = signal_agent_process(pid, Some(stop_signal)) {
+        eprintln!("Failed to signal agent run {}: {}", run_id, error);
     }
 
-    Err("Simulator mode requires `bun` or `node` in PATH".to_string())
+    let grace_period =
+        std::time::Duration::from_millis(grace_period_ms.unwrap_or(DEFAULT_STOP_GRACE_PERIOD_MS));
+    let host_for_escalation = host.clone();
+    let run_id = run_id.to_string();
+    std::thread::spawn(move || {
+        std::thread::sleep(grace_period);
+        // The worker drops the entry once every process holding the agent's output
+        // has exited, so a matching entry means something in the group is still alive.
+        let still_running = host_for_escalation
+            .agent_runtime()
+            .processes_by_agent_id
+            .lock()
+            .map(|guard| {
+                guard
+                    .get(&agent_id)
+                    .is_some_and(|process| process.run_id == run_id)
+            })
+            .unwrap_or(false);
+        // Children that closed or redirected the inherited pipes no longer keep the
+        // entry alive, but still belong to the agent's process group.
+        if still_running || process_group_exists(pid) {
+            let _ = signal_agent_process(pid, None);
+        }
+    });
 }
 
-fn command_exists(command: &str) -> bool {
-    Command::new(command)
-        .arg("--version")
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
+/// Whether any process is left in the group the agent leads.
+#[cfg(not(target_os = "windows"))]
+fn process_group_exists(pid: u32) -> bool {
+    // SAFETY: killpg has no memory-safety preconditions; signal 0 only checks that
+    // the group exists and may be signalled.
+    unsafe { libc::killpg(pid as libc::pid_t, 0) == 0 }
 }
 
-fn strip_ansi_codes(input: &str) -> String {
-    let mut output = String::new();
-    let mut character_iterator = input.chars().peekable();
+#[cfg(target_os = "windows")]
+fn process_group_exists(_pid: u32) -> bool {
+    false
+}
 
-    while let Some(character) = character_iterator.next() {
-        if character != '\u{1b}' {
-            output.push(character);
-            continue;
+/// Sends `signal` to the agent's process tree, or kills it when `signal` is `None`.
+fn signal_agent_process(pid: u32, signal: Option<AgentStopSignal>) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        // Console processes have no window to close, so the polite request may be
+        // refused; the forced kill after the grace period takes care of them.
+        let pid_argument = pid.to_string();
+        let mut arguments = vec!["/T", "/PID", pid_argument.as_str()];
+        if signal.is_none() {
+            arguments.insert(0, "/F");
         }
+        let output = std::process::Command::new("taskkill")
+            .args(&arguments)
+            .creation_flags(0x0800_0000) // CREATE_NO_WINDOW
+            .output()
+            .map_err(|e| format!("Failed to run taskkill: {}", e))?;
 
-        let Some(next_character) = character_iterator.peek().copied() else {
-            break;
-        };
-        if next_character != '[' {
-            continue;
+        if signal.is_none() && !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(if stderr.is_empty() {
+                "Failed to stop running agent".to_string()
+            } else {
+                stderr
+            });
         }
+    }
 
-        let _ = character_iterator.next();
-        while let Some(escape_character) = character_iterator.next() {
-            if ('@'..='~').contains(&escape_character) {
-                break;
-            }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let signal_number = match signal {
+            Some(AgentStopSignal::Interrupt) => libc::SIGINT,
+            Some(AgentStopSignal::Terminate) => libc::SIGTERM,
+            None => libc::SIGKILL,
+        };
+        // The agent leads its own process group, whose id is the agent's pid.
+        // SAFETY: killpg has no memory-safety preconditions.
+        if unsafe { libc::killpg(pid as libc::pid_t, signal_number) } != 0 {
+            return Err(format!(
+                "Failed to stop running agent: {}",
+                std::io::Error::last_os_error()
+            ));
         }
     }
 
-    output
+    Ok(())
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn list_agent_models(
+    host: Host,
+    agent_id: Option<i64>,
+) -> Result<Vec<AgentModelOption>, String> {
+    let backend = match agent_id {
+        Some(agent_id) => {
+            let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+            load_agent_backend(&conn, agent_id)?
+        }
+        None => create_agent_backend(AgentBackendKind::CursorAgent, None)?,
+    };
+    backend.list_models()
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn open_in_cursor(path: String) -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
@@ -1591,7 +2597,7 @@ pub fn open_in_cursor(path: String) -> Result<(), String> {
     Ok(())
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn open_in_file_manager(path: String) -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
@@ -40,23 +40,207 @@ impl Database {
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 repo_id INTEGER NOT NULL,
                 name TEXT NOT NULL,
+                worktree_path TEXT,
+                branch_name TEXT,
+                backend TEXT NOT NULL DEFAULT 'cursor-agent',
+                backend_command TEXT,
+                session_id TEXT,
+                timeout_seconds INTEGER,
+                idle_timeout_seconds INTEGER,
+                cpu_time_limit_seconds INTEGER,
+                memory_limit_mb INTEGER,
+                open_files_limit INTEGER,
+                checkpoints_enabled INTEGER NOT NULL DEFAULT 0,
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
                 FOREIGN KEY (repo_id) REFERENCES repos(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS agent_runs (
+                run_id TEXT PRIMARY KEY,
+                agent_id INTEGER NOT NULL,
+                prompt TEXT NOT NULL,
+                model TEXT,
+                working_directory TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'running',
+                exit_code INTEGER,
+                termination_reason TEXT,
+                session_id TEXT,
+                base_tree TEXT,
+                result_tree TEXT,
+                patch TEXT,
+                reverted_at DATETIME,
+                checkpoint_ref TEXT,
+                reported_model TEXT,
+                duration_ms INTEGER,
+                api_duration_ms INTEGER,
+                input_tokens INTEGER,
+                output_tokens INTEGER,
+                cache_read_tokens INTEGER,
+                cache_write_tokens INTEGER,
+                cost_usd REAL,
+                started_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                finished_at DATETIME,
+                FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_agent_runs_agent_id ON agent_runs(agent_id);
+
+            CREATE TABLE IF NOT EXISTS agent_run_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                run_id TEXT NOT NULL,
+                sequence INTEGER NOT NULL,
+                stream TEXT NOT NULL,
+                line TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (run_id) REFERENCES agent_runs(run_id) ON DELETE CASCADE
+            );
+
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_agent_run_events_run_sequence
+                ON agent_run_events(run_id, sequence);
+
+            CREATE TABLE IF NOT EXISTS agent_run_changed_files (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                run_id TEXT NOT NULL,
+                path TEXT NOT NULL,
+                status TEXT NOT NULL,
+                additions INTEGER,
+                deletions INTEGER,
+                FOREIGN KEY (run_id) REFERENCES agent_runs(run_id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_agent_run_changed_files_run_id
+                ON agent_run_changed_files(run_id);
+
+            CREATE TABLE IF NOT EXISTS agent_policy_violations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                run_id TEXT NOT NULL,
+                agent_id INTEGER NOT NULL,
+                call_id TEXT NOT NULL,
+                tool TEXT NOT NULL,
+                rule TEXT NOT NULL,
+                detail TEXT NOT NULL,
+                action TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (run_id) REFERENCES agent_runs(run_id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_agent_policy_violations_agent_id
+                ON agent_policy_violations(agent_id);
+
+            CREATE TABLE IF NOT EXISTS agent_batches (
+                batch_id TEXT PRIMARY KEY,
+                group_id INTEGER NOT NULL,
+                prompt TEXT NOT NULL,
+                model TEXT,
+                fresh_branch INTEGER NOT NULL DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS agent_batch_runs (
+                batch_id TEXT NOT NULL,
+                repo_id INTEGER NOT NULL,
+                agent_id INTEGER,
+                run_id TEXT NOT NULL UNIQUE,
+                status TEXT NOT NULL DEFAULT 'pending',
+                error TEXT,
+                PRIMARY KEY (batch_id, repo_id),
+                FOREIGN KEY (batch_id) REFERENCES agent_batches(batch_id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS agent_schedules (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                agent_id INTEGER NOT NULL,
+                cron TEXT NOT NULL,
+                prompt TEXT NOT NULL,
+                model TEXT,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                missed_run_policy TEXT NOT NULL DEFAULT 'run_once',
+                last_run_at TEXT,
+                last_run_id TEXT,
+                last_error TEXT,
+                next_run_at TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_agent_schedules_next_run_at
+                ON agent_schedules(next_run_at);
+
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS access_tokens (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                token_hash TEXT NOT NULL UNIQUE,
+                scopes TEXT NOT NULL,
+                expires_at TEXT,
+                last_used_at TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
             );",
         )?;
 
-        // Migration: add group_id column if it doesn't exist (for existing databases)
-        let has_group_id: bool = conn
-            .prepare("SELECT COUNT(*) FROM pragma_table_info('repos') WHERE name='group_id'")
-            .and_then(|mut stmt| stmt.query_row([], |row| row.get::<_, i64>(0)))
-            .map(|count| count > 0)
-            .unwrap_or(false);
-
-        if !has_group_id {
-            conn.execute_batch(
-                "ALTER TABLE repos ADD COLUMN group_id INTEGER REFERENCES groups(id) ON DELETE SET NULL;",
-            )?;
-        }
+        // Migrations: add columns introduced after the initial schema (for existing databases)
+        add_column_if_missing(
+            &conn,
+            "repos",
+            "group_id",
+            "INTEGER REFERENCES groups(id) ON DELETE SET NULL",
+        )?;
+        add_column_if_missing(&conn, "repos", "max_concurrent_runs", "INTEGER")?;
+        add_column_if_missing(
+            &conn,
+            "access_tokens",
+            "kind",
+            "TEXT NOT NULL DEFAULT 'token'",
+        )?;
+        add_column_if_missing(&conn, "agents", "worktree_path", "TEXT")?;
+        add_column_if_missing(&conn, "agents", "branch_name", "TEXT")?;
+        add_column_if_missing(
+            &conn,
+            "agents",
+            "backend",
+            "TEXT NOT NULL DEFAULT 'cursor-agent'",
+        )?;
+        add_column_if_missing(&conn, "agents", "backend_command", "TEXT")?;
+        add_column_if_missing(&conn, "agents", "session_id", "TEXT")?;
+        add_column_if_missing(&conn, "agent_runs", "session_id", "TEXT")?;
+        add_column_if_missing(&conn, "agents", "timeout_seconds", "INTEGER")?;
+        add_column_if_missing(&conn, "agents", "idle_timeout_seconds", "INTEGER")?;
+        add_column_if_missing(&conn, "agents", "cpu_time_limit_seconds", "INTEGER")?;
+        add_column_if_missing(&conn, "agents", "memory_limit_mb", "INTEGER")?;
+        add_column_if_missing(&conn, "agents", "open_files_limit", "INTEGER")?;
+        add_column_if_missing(&conn, "agent_runs", "termination_reason", "TEXT")?;
+        add_column_if_missing(&conn, "agent_runs", "base_tree", "TEXT")?;
+        add_column_if_missing(&conn, "agent_runs", "result_tree", "TEXT")?;
+        add_column_if_missing(&conn, "agent_runs", "patch", "TEXT")?;
+        add_column_if_missing(&conn, "agent_runs", "reverted_at", "DATETIME")?;
+        add_column_if_missing(
+            &conn,
+            "agents",
+            "checkpoints_enabled",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        add_column_if_missing(&conn, "agent_runs", "checkpoint_ref", "TEXT")?;
+        add_column_if_missing(&conn, "agent_runs", "reported_model", "TEXT")?;
+        add_column_if_missing(&conn, "agent_runs", "duration_ms", "INTEGER")?;
+        add_column_if_missing(&conn, "agent_runs", "api_duration_ms", "INTEGER")?;
+        add_column_if_missing(&conn, "agent_runs", "input_tokens", "INTEGER")?;
+        add_column_if_missing(&conn, "agent_runs", "output_tokens", "INTEGER")?;
+        add_column_if_missing(&conn, "agent_runs", "cache_read_tokens", "INTEGER")?;
+        add_column_if_missing(&conn, "agent_runs", "cache_write_tokens", "INTEGER")?;
+        add_column_if_missing(&conn, "agent_runs", "cost_usd", "REAL")?;
+
+        // No run outlives the process that started it, so runs still marked as
+        // running were cut short by a crash or a kill.
+        conn.execute(
+            "UPDATE agent_runs
+             SET status = 'failed', finished_at = CURRENT_TIMESTAMP
+             WHERE status = 'running'",
+            [],
+        )?;
 
         Ok(Self {
             conn: Mutex::new(conn),
@@ -64,6 +248,31 @@ impl Database {
     }
 }
 
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let has_column: bool = conn
+        .prepare(&format!(
+            "SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name='{}'",
+            table, column
+        ))
+        .and_then(|mut stmt| stmt.query_row([], |row| row.get::<_, i64>(0)))
+        .map(|count| count > 0)
+        .unwrap_or(false);
+
+    if !has_column {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {};",
+            table, column, definition
+        ))?;
+    }
+
+    Ok(())
+}
+
 fn get_db_path() -> PathBuf {
     let home = dirs::home_dir().expect("Could not find home directory");
     home.join(".symphony").join("symphony.db")
@@ -0,0 +1,249 @@
+use crate::access_tokens::{format_time, insert_access_token, AccessScope, AccessTokenKind};
+use crate::commands::emit_runtime_event;
+use crate::host::Host;
+use crate::host_api::web_pairing_url;
+use chrono::{DateTime, Duration, Utc};
+use qrcode::{render::svg, QrCode};
+use rand::Rng;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+/// Letters and digits that are hard to mix up when typed from another screen.
+const PAIRING_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const PAIRING_CODE_LENGTH: usize = 8;
+const PAIRING_CODE_LIFETIME_MINUTES: i64 = 10;
+/// Wrong codes accepted from one address before it may no longer try the pending
+/// code. Other addresses can still pair with it.
+const MAX_FAILED_PAIRING_ATTEMPTS: u32 = 5;
+const MAX_DEVICE_NAME_LENGTH: usize = 80;
+const DEFAULT_DEVICE_SCOPES: [AccessScope; 3] = [
+    AccessScope::Read,
+    AccessScope::RunAgents,
+    AccessScope::ManageRepos,
+];
+
+/// The pairing code the host is waiting for, if any. Only one code is valid at a
+/// time, and it can be exchanged once.
+#[derive(Default)]
+pub struct DevicePairingState {
+    pending: Mutex<Option<PendingPairing>>,
+}
+
+struct PendingPairing {
+    code: String,
+    scopes: Vec<AccessScope>,
+    expires_at: DateTime<Utc>,
+    failed_attempts_by_address: HashMap<IpAddr, u32>,
+}
+
+impl PendingPairing {
+    /// Checks a code typed on the device at `remote_address`, counting wrong codes
+    /// per address so one client guessing cannot cancel another's pairing.
+    fn verify_code(&mut self, code: &str, remote_address: IpAddr) -> Result<(), String> {
+        let failed_attempts = self
+            .failed_attempts_by_address
+            .entry(remote_address)
+            .or_default();
+        if *failed_attempts >= MAX_FAILED_PAIRING_ATTEMPTS {
+            return Err(
+                "Too many wrong pairing codes from this device; start a new pairing".to_string(),
+            );
+        }
+        if normalize_pairing_code(code) != self.code {
+            *failed_attempts += 1;
+            return Err("The pairing code is not valid".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DevicePairing {
+    /// Shown as `XXXX-XXXX`; the dash is optional when typed.
+    pub code: String,
+    /// Opens the web UI and pairs with the code.
+    pub pairing_url: String,
+    /// A QR code of `pairing_url`, as an SVG document.
+    pub qr_svg: String,
+    pub expires_at: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DevicePairedPayload {
+    pub token_id: i64,
+    pub name: String,
+}
+
+fn generate_pairing_code() -> String {
+    let mut rng = rand::rng();
+    (0..PAIRING_CODE_LENGTH)
+        .map(|_| {
+            let index = rng.random_range(0..PAIRING_CODE_ALPHABET.len());
+            char::from(PAIRING_CODE_ALPHABET[index])
+        })
+        .collect()
+}
+
+fn format_pairing_code(code: &str) -> String {
+    let (first, second) = code.split_at(PAIRING_CODE_LENGTH / 2);
+    format!("{}-{}", first, second)
+}
+
+fn normalize_pairing_code(code: &str) -> String {
+    code.chars()
+        .filter(|character| character.is_ascii_alphanumeric())
+        .map(|character| character.to_ascii_uppercase())
+        .collect()
+}
+
+fn render_qr_svg(url: &str) -> Result<String, String> {
+    let qr_code = QrCode::new(url.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(qr_code
+        .render::<svg::Color<'_>>()
+        .min_dimensions(200, 200)
+        .quiet_zone(true)
+        .build())
+}
+
+/// Replaces any pending pairing with a new code.
+fn begin_device_pairing(host: &Host, scopes: Vec<AccessScope>) -> Result<DevicePairing, String> {
+    let raw_code = generate_pairing_code();
+    let code = format_pairing_code(&raw_code);
+    let expires_at = Utc::now() + Duration::minutes(PAIRING_CODE_LIFETIME_MINUTES);
+    let pairing_url = web_pairing_url(&code);
+    let qr_svg = render_qr_svg(&pairing_url)?;
+
+    let mut pending = host.pairing().pending.lock().map_err(|e| e.to_string())?;
+    *pending = Some(PendingPairing {
+        code: raw_code,
+        scopes,
+        expires_at,
+        failed_attempts_by_address: HashMap::new(),
+    });
+    Ok(DevicePairing {
+        code,
+        pairing_url,
+        qr_svg,
+        expires_at: format_time(expires_at),
+    })
+}
+
+/// Exchanges the pending pairing code for a new device token and returns its
+/// secret. The code stops working once used or once it expires, and for addresses
+/// that sent too many wrong codes.
+pub(crate) fn pair_device(
+    host: &Host,
+    code: &str,
+    device_name: &str,
+    remote_address: IpAddr,
+) -> Result<String, String> {
+    let scopes = {
+        let mut pending_guard = host.pairing().pending.lock().map_err(|e| e.to_string())?;
+        let Some(pending) = pending_guard.as_mut() else {
+            return Err("No device pairing is in progress".to_string());
+        };
+        if pending.expires_at <= Utc::now() {
+            *pending_guard = None;
+            return Err("The pairing code has expired".to_string());
+        }
+        pending.verify_code(code, remote_address)?;
+        let scopes = std::mem::take(&mut pending.scopes);
+        *pending_guard = None;
+        scopes
+    };
+
+    let name: String = device_name
+        .trim()
+        .chars()
+        .take(MAX_DEVICE_NAME_LENGTH)
+        .collect();
+    let name = if name.is_empty() {
+        "Unnamed device".to_string()
+    } else {
+        name
+    };
+    let (token, secret) = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        insert_access_token(&conn, &name, AccessTokenKind::Device, &scopes, None)?
+    };
+
+    emit_runtime_event(
+        host,
+        "host-device-paired",
+        DevicePairedPayload {
+            token_id: token.id,
+            name: token.name,
+        },
+    );
+    Ok(secret)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn start_device_pairing(
+    host: Host,
+    scopes: Option<Vec<AccessScope>>,
+) -> Result<DevicePairing, String> {
+    let scopes = scopes.unwrap_or_else(|| DEFAULT_DEVICE_SCOPES.to_vec());
+    if scopes.is_empty() {
+        return Err("Choose at least one scope".to_string());
+    }
+    begin_device_pairing(&host, scopes)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn cancel_device_pairing(host: Host) -> Result<(), String> {
+    let mut pending = host.pairing().pending.lock().map_err(|e| e.to_string())?;
+    *pending = None;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_pairing() -> PendingPairing {
+        PendingPairing {
+            code: "ABCD2345".to_string(),
+            scopes: DEFAULT_DEVICE_SCOPES.to_vec(),
+            expires_at: Utc::now() + Duration::minutes(PAIRING_CODE_LIFETIME_MINUTES),
+            failed_attempts_by_address: HashMap::new(),
+        }
+    }
+
+    fn address(last_octet: u8) -> IpAddr {
+        IpAddr::from([192, 168, 1, last_octet])
+    }
+
+    #[test]
+    fn accepts_the_code_with_or_without_the_dash() {
+        let mut pending = pending_pairing();
+        assert!(pending.verify_code("abcd-2345", address(10)).is_ok());
+        assert!(pending.verify_code("ABCD2345", address(10)).is_ok());
+    }
+
+    #[test]
+    fn locks_out_an_address_after_too_many_wrong_codes() {
+        let mut pending = pending_pairing();
+        for _ in 0..MAX_FAILED_PAIRING_ATTEMPTS {
+            assert_eq!(
+                pending.verify_code("WRONG-CODE", address(66)),
+                Err("The pairing code is not valid".to_string())
+            );
+        }
+        let error = pending.verify_code("ABCD-2345", address(66)).unwrap_err();
+        assert!(error.starts_with("Too many wrong pairing codes"));
+    }
+
+    #[test]
+    fn wrong_codes_from_one_address_do_not_cancel_the_pairing_for_others() {
+        let mut pending = pending_pairing();
+        for _ in 0..MAX_FAILED_PAIRING_ATTEMPTS + 3 {
+            let _ = pending.verify_code("WRONG-CODE", address(66));
+        }
+        assert!(pending.verify_code("ABCD-2345", address(10)).is_ok());
+    }
+}
@@ -0,0 +1,86 @@
+use crate::commands::AgentRuntimeState;
+use crate::db::Database;
+use crate::device_pairing::DevicePairingState;
+use crate::host_api::{HostAccessState, HostBridgeState};
+use std::sync::Arc;
+#[cfg(feature = "desktop")]
+use std::sync::OnceLock;
+#[cfg(feature = "desktop")]
+use tauri::ipc::{CommandArg, CommandItem, InvokeError};
+#[cfg(feature = "desktop")]
+use tauri::{AppHandle, Runtime, State};
+
+/// The state of a running Symphony host, shared by the desktop app and the headless
+/// daemon. Clones are cheap and share the same state.
+#[derive(Clone)]
+pub struct Host {
+    inner: Arc<HostInner>,
+}
+
+struct HostInner {
+    db: Database,
+    agent_runtime: AgentRuntimeState,
+    bridge: HostBridgeState,
+    access: HostAccessState,
+    pairing: DevicePairingState,
+    /// Set when running in the desktop app, whose webview also receives events.
+    #[cfg(feature = "desktop")]
+    app: OnceLock<AppHandle>,
+}
+
+impl Host {
+    pub fn new(db: Database, bridge: HostBridgeState, access: HostAccessState) -> Self {
+        Self {
+            inner: Arc::new(HostInner {
+                db,
+                agent_runtime: AgentRuntimeState::default(),
+                bridge,
+                access,
+                pairing: DevicePairingState::default(),
+                #[cfg(feature = "desktop")]
+                app: OnceLock::new(),
+            }),
+        }
+    }
+
+    pub fn db(&self) -> &Database {
+        &self.inner.db
+    }
+
+    pub fn agent_runtime(&self) -> &AgentRuntimeState {
+        &self.inner.agent_runtime
+    }
+
+    pub fn bridge(&self) -> &HostBridgeState {
+        &self.inner.bridge
+    }
+
+    pub fn access(&self) -> &HostAccessState {
+        &self.inner.access
+    }
+
+    pub fn pairing(&self) -> &DevicePairingState {
+        &self.inner.pairing
+    }
+
+    #[cfg(feature = "desktop")]
+    pub fn attach_app(&self, app: AppHandle) {
+        let _ = self.inner.app.set(app);
+    }
+
+    /// The desktop app, `None` in the headless daemon.
+    #[cfg(feature = "desktop")]
+    pub fn app(&self) -> Option<&AppHandle> {
+        self.inner.app.get()
+    }
+}
+
+/// Lets Tauri commands take the managed `Host` by value, so the host bridge can
+/// call the same commands without a Tauri app.
+#[cfg(feature = "desktop")]
+impl<'de, R: Runtime> CommandArg<'de, R> for Host {
+    fn from_command(command: CommandItem<'de, R>) -> Result<Self, InvokeError> {
+        let host: State<'_, Host> = CommandArg::from_command(command)?;
+        Ok(host.inner().clone())
+    }
+}
@@ -1,13 +1,47 @@
+use crate::access_tokens::{
+    authenticate_access_token, command_scope, create_access_token, is_scoped_access_token,
+    list_access_tokens, list_paired_devices, revoke_access_token, AccessGrant, AccessScope,
+    ACCESS_TOKEN_REVOKED_EVENT,
+};
+use crate::agent_approvals::{approve_tool_call, deny_tool_call};
+use crate::agent_batches::{
+    delete_agent_batch, get_agent_batch, list_agent_batches, run_group_agents,
+};
+use crate::agent_input::send_agent_input;
+use crate::agent_limits::AgentRunLimits;
+use crate::agent_policy::list_agent_policy_violations;
+use crate::agent_queue::{cancel_queued_prompt, list_agent_queue, reorder_agent_queue};
+use crate::agent_run_changes::{get_agent_run_changes, revert_agent_run};
+use crate::agent_scheduler::{
+    get_scheduler_settings, list_scheduled_runs, set_repo_run_limit, set_scheduler_settings,
+};
+use crate::agent_schedules::{
+    create_agent_schedule, delete_agent_schedule, list_agent_schedules, set_agent_schedule_enabled,
+    MissedRunPolicy,
+};
+use crate::agent_usage::{get_agent_stats, get_group_stats, get_repo_stats};
+use crate::async_runtime;
+use crate::checkpoints::{
+    delete_agent_checkpoint, list_agent_checkpoints, restore_agent_checkpoint,
+    set_agent_checkpoints,
+};
 use crate::commands::{
     add_repo, clone_repo, commit_working_tree, create_agent, create_group, create_local_branch,
-    delete_agent, delete_group, delete_local_branch, get_commit_changes, get_current_branch,
-    get_remote_url, get_repo_sync_status, get_repo_working_tree_status, get_working_tree_file_diff,
-    list_agent_models, list_agents, list_git_history, list_groups, list_local_branches, list_repos,
-    list_working_tree_changes, move_repo_to_group, open_in_cursor, open_in_file_manager, pull_repo,
-    remove_repo, rename_agent, rename_group, run_repo_agent, stop_repo_agent, switch_branch,
-    AgentRuntimeState,
+    delete_agent, delete_group, delete_local_branch, get_agent_run_transcript, get_commit_changes,
+    get_current_branch, get_remote_url, get_repo_sync_status, get_repo_working_tree_status,
+    get_working_tree_file_diff, list_agent_models, list_agent_runs, list_agents, list_git_history,
+    list_groups, list_local_branches, list_repos, list_working_tree_changes, move_repo_to_group,
+    open_in_cursor, open_in_file_manager, pull_repo, remove_repo, rename_agent, rename_group,
+    reset_agent_session, run_repo_agent, set_agent_backend, set_agent_run_limits, stop_repo_agent,
+    switch_branch, AgentRunOptions,
+};
+use crate::device_pairing::{cancel_device_pairing, pair_device, start_device_pairing};
+use crate::host::Host;
+use crate::worktrees::{
+    cleanup_stale_worktrees, create_agent_worktree, discard_agent_worktree, merge_agent_worktree,
+    rebase_agent_worktree,
 };
-use crate::db::Database;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::{ConnectInfo, Query, State as AxumState};
 use axum::http::header::AUTHORIZATION;
 use axum::http::{HeaderMap, StatusCode};
@@ -17,27 +51,65 @@ use axum::response::Response;
 use axum::routing::{get, post};
 use axum::Json;
 use axum::Router;
-use qrcode::{render::unicode, QrCode};
+use futures_util::SinkExt;
 use rand::distr::Alphanumeric;
 use rand::Rng;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::Infallible;
 use std::fs;
 use std::net::{IpAddr, SocketAddr, UdpSocket};
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use tauri::{Manager, State as TauriState};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
-use tokio::sync::broadcast;
-use tokio_stream::wrappers::BroadcastStream;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt;
 use tower_http::cors::{Any, CorsLayer};
 
+/// Events kept for clients that reconnect with `Last-Event-ID`. At least as many as
+/// the broadcast channel holds, so a lagging subscriber can catch up from it.
+const EVENT_HISTORY_CAPACITY: usize = 4096;
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+/// Sent instead of events that are no longer in the history. Clients should reload
+/// their state when they receive it.
+const EVENT_GAP_EVENT: &str = "host-events-gap";
+/// How often open event streams check that their token still works.
+const TOKEN_REVALIDATION_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a stream ticket can be used to open an event stream.
+const STREAM_TICKET_LIFETIME: Duration = Duration::from_secs(60);
+
+/// Sent to clients as `<epoch>-<seq>`. The epoch is picked when the host starts, so
+/// ids from an earlier host process are never mistaken for ones of this process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BridgeEventId {
+    epoch: u64,
+    seq: u64,
+}
+
+impl BridgeEventId {
+    fn parse(value: &str) -> Option<Self> {
+        let (epoch, seq) = value.trim().split_once('-')?;
+        Some(Self {
+            epoch: u64::from_str_radix(epoch, 16).ok()?,
+            seq: seq.parse().ok()?,
+        })
+    }
+}
+
+impl std::fmt::Display for BridgeEventId {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{:x}-{}", self.epoch, self.seq)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BridgeEvent {
+    pub id: BridgeEventId,
     pub event: String,
     pub payload: Value,
 }
@@ -48,35 +120,128 @@ struct BridgeEventEnvelope {
     payload: Value,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BridgeEventGapPayload {
+    /// The last event the client received.
+    last_event_id: String,
+    /// The oldest event that could still be replayed; the ones in between are lost.
+    next_event_id: String,
+}
+
+struct BridgeEventHistory {
+    next_seq: u64,
+    events: VecDeque<BridgeEvent>,
+}
+
+/// Events to send a client before live ones.
+struct BridgeEventReplay {
+    gap: Option<BridgeEventGapPayload>,
+    events: Vec<BridgeEvent>,
+    /// The sequence number the replay starts after; later live events follow it.
+    after_seq: u64,
+}
+
 #[derive(Clone)]
 pub struct HostBridgeState {
+    epoch: u64,
     sender: broadcast::Sender<BridgeEvent>,
+    history: Arc<Mutex<BridgeEventHistory>>,
 }
 
 impl HostBridgeState {
     pub fn new() -> Self {
-        let (sender, _) = broadcast::channel(1024);
-        Self { sender }
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            epoch: rand::random(),
+            sender,
+            history: Arc::new(Mutex::new(BridgeEventHistory {
+                next_seq: 1,
+                events: VecDeque::with_capacity(EVENT_HISTORY_CAPACITY),
+            })),
+        }
     }
 
     pub fn send_event(&self, event: &str, payload: Value) {
-        let _ = self.sender.send(BridgeEvent {
+        let Ok(mut history) = self.history.lock() else {
+            return;
+        };
+        let event = BridgeEvent {
+            id: self.event_id(history.next_seq),
             event: event.to_string(),
             payload,
-        });
+        };
+        history.next_seq += 1;
+        if history.events.len() == EVENT_HISTORY_CAPACITY {
+            history.events.pop_front();
+        }
+        history.events.push_back(event.clone());
+        // Sent while the history is locked, so subscribers see events in id order.
+        let _ = self.sender.send(event);
+    }
+
+    fn event_id(&self, seq: u64) -> BridgeEventId {
+        BridgeEventId {
+            epoch: self.epoch,
+            seq,
+        }
     }
 
+    /// Subscribes to new events.
     fn subscribe(&self) -> broadcast::Receiver<BridgeEvent> {
         self.sender.subscribe()
     }
+
+    /// The id of the last event sent so far.
+    fn latest_event_id(&self) -> BridgeEventId {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        self.event_id(history.next_seq - 1)
+    }
+
+    /// The events after `last_event_id` that are still in the history. An id this
+    /// host never sent, e.g. one from an earlier host process, is reported as a gap
+    /// and the whole history is replayed.
+    fn replay_after(&self, last_event_id: BridgeEventId) -> BridgeEventReplay {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        let oldest_seq = history
+            .events
+            .front()
+            .map_or(history.next_seq, |event| event.id.seq);
+        let is_known = last_event_id.epoch == self.epoch && last_event_id.seq < history.next_seq;
+        let gap = (!is_known || last_event_id.seq.saturating_add(1) < oldest_seq).then(|| {
+            BridgeEventGapPayload {
+                last_event_id: last_event_id.to_string(),
+                next_event_id: self.event_id(oldest_seq).to_string(),
+            }
+        });
+        let after_seq = if is_known { last_event_id.seq } else { 0 };
+        let events = history
+            .events
+            .iter()
+            .filter(|event| event.id.seq > after_seq)
+            .cloned()
+            .collect();
+        BridgeEventReplay {
+            gap,
+            events,
+            after_seq,
+        }
+    }
 }
 
 #[derive(Clone)]
 struct HttpBridgeAppState {
-    app: tauri::AppHandle,
-    events: HostBridgeState,
+    host: Host,
     auth_token: String,
     allow_lan_access: Arc<AtomicBool>,
+    stream_tickets: Arc<Mutex<HashMap<String, StreamTicket>>>,
+}
+
+/// Lets browsers, which cannot set headers on event streams, open one without
+/// putting their token in the URL. Each ticket works once.
+struct StreamTicket {
+    token: String,
+    expires_at: Instant,
 }
 
 #[derive(Clone)]
@@ -125,6 +290,13 @@ struct InvokeRequest {
     args: Option<Value>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PairDeviceRequest {
+    code: String,
+    device_name: String,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct InvokeResponse {
@@ -133,9 +305,213 @@ struct InvokeResponse {
     error: Option<String>,
 }
 
+/// Sets which events the connection receives, with the fields of
+/// [`BridgeEventFilter`] as params.
+const WS_SUBSCRIBE_METHOD: &str = "subscribe";
+const WS_PARSE_ERROR: i64 = -32700;
+const WS_INVALID_PARAMS: i64 = -32602;
+const WS_INTERNAL_ERROR: i64 = -32603;
+/// The command ran and returned an error.
+const WS_COMMAND_ERROR: i64 = -32000;
+const WS_UNAUTHORIZED: i64 = -32001;
+
+/// A JSON-RPC style request on `/api/ws`; `method` is a command name as accepted
+/// by `/api/invoke`.
+#[derive(Debug, Deserialize)]
+struct WsRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    params: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct WsResponse {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<WsError>,
+}
+
+#[derive(Debug, Serialize)]
+struct WsError {
+    code: i64,
+    message: String,
+}
+
+impl WsResponse {
+    fn result(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Value, code: i64, message: String) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(WsError { code, message }),
+        }
+    }
+
+    fn into_message(self) -> Message {
+        let text = serde_json::to_string(&self).unwrap_or_else(|_| "null".to_string());
+        Message::Text(text.into())
+    }
+}
+
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct EventQueryParameters {
+    /// Only scoped tokens; the host's own token is refused in URLs.
     token: Option<String>,
+    /// A one-time ticket from `/api/events/ticket`.
+    ticket: Option<String>,
+    /// For clients that cannot set the `Last-Event-ID` header.
+    last_event_id: Option<String>,
+    /// Comma-separated filters, see [`BridgeEventFilter`].
+    events: Option<String>,
+    agent_id: Option<String>,
+    run_id: Option<String>,
+    repo_id: Option<String>,
+}
+
+/// Limits the events a client receives. Each set field must match: `events` by
+/// name, the others by the payload's `agentId`, `runId` or `repoId`. Agent events
+/// match a repo through their agent. Events without the field never match it.
+/// Fields are named as in the query string, with arrays for the lists.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BridgeEventFilter {
+    events: Option<HashSet<String>>,
+    #[serde(rename = "agentId")]
+    agent_ids: Option<HashSet<i64>>,
+    #[serde(rename = "runId")]
+    run_ids: Option<HashSet<String>>,
+    #[serde(rename = "repoId")]
+    repo_ids: Option<HashSet<i64>>,
+}
+
+impl BridgeEventFilter {
+    fn from_query(query: &EventQueryParameters) -> Result<Self, String> {
+        fn parse_list<T: std::str::FromStr + Eq + std::hash::Hash>(
+            name: &str,
+            value: Option<&String>,
+        ) -> Result<Option<HashSet<T>>, String> {
+            value
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(|item| {
+                            item.parse()
+                                .map_err(|_| format!("Invalid {}: {}", name, item))
+                        })
+                        .collect()
+                })
+                .transpose()
+        }
+
+        Ok(Self {
+            events: parse_list("events", query.events.as_ref())?,
+            agent_ids: parse_list("agentId", query.agent_id.as_ref())?,
+            run_ids: parse_list("runId", query.run_id.as_ref())?,
+            repo_ids: parse_list("repoId", query.repo_id.as_ref())?,
+        })
+    }
+
+    /// The agent whose repo `matches` needs for the event, when the filter has repos
+    /// and the event only names its agent.
+    fn agent_for_repo_match(&self, event: &BridgeEvent) -> Option<i64> {
+        self.repo_ids.as_ref()?;
+        let payload = &event.payload;
+        if payload.get("repoId").is_some() {
+            return None;
+        }
+        payload.get("agentId").and_then(Value::as_i64)
+    }
+
+    /// `agent_repo_id` is the repo of the agent from `agent_for_repo_match`.
+    fn matches(&self, event: &BridgeEvent, agent_repo_id: Option<i64>) -> bool {
+        let payload = &event.payload;
+        let agent_id = payload.get("agentId").and_then(Value::as_i64);
+        if self
+            .events
+            .as_ref()
+            .is_some_and(|events| !events.contains(&event.event))
+        {
+            return false;
+        }
+        if let Some(agent_ids) = &self.agent_ids {
+            if !agent_id.is_some_and(|agent_id| agent_ids.contains(&agent_id)) {
+                return false;
+            }
+        }
+        if let Some(run_ids) = &self.run_ids {
+            let run_id = payload.get("runId").and_then(Value::as_str);
+            if !run_id.is_some_and(|run_id| run_ids.contains(run_id)) {
+                return false;
+            }
+        }
+        if let Some(repo_ids) = &self.repo_ids {
+            let repo_id = payload
+                .get("repoId")
+                .and_then(Value::as_i64)
+                .or(agent_repo_id);
+            if !repo_id.is_some_and(|repo_id| repo_ids.contains(&repo_id)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Applies a stream's filter, looking up the repos of agents as needed.
+struct BridgeEventMatcher {
+    host: Host,
+    filter: watch::Receiver<BridgeEventFilter>,
+    agent_repo_ids: HashMap<i64, Option<i64>>,
+}
+
+impl BridgeEventMatcher {
+    async fn is_wanted(&mut self, event: &BridgeEvent) -> bool {
+        // The filter is only borrowed briefly, never across the lookup.
+        let agent_id = self.filter.borrow().agent_for_repo_match(event);
+        let agent_repo_id = match agent_id {
+            Some(agent_id) => match self.agent_repo_ids.get(&agent_id) {
+                Some(repo_id) => *repo_id,
+                None => {
+                    let host = self.host.clone();
+                    let repo_id =
+                        async_runtime::spawn_blocking(move || load_agent_repo_id(&host, agent_id))
+                            .await
+                            .ok()
+                            .flatten();
+                    self.agent_repo_ids.insert(agent_id, repo_id);
+                    repo_id
+                }
+            },
+            None => None,
+        };
+        self.filter.borrow().matches(event, agent_repo_id)
+    }
+}
+
+fn load_agent_repo_id(host: &Host, agent_id: i64) -> Option<i64> {
+    let conn = host.db().conn.lock().ok()?;
+    conn.query_row(
+        "SELECT repo_id FROM agents WHERE id = ?1",
+        rusqlite::params![agent_id],
+        |row| row.get(0),
+    )
+    .ok()
 }
 
 #[derive(Debug, Deserialize)]
@@ -161,6 +537,23 @@ struct RepoIdArgs {
 struct CreateAgentArgs {
     repo_id: i64,
     name: String,
+    isolated_workspace: Option<bool>,
+    backend: Option<String>,
+    backend_command: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetAgentBackendArgs {
+    agent_id: i64,
+    backend: String,
+    backend_command: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListAgentModelsArgs {
+    agent_id: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -169,6 +562,106 @@ struct AgentIdArgs {
     agent_id: i64,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetAgentRunLimitsArgs {
+    agent_id: i64,
+    limits: AgentRunLimits,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetAgentCheckpointsArgs {
+    agent_id: i64,
+    enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentCheckpointArgs {
+    agent_id: i64,
+    run_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StopRepoAgentArgs {
+    agent_id: i64,
+    grace_period_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendAgentInputArgs {
+    agent_id: i64,
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApproveToolCallArgs {
+    agent_id: i64,
+    call_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DenyToolCallArgs {
+    agent_id: i64,
+    call_id: String,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetSchedulerSettingsArgs {
+    max_concurrent_runs: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateAgentScheduleArgs {
+    agent_id: i64,
+    cron: String,
+    prompt: String,
+    model: Option<String>,
+    missed_run_policy: Option<MissedRunPolicy>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetAgentScheduleEnabledArgs {
+    schedule_id: i64,
+    enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScheduleIdArgs {
+    schedule_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetRepoRunLimitArgs {
+    repo_id: i64,
+    max_concurrent_runs: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReorderAgentQueueArgs {
+    agent_id: i64,
+    run_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CancelQueuedPromptArgs {
+    agent_id: i64,
+    run_id: String,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct RenameAgentArgs {
@@ -257,9 +750,27 @@ struct RunRepoAgentArgs {
     prompt: String,
     agent_id: i64,
     run_id: String,
-    force_approve: Option<bool>,
-    simulate_mode: Option<bool>,
-    model: Option<String>,
+    options: Option<AgentRunOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListAgentRunsArgs {
+    agent_id: i64,
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListAgentPolicyViolationsArgs {
+    agent_id: i64,
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RunIdArgs {
+    run_id: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -268,6 +779,29 @@ struct GroupIdArgs {
     id: i64,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GroupStatsArgs {
+    group_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RunGroupAgentsArgs {
+    group_id: i64,
+    prompt: String,
+    model: Option<String>,
+    fresh_branch: Option<bool>,
+    force_approve: Option<bool>,
+    simulate_mode: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchIdArgs {
+    batch_id: String,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct CreateGroupArgs {
@@ -281,6 +815,26 @@ struct RenameGroupArgs {
     name: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateAccessTokenArgs {
+    name: String,
+    scopes: Vec<AccessScope>,
+    expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenIdArgs {
+    token_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StartDevicePairingArgs {
+    scopes: Option<Vec<AccessScope>>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct MoveRepoToGroupArgs {
@@ -311,15 +865,45 @@ fn extract_bearer_token(headers: &HeaderMap) -> Option<String> {
         })
 }
 
-fn is_authorized(headers: &HeaderMap, query_token: Option<&str>, expected_token: &str) -> bool {
-    let header_token = extract_bearer_token(headers);
-    let token = header_token.or_else(|| {
-        query_token
-            .map(str::trim)
-            .filter(|value| !value.is_empty())
-            .map(ToString::to_string)
-    });
-    token.as_deref() == Some(expected_token)
+/// The token of an event stream request. URLs end up in logs, so the query string
+/// only takes scoped tokens and one-time tickets, never the host's own token.
+fn stream_request_token(
+    state: &HttpBridgeAppState,
+    headers: &HeaderMap,
+    query: &EventQueryParameters,
+) -> Option<String> {
+    if let Some(token) = extract_bearer_token(headers) {
+        return Some(token);
+    }
+    if let Some(ticket) = query.ticket.as_deref() {
+        return redeem_stream_ticket(state, ticket.trim());
+    }
+    query
+        .token
+        .as_deref()
+        .map(str::trim)
+        .filter(|token| is_scoped_access_token(token) && *token != state.auth_token)
+        .map(ToString::to_string)
+}
+
+fn redeem_stream_ticket(state: &HttpBridgeAppState, ticket: &str) -> Option<String> {
+    let ticket = state.stream_tickets.lock().ok()?.remove(ticket)?;
+    (ticket.expires_at > Instant::now()).then_some(ticket.token)
+}
+
+/// The host's own token may do everything; other tokens are looked up with their
+/// scopes, on a blocking thread since that uses the database.
+async fn authorize_token(state: &HttpBridgeAppState, token: Option<&str>) -> Option<AccessGrant> {
+    let token = token?;
+    if token == state.auth_token {
+        return Some(AccessGrant::admin());
+    }
+    let host = state.host.clone();
+    let token = token.to_string();
+    async_runtime::spawn_blocking(move || authenticate_access_token(&host, &token))
+        .await
+        .ok()
+        .flatten()
 }
 
 fn read_web_port() -> u16 {
@@ -336,10 +920,9 @@ fn get_or_generate_auth_token() -> String {
             .take(40)
             .map(char::from)
             .collect();
-        println!(
-            "SYMPHONY_HOST_TOKEN was not set. Generated session token: {}",
-            generated
-        );
+        // Never printed, since it grants everything. Devices pair instead, and the
+        // command-line client needs SYMPHONY_HOST_TOKEN to be set.
+        println!("SYMPHONY_HOST_TOKEN was not set; generated a token for this session.");
         generated
     })
 }
@@ -399,31 +982,12 @@ fn detect_local_ip_address() -> Option<IpAddr> {
     Some(socket.local_addr().ok()?.ip())
 }
 
-fn print_access_qr_code(auth_token: &str) {
-    let web_port = read_web_port();
-    let localhost_url = format!("http://localhost:{}?access_token={}", web_port, auth_token);
-    let lan_url = detect_local_ip_address()
-        .map(|ip| format!("http://{}:{}?access_token={}", ip, web_port, auth_token));
-    let qr_target_url = lan_url.as_deref().unwrap_or(localhost_url.as_str());
-
-    println!("Symphony web access URL (localhost): {}", localhost_url);
-    if let Some(url) = lan_url.as_ref() {
-        println!("Symphony web access URL (mobile/LAN): {}", url);
-    }
-
-    match QrCode::new(qr_target_url.as_bytes()) {
-        Ok(qr_code) => {
-            let rendered = qr_code
-                .render::<unicode::Dense1x2>()
-                .quiet_zone(true)
-                .build();
-            println!("Scan this QR code to open Symphony in your browser:");
-            println!("{}", rendered);
-        }
-        Err(error) => {
-            eprintln!("Failed to generate QR code for web access URL: {}", error);
-        }
-    }
+fn print_web_access_hint() {
+    println!(
+        "Symphony web access URL (localhost): http://localhost:{}",
+        read_web_port()
+    );
+    println!("Pair a phone or browser from Settings -> Paired devices, or run `symphony pair`.");
 }
 
 fn client_access_allowed(remote_address: SocketAddr, allow_lan_access: bool) -> bool {
@@ -436,16 +1000,15 @@ fn current_host_access_settings(state: &HostAccessState) -> HostAccessSettings {
     }
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn get_host_access_settings(state: TauriState<'_, HostAccessState>) -> HostAccessSettings {
-    current_host_access_settings(&state)
+pub fn get_host_access_settings(host: Host) -> HostAccessSettings {
+    current_host_access_settings(host.access())
 }
 
-#[tauri::command]
-pub fn set_host_access_settings(
-    state: TauriState<'_, HostAccessState>,
-    allow_lan_access: bool,
-) -> HostAccessSettings {
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn set_host_access_settings(host: Host, allow_lan_access: bool) -> HostAccessSettings {
+    let state = host.access();
     state
         .allow_lan_access
         .store(allow_lan_access, Ordering::Relaxed);
@@ -454,43 +1017,62 @@ pub fn set_host_access_settings(
             eprintln!("Failed to persist host access settings: {}", error);
         }
     }
-    current_host_access_settings(&state)
+    current_host_access_settings(state)
 }
 
+/// The web UI's URL without credentials; devices sign in by pairing.
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn get_lan_listen_url(state: TauriState<'_, HostAccessState>) -> Option<String> {
+pub fn get_lan_listen_url(host: Host) -> Option<String> {
+    let state = host.access();
     if !state.allow_lan_access.load(Ordering::Relaxed) {
         return None;
     }
+    Some(web_base_url())
+}
+
+fn web_base_url() -> String {
     let port = read_web_port();
-    let base_url = detect_local_ip_address()
+    detect_local_ip_address()
         .map(|ip| format!("http://{}:{}", ip, port))
-        .unwrap_or_else(|| format!("http://localhost:{}", port));
-    Some(format!("{}?access_token={}", base_url, state.auth_token))
+        .unwrap_or_else(|| format!("http://localhost:{}", port))
+}
+
+/// The web UI's URL on the local network, signed in with the token.
+pub fn web_access_url(token: &str) -> String {
+    format!("{}?access_token={}", web_base_url(), token)
+}
+
+/// The web UI's URL on the local network, paired with the code.
+pub fn web_pairing_url(code: &str) -> String {
+    format!("{}?pairing_code={}", web_base_url(), code)
 }
 
 fn invoke_dispatch(
-    app: &tauri::AppHandle,
+    host: &Host,
+    grant: &AccessGrant,
     command_name: &str,
     args: Option<Value>,
 ) -> Result<Value, String> {
-    let db: TauriState<'_, Database> = app.state();
-    let agent_runtime: TauriState<'_, AgentRuntimeState> = app.state();
-    let host_access_state: TauriState<'_, HostAccessState> = app.state();
+    if !grant.allows(command_scope(command_name, args.as_ref())) {
+        return Err(format!("This access token cannot run {}", command_name));
+    }
 
     match command_name {
-        "list_repos" => Ok(serde_json::to_value(list_repos(db)?).map_err(|e| e.to_string())?),
+        "list_repos" => {
+            Ok(serde_json::to_value(list_repos(host.clone())?).map_err(|e| e.to_string())?)
+        }
         "add_repo" => {
             let parsed: AddRepoArgs = deserialize_args(args)?;
             Ok(
-                serde_json::to_value(add_repo(db, parsed.path, parsed.group_id)?)
+                serde_json::to_value(add_repo(host.clone(), parsed.path, parsed.group_id)?)
                     .map_err(|e| e.to_string())?,
             )
         }
         "clone_repo" => {
             let parsed: CloneRepoArgs = deserialize_args(args)?;
             Ok(serde_json::to_value(clone_repo(
-                db,
+                host.clone(),
                 parsed.url,
                 parsed.destination_parent,
                 parsed.group_id,
@@ -499,7 +1081,7 @@ fn invoke_dispatch(
         }
         "remove_repo" => {
             let parsed: RemoveRepoArgs = deserialize_args(args)?;
-            remove_repo(db, parsed.id)?;
+            remove_repo(host.clone(), parsed.id)?;
             Ok(Value::Null)
         }
         "open_in_cursor" => {
@@ -611,82 +1193,397 @@ fn invoke_dispatch(
         "list_agents" => {
             let parsed: RepoIdArgs = deserialize_args(args)?;
             Ok(
-                serde_json::to_value(list_agents(db, parsed.repo_id)?)
+                serde_json::to_value(list_agents(host.clone(), parsed.repo_id)?)
                     .map_err(|e| e.to_string())?,
             )
         }
         "create_agent" => {
             let parsed: CreateAgentArgs = deserialize_args(args)?;
-            Ok(
-                serde_json::to_value(create_agent(db, parsed.repo_id, parsed.name)?)
-                    .map_err(|e| e.to_string())?,
-            )
+            Ok(serde_json::to_value(create_agent(
+                host.clone(),
+                parsed.repo_id,
+                parsed.name,
+                parsed.isolated_workspace,
+                parsed.backend,
+                parsed.backend_command,
+            )?)
+            .map_err(|e| e.to_string())?)
         }
         "delete_agent" => {
             let parsed: AgentIdArgs = deserialize_args(args)?;
-            delete_agent(db, parsed.agent_id)?;
+            delete_agent(host.clone(), parsed.agent_id)?;
             Ok(Value::Null)
         }
         "rename_agent" => {
             let parsed: RenameAgentArgs = deserialize_args(args)?;
-            rename_agent(db, parsed.agent_id, parsed.name)?;
+            rename_agent(host.clone(), parsed.agent_id, parsed.name)?;
             Ok(Value::Null)
         }
+        "set_agent_backend" => {
+            let parsed: SetAgentBackendArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(set_agent_backend(
+                host.clone(),
+                parsed.agent_id,
+                parsed.backend,
+                parsed.backend_command,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "set_agent_run_limits" => {
+            let parsed: SetAgentRunLimitsArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(set_agent_run_limits(
+                host.clone(),
+                parsed.agent_id,
+                parsed.limits,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "reset_agent_session" => {
+            let parsed: AgentIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(reset_agent_session(host.clone(), parsed.agent_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
         "list_agent_models" => {
-            Ok(serde_json::to_value(list_agent_models()?).map_err(|e| e.to_string())?)
+            let parsed: ListAgentModelsArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(list_agent_models(host.clone(), parsed.agent_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
         }
         "run_repo_agent" => {
             let parsed: RunRepoAgentArgs = deserialize_args(args)?;
             run_repo_agent(
-                app.clone(),
-                agent_runtime,
+                host.clone(),
                 parsed.repo_path,
                 parsed.prompt,
                 parsed.agent_id,
                 parsed.run_id,
-                parsed.force_approve,
-                parsed.simulate_mode,
-                parsed.model,
+                parsed.options,
             )?;
             Ok(Value::Null)
         }
         "stop_repo_agent" => {
+            let parsed: StopRepoAgentArgs = deserialize_args(args)?;
+            stop_repo_agent(host.clone(), parsed.agent_id, parsed.grace_period_ms)?;
+            Ok(Value::Null)
+        }
+        "send_agent_input" => {
+            let parsed: SendAgentInputArgs = deserialize_args(args)?;
+            send_agent_input(host.clone(), parsed.agent_id, parsed.text)?;
+            Ok(Value::Null)
+        }
+        "approve_tool_call" => {
+            let parsed: ApproveToolCallArgs = deserialize_args(args)?;
+            approve_tool_call(host.clone(), parsed.agent_id, parsed.call_id)?;
+            Ok(Value::Null)
+        }
+        "deny_tool_call" => {
+            let parsed: DenyToolCallArgs = deserialize_args(args)?;
+            deny_tool_call(host.clone(), parsed.agent_id, parsed.call_id, parsed.reason)?;
+            Ok(Value::Null)
+        }
+        "list_agent_queue" => {
+            let parsed: AgentIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(list_agent_queue(host.clone(), parsed.agent_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "reorder_agent_queue" => {
+            let parsed: ReorderAgentQueueArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(reorder_agent_queue(
+                host.clone(),
+                parsed.agent_id,
+                parsed.run_ids,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "cancel_queued_prompt" => {
+            let parsed: CancelQueuedPromptArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(cancel_queued_prompt(
+                host.clone(),
+                parsed.agent_id,
+                parsed.run_id,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "list_scheduled_runs" => {
+            Ok(serde_json::to_value(list_scheduled_runs(host.clone())?)
+                .map_err(|e| e.to_string())?)
+        }
+        "get_scheduler_settings" => Ok(serde_json::to_value(get_scheduler_settings(host.clone())?)
+            .map_err(|e| e.to_string())?),
+        "set_scheduler_settings" => {
+            let parsed: SetSchedulerSettingsArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(set_scheduler_settings(
+                host.clone(),
+                parsed.max_concurrent_runs,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "set_repo_run_limit" => {
+            let parsed: SetRepoRunLimitArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(set_repo_run_limit(
+                host.clone(),
+                parsed.repo_id,
+                parsed.max_concurrent_runs,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "list_agent_schedules" => {
+            let parsed: AgentIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(list_agent_schedules(host.clone(), parsed.agent_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "create_agent_schedule" => {
+            let parsed: CreateAgentScheduleArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(create_agent_schedule(
+                host.clone(),
+                parsed.agent_id,
+                parsed.cron,
+                parsed.prompt,
+                parsed.model,
+                parsed.missed_run_policy,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "set_agent_schedule_enabled" => {
+            let parsed: SetAgentScheduleEnabledArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(set_agent_schedule_enabled(
+                host.clone(),
+                parsed.schedule_id,
+                parsed.enabled,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "delete_agent_schedule" => {
+            let parsed: ScheduleIdArgs = deserialize_args(args)?;
+            delete_agent_schedule(host.clone(), parsed.schedule_id)?;
+            Ok(Value::Null)
+        }
+        "list_agent_runs" => {
+            let parsed: ListAgentRunsArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(list_agent_runs(
+                host.clone(),
+                parsed.agent_id,
+                parsed.limit,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "get_agent_run_transcript" => {
+            let parsed: RunIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(get_agent_run_transcript(host.clone(), parsed.run_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "get_agent_run_changes" => {
+            let parsed: RunIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(get_agent_run_changes(host.clone(), parsed.run_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "list_agent_policy_violations" => {
+            let parsed: ListAgentPolicyViolationsArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(list_agent_policy_violations(
+                host.clone(),
+                parsed.agent_id,
+                parsed.limit,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "get_agent_stats" => {
             let parsed: AgentIdArgs = deserialize_args(args)?;
-            stop_repo_agent(app.clone(), agent_runtime, parsed.agent_id)?;
+            Ok(
+                serde_json::to_value(get_agent_stats(host.clone(), parsed.agent_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "get_repo_stats" => {
+            let parsed: RepoIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(get_repo_stats(host.clone(), parsed.repo_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "get_group_stats" => {
+            let parsed: GroupStatsArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(get_group_stats(host.clone(), parsed.group_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "run_group_agents" => {
+            let parsed: RunGroupAgentsArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(run_group_agents(
+                host.clone(),
+                parsed.group_id,
+                parsed.prompt,
+                parsed.model,
+                parsed.fresh_branch,
+                parsed.force_approve,
+                parsed.simulate_mode,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "get_agent_batch" => {
+            let parsed: BatchIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(get_agent_batch(host.clone(), parsed.batch_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "list_agent_batches" => {
+            let parsed: GroupStatsArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(list_agent_batches(host.clone(), parsed.group_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "delete_agent_batch" => {
+            let parsed: BatchIdArgs = deserialize_args(args)?;
+            delete_agent_batch(host.clone(), parsed.batch_id)?;
             Ok(Value::Null)
         }
-        "list_groups" => Ok(serde_json::to_value(list_groups(db)?).map_err(|e| e.to_string())?),
+        "revert_agent_run" => {
+            let parsed: RunIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(revert_agent_run(host.clone(), parsed.run_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "set_agent_checkpoints" => {
+            let parsed: SetAgentCheckpointsArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(set_agent_checkpoints(
+                host.clone(),
+                parsed.agent_id,
+                parsed.enabled,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "list_agent_checkpoints" => {
+            let parsed: AgentIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(list_agent_checkpoints(host.clone(), parsed.agent_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "restore_agent_checkpoint" => {
+            let parsed: AgentCheckpointArgs = deserialize_args(args)?;
+            restore_agent_checkpoint(host.clone(), parsed.agent_id, parsed.run_id)?;
+            Ok(Value::Null)
+        }
+        "delete_agent_checkpoint" => {
+            let parsed: AgentCheckpointArgs = deserialize_args(args)?;
+            delete_agent_checkpoint(host.clone(), parsed.agent_id, parsed.run_id)?;
+            Ok(Value::Null)
+        }
+        "create_agent_worktree" => {
+            let parsed: AgentIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(create_agent_worktree(host.clone(), parsed.agent_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "merge_agent_worktree" => {
+            let parsed: AgentIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(merge_agent_worktree(host.clone(), parsed.agent_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "rebase_agent_worktree" => {
+            let parsed: AgentIdArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(rebase_agent_worktree(host.clone(), parsed.agent_id)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "discard_agent_worktree" => {
+            let parsed: AgentIdArgs = deserialize_args(args)?;
+            discard_agent_worktree(host.clone(), parsed.agent_id)?;
+            Ok(Value::Null)
+        }
+        "cleanup_stale_worktrees" => {
+            Ok(serde_json::to_value(cleanup_stale_worktrees(host.clone())?)
+                .map_err(|e| e.to_string())?)
+        }
+        "list_groups" => {
+            Ok(serde_json::to_value(list_groups(host.clone())?).map_err(|e| e.to_string())?)
+        }
         "create_group" => {
             let parsed: CreateGroupArgs = deserialize_args(args)?;
-            Ok(serde_json::to_value(create_group(db, parsed.name)?).map_err(|e| e.to_string())?)
+            Ok(
+                serde_json::to_value(create_group(host.clone(), parsed.name)?)
+                    .map_err(|e| e.to_string())?,
+            )
         }
         "rename_group" => {
             let parsed: RenameGroupArgs = deserialize_args(args)?;
-            rename_group(db, parsed.id, parsed.name)?;
+            rename_group(host.clone(), parsed.id, parsed.name)?;
             Ok(Value::Null)
         }
         "delete_group" => {
             let parsed: GroupIdArgs = deserialize_args(args)?;
-            delete_group(db, parsed.id)?;
+            delete_group(host.clone(), parsed.id)?;
             Ok(Value::Null)
         }
         "move_repo_to_group" => {
             let parsed: MoveRepoToGroupArgs = deserialize_args(args)?;
-            move_repo_to_group(db, parsed.repo_id, parsed.group_id)?;
+            move_repo_to_group(host.clone(), parsed.repo_id, parsed.group_id)?;
             Ok(Value::Null)
         }
         "get_host_access_settings" => Ok(serde_json::to_value(current_host_access_settings(
-            &host_access_state,
+            host.access(),
         ))
         .map_err(|error| error.to_string())?),
         "set_host_access_settings" => {
             let parsed: SetHostAccessSettingsArgs = deserialize_args(args)?;
             Ok(serde_json::to_value(set_host_access_settings(
-                host_access_state,
+                host.clone(),
                 parsed.allow_lan_access,
             ))
             .map_err(|error| error.to_string())?)
         }
+        "list_access_tokens" => {
+            Ok(serde_json::to_value(list_access_tokens(host.clone())?)
+                .map_err(|e| e.to_string())?)
+        }
+        "create_access_token" => {
+            let parsed: CreateAccessTokenArgs = deserialize_args(args)?;
+            Ok(serde_json::to_value(create_access_token(
+                host.clone(),
+                parsed.name,
+                parsed.scopes,
+                parsed.expires_in_days,
+            )?)
+            .map_err(|e| e.to_string())?)
+        }
+        "revoke_access_token" => {
+            let parsed: TokenIdArgs = deserialize_args(args)?;
+            revoke_access_token(host.clone(), parsed.token_id)?;
+            Ok(Value::Null)
+        }
+        "list_paired_devices" => {
+            Ok(serde_json::to_value(list_paired_devices(host.clone())?)
+                .map_err(|e| e.to_string())?)
+        }
+        "start_device_pairing" => {
+            let parsed: StartDevicePairingArgs = deserialize_args(args)?;
+            Ok(
+                serde_json::to_value(start_device_pairing(host.clone(), parsed.scopes)?)
+                    .map_err(|e| e.to_string())?,
+            )
+        }
+        "cancel_device_pairing" => {
+            cancel_device_pairing(host.clone())?;
+            Ok(Value::Null)
+        }
         _ => Err(format!("Unknown command: {}", command_name)),
     }
 }
@@ -710,7 +1607,8 @@ async fn invoke_handler(
             }),
         );
     }
-    if !is_authorized(&headers, None, &state.auth_token) {
+    let Some(grant) = authorize_token(&state, extract_bearer_token(&headers).as_deref()).await
+    else {
         return (
             StatusCode::UNAUTHORIZED,
             Json(InvokeResponse {
@@ -719,14 +1617,14 @@ async fn invoke_handler(
                 error: Some("Unauthorized".to_string()),
             }),
         );
-    }
+    };
 
-    let app = state.app.clone();
+    let host = state.host.clone();
     let command_name = request.command;
     let args = request.args;
 
     let dispatch_result =
-        tauri::async_runtime::spawn_blocking(move || invoke_dispatch(&app, &command_name, args))
+        async_runtime::spawn_blocking(move || invoke_dispatch(&host, &grant, &command_name, args))
             .await;
     match dispatch_result {
         Ok(Ok(data)) => (
@@ -767,7 +1665,10 @@ async fn health_handler(
     ) {
         return (StatusCode::FORBIDDEN, Json(json!({ "ok": false })));
     }
-    if !is_authorized(&headers, None, &state.auth_token) {
+    if authorize_token(&state, extract_bearer_token(&headers).as_deref())
+        .await
+        .is_none()
+    {
         return (StatusCode::UNAUTHORIZED, Json(json!({ "ok": false })));
     }
     (StatusCode::OK, Json(json!({ "ok": true })))
@@ -785,26 +1686,335 @@ async fn events_handler(
     ) {
         return (StatusCode::FORBIDDEN, "LAN access is disabled").into_response();
     }
-    if !is_authorized(&headers, query.token.as_deref(), &state.auth_token) {
+    let token = stream_request_token(&state, &headers, &query);
+    if authorize_token(&state, token.as_deref()).await.is_none() {
         return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
     }
+    let token = token.unwrap_or_default();
+
+    let filter = match BridgeEventFilter::from_query(&query) {
+        Ok(filter) => filter,
+        Err(error) => return (StatusCode::BAD_REQUEST, error).into_response(),
+    };
+    // Resolved before the response is sent, so a client that starts something once
+    // the stream is open also gets the events it causes.
+    let last_event_id = requested_last_event_id(&headers, &query)
+        .unwrap_or_else(|| state.host.bridge().latest_event_id());
+    let (sender, receiver) = mpsc::channel(64);
+    let (_, filter) = watch::channel(filter);
+    async_runtime::spawn(forward_bridge_events(
+        state,
+        token,
+        last_event_id,
+        filter,
+        sender,
+        bridge_sse_event,
+    ));
+
+    Sse::new(ReceiverStream::new(receiver).map(Ok::<Event, Infallible>))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn requested_last_event_id(
+    headers: &HeaderMap,
+    query: &EventQueryParameters,
+) -> Option<BridgeEventId> {
+    headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(BridgeEventId::parse)
+        .or_else(|| {
+            query
+                .last_event_id
+                .as_deref()
+                .and_then(BridgeEventId::parse)
+        })
+}
+
+/// What the host sends a subscribed client, in order.
+enum BridgeStreamItem {
+    Event(BridgeEvent),
+    Gap(BridgeEventGapPayload),
+}
+
+/// The gap event has no id, so a client that reconnects after it still resumes
+/// from the last event it received.
+fn bridge_sse_event(item: BridgeStreamItem) -> Event {
+    let (id, envelope) = match item {
+        BridgeStreamItem::Event(event) => (
+            Some(event.id),
+            BridgeEventEnvelope {
+                event: event.event,
+                payload: event.payload,
+            },
+        ),
+        BridgeStreamItem::Gap(gap) => (
+            None,
+            BridgeEventEnvelope {
+                event: EVENT_GAP_EVENT.to_string(),
+                payload: serde_json::to_value(gap).unwrap_or(Value::Null),
+            },
+        ),
+    };
+    let sse_event = Event::default().event(&envelope.event);
+    let data = serde_json::to_string(&envelope).unwrap_or_else(|_| "null".to_string());
+    match id {
+        Some(id) => sse_event.id(id.to_string()).data(data),
+        None => sse_event.data(data),
+    }
+}
+
+/// Sends the client's missed events, then live ones, until the client disconnects
+/// or its token stops working. A client that falls behind the broadcast channel
+/// catches up from the history. Only events that match the current filter are sent.
+async fn forward_bridge_events<T>(
+    state: HttpBridgeAppState,
+    token: String,
+    last_event_id: BridgeEventId,
+    filter: watch::Receiver<BridgeEventFilter>,
+    sender: mpsc::Sender<T>,
+    to_message: fn(BridgeStreamItem) -> T,
+) {
+    let host = state.host.clone();
+    let bridge = host.bridge();
+    let mut matcher = BridgeEventMatcher {
+        host: host.clone(),
+        filter,
+        agent_repo_ids: HashMap::new(),
+    };
+    // Tokens can expire or be revoked while the stream is open.
+    let mut revalidation = tokio::time::interval_at(
+        tokio::time::Instant::now() + TOKEN_REVALIDATION_INTERVAL,
+        TOKEN_REVALIDATION_INTERVAL,
+    );
+
+    // Events sent between `last_event_id` and subscribing come from the history.
+    let mut receiver = bridge.subscribe();
+    let mut replay = bridge.replay_after(last_event_id);
+    let mut last_sent_seq = replay.after_seq;
+    loop {
+        if let Some(gap) = replay.gap.take() {
+            if sender
+                .send(to_message(BridgeStreamItem::Gap(gap)))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+        for event in std::mem::take(&mut replay.events) {
+            last_sent_seq = event.id.seq;
+            if !matcher.is_wanted(&event).await {
+                continue;
+            }
+            if sender
+                .send(to_message(BridgeStreamItem::Event(event)))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        loop {
+            let received = tokio::select! {
+                received = receiver.recv() => received,
+                _ = revalidation.tick() => {
+                    if authorize_token(&state, Some(&token)).await.is_none() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+            match received {
+                Ok(event) => {
+                    // Events already sent from the history.
+                    if event.id.seq <= last_sent_seq {
+                        continue;
+                    }
+                    last_sent_seq = event.id.seq;
+                    if event.event == ACCESS_TOKEN_REVOKED_EVENT
+                        && authorize_token(&state, Some(&token)).await.is_none()
+                    {
+                        return;
+                    }
+                    if !matcher.is_wanted(&event).await {
+                        continue;
+                    }
+                    if sender
+                        .send(to_message(BridgeStreamItem::Event(event)))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    replay = bridge.replay_after(bridge.event_id(last_sent_seq));
+                    break;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
+}
 
-    let stream = BroadcastStream::new(state.events.subscribe()).filter_map(|message| {
-        let event = match message {
-            Ok(payload) => payload,
-            Err(_) => return None,
+/// Serves commands and events over one WebSocket. Takes the same query parameters
+/// as `/api/events`, since browsers cannot set headers on a WebSocket.
+async fn ws_handler(
+    AxumState(state): AxumState<HttpBridgeAppState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<EventQueryParameters>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    if !client_access_allowed(
+        remote_address,
+        state.allow_lan_access.load(Ordering::Relaxed),
+    ) {
+        return (StatusCode::FORBIDDEN, "LAN access is disabled").into_response();
+    }
+    let token = stream_request_token(&state, &headers, &query);
+    if authorize_token(&state, token.as_deref()).await.is_none() {
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    }
+    let token = token.unwrap_or_default();
+
+    let filter = match BridgeEventFilter::from_query(&query) {
+        Ok(filter) => filter,
+        Err(error) => return (StatusCode::BAD_REQUEST, error).into_response(),
+    };
+    let last_event_id = requested_last_event_id(&headers, &query)
+        .unwrap_or_else(|| state.host.bridge().latest_event_id());
+    upgrade
+        .on_upgrade(move |socket| serve_bridge_socket(state, token, last_event_id, filter, socket))
+}
+
+async fn serve_bridge_socket(
+    state: HttpBridgeAppState,
+    token: String,
+    last_event_id: BridgeEventId,
+    filter: BridgeEventFilter,
+    socket: WebSocket,
+) {
+    let host = state.host.clone();
+    let (mut socket_sender, mut socket_receiver) = futures_util::StreamExt::split(socket);
+    let (sender, mut receiver) = mpsc::channel::<Message>(64);
+    let (filter_sender, filter_receiver) = watch::channel(filter);
+    let mut forwarder = async_runtime::spawn(forward_bridge_events(
+        state.clone(),
+        token.clone(),
+        last_event_id,
+        filter_receiver,
+        sender.clone(),
+        bridge_ws_message,
+    ));
+    let mut writer = async_runtime::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            let is_close = matches!(message, Message::Close(_));
+            if socket_sender.send(message).await.is_err() || is_close {
+                return;
+            }
+        }
+    });
+
+    loop {
+        let message = tokio::select! {
+            message = socket_receiver.next() => message,
+            // The forwarder only stops early once the token no longer works, or
+            // once the writer is gone.
+            _ = &mut forwarder => {
+                if sender.send(Message::Close(None)).await.is_ok() {
+                    let _ = (&mut writer).await;
+                }
+                break;
+            }
         };
-        let envelope = BridgeEventEnvelope {
-            event: event.event,
-            payload: event.payload,
+        let Some(Ok(message)) = message else {
+            break;
+        };
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let request = match serde_json::from_str::<WsRequest>(&text) {
+            Ok(request) => request,
+            Err(error) => {
+                let response = WsResponse::error(Value::Null, WS_PARSE_ERROR, error.to_string());
+                if sender.send(response.into_message()).await.is_err() {
+                    break;
+                }
+                continue;
+            }
         };
-        let data = serde_json::to_string(&envelope).unwrap_or_else(|_| "null".to_string());
-        Some(Ok::<Event, Infallible>(Event::default().data(data)))
-    });
 
-    Sse::new(stream)
-        .keep_alive(KeepAlive::default())
-        .into_response()
+        // Replaces the connection's event filter; an empty filter receives everything.
+        if request.method == WS_SUBSCRIBE_METHOD {
+            let params = request.params.unwrap_or_else(|| json!({}));
+            let response = match serde_json::from_value::<BridgeEventFilter>(params) {
+                Ok(filter) => {
+                    filter_sender.send_replace(filter);
+                    WsResponse::result(request.id, Value::Bool(true))
+                }
+                Err(error) => WsResponse::error(request.id, WS_INVALID_PARAMS, error.to_string()),
+            };
+            if sender.send(response.into_message()).await.is_err() {
+                break;
+            }
+            continue;
+        }
+
+        // Checked for every command, so a revoked or expired token stops working
+        // on open connections too.
+        let Some(grant) = authorize_token(&state, Some(&token)).await else {
+            let response =
+                WsResponse::error(request.id, WS_UNAUTHORIZED, "Unauthorized".to_string());
+            let _ = sender.send(response.into_message()).await;
+            break;
+        };
+
+        // Commands run concurrently; clients match responses to requests by id.
+        let host = host.clone();
+        let sender = sender.clone();
+        async_runtime::spawn(async move {
+            let WsRequest { id, method, params } = request;
+            let dispatch_result = async_runtime::spawn_blocking(move || {
+                invoke_dispatch(&host, &grant, &method, params)
+            })
+            .await;
+            let response = match dispatch_result {
+                Ok(Ok(result)) => WsResponse::result(id, result),
+                Ok(Err(error)) => WsResponse::error(id, WS_COMMAND_ERROR, error),
+                Err(error) => WsResponse::error(
+                    id,
+                    WS_INTERNAL_ERROR,
+                    format!("Bridge task failed: {}", error),
+                ),
+            };
+            let _ = sender.send(response.into_message()).await;
+        });
+    }
+
+    writer.abort();
+    forwarder.abort();
+}
+
+fn bridge_ws_message(item: BridgeStreamItem) -> Message {
+    let params = match item {
+        BridgeStreamItem::Event(event) => json!({
+            "id": event.id.to_string(),
+            "event": event.event,
+            "payload": event.payload,
+        }),
+        BridgeStreamItem::Gap(gap) => json!({
+            "event": EVENT_GAP_EVENT,
+            "payload": gap,
+        }),
+    };
+    let notification = json!({ "jsonrpc": "2.0", "method": "event", "params": params });
+    Message::Text(notification.to_string().into())
 }
 
 async fn verify_auth_handler(
@@ -818,17 +2028,100 @@ async fn verify_auth_handler(
     ) {
         return (StatusCode::FORBIDDEN, Json(json!({ "ok": false })));
     }
-    if !is_authorized(&headers, None, &state.auth_token) {
+    let Some(grant) = authorize_token(&state, extract_bearer_token(&headers).as_deref()).await
+    else {
         return (StatusCode::UNAUTHORIZED, Json(json!({ "ok": false })));
+    };
+    (
+        StatusCode::OK,
+        Json(json!({ "ok": true, "scopes": grant.scopes() })),
+    )
+}
+
+/// Issues a one-time ticket for opening an event stream, for clients that cannot
+/// send their token in a header there.
+async fn stream_ticket_handler(
+    AxumState(state): AxumState<HttpBridgeAppState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !client_access_allowed(
+        remote_address,
+        state.allow_lan_access.load(Ordering::Relaxed),
+    ) {
+        return (StatusCode::FORBIDDEN, Json(json!({ "ok": false })));
     }
-    (StatusCode::OK, Json(json!({ "ok": true })))
+    let Some(token) = extract_bearer_token(&headers) else {
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "ok": false })));
+    };
+    if authorize_token(&state, Some(&token)).await.is_none() {
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "ok": false })));
+    }
+    let ticket: String = rand::rng()
+        .sample_iter(Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+    let Ok(mut tickets) = state.stream_tickets.lock() else {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "ok": false })),
+        );
+    };
+    let now = Instant::now();
+    tickets.retain(|_, ticket| ticket.expires_at > now);
+    tickets.insert(
+        ticket.clone(),
+        StreamTicket {
+            token,
+            expires_at: now + STREAM_TICKET_LIFETIME,
+        },
+    );
+    (
+        StatusCode::OK,
+        Json(json!({ "ok": true, "ticket": ticket })),
+    )
 }
 
-pub fn start_host_bridge(
-    app: tauri::AppHandle,
-    events: HostBridgeState,
-    host_access_state: HostAccessState,
-) {
+/// Exchanges a pairing code for a device token. Needs no token, since the code is
+/// the credential.
+async fn pair_device_handler(
+    AxumState(state): AxumState<HttpBridgeAppState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Json(request): Json<PairDeviceRequest>,
+) -> impl IntoResponse {
+    if !client_access_allowed(
+        remote_address,
+        state.allow_lan_access.load(Ordering::Relaxed),
+    ) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "ok": false, "error": "LAN access is disabled" })),
+        );
+    }
+    let host = state.host.clone();
+    let paired = async_runtime::spawn_blocking(move || {
+        pair_device(
+            &host,
+            &request.code,
+            &request.device_name,
+            remote_address.ip(),
+        )
+    })
+    .await
+    .unwrap_or_else(|error| Err(error.to_string()));
+    match paired {
+        Ok(token) => (StatusCode::OK, Json(json!({ "ok": true, "token": token }))),
+        Err(error) => (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "ok": false, "error": error })),
+        ),
+    }
+}
+
+/// Serves the host bridge in the background. Returns the server task, or `None` if
+/// the configured address is invalid.
+pub fn start_host_bridge(host: Host) -> Option<async_runtime::JoinHandle<()>> {
     let bind_host = std::env::var("SYMPHONY_HOST_BIND").unwrap_or_else(|_| "0.0.0.0".to_string());
     let bind_port = std::env::var("SYMPHONY_HOST_PORT")
         .ok()
@@ -838,11 +2131,12 @@ pub fn start_host_bridge(
         Ok(value) => value,
         Err(error) => {
             eprintln!("Failed to parse SYMPHONY host bridge address: {}", error);
-            return;
+            return None;
         }
     };
+    let host_access_state = host.access().clone();
     let auth_token = host_access_state.auth_token.clone();
-    print_access_qr_code(&auth_token);
+    print_web_access_hint();
     println!(
         "Symphony LAN access is {}",
         if host_access_state.allow_lan_access.load(Ordering::Relaxed) {
@@ -852,18 +2146,21 @@ pub fn start_host_bridge(
         }
     );
 
-    tauri::async_runtime::spawn(async move {
+    Some(async_runtime::spawn(async move {
         let state = HttpBridgeAppState {
-            app,
-            events,
+            host,
             auth_token,
             allow_lan_access: host_access_state.allow_lan_access,
+            stream_tickets: Arc::new(Mutex::new(HashMap::new())),
         };
         let app_router = Router::new()
             .route("/health", get(health_handler))
             .route("/api/auth/verify", get(verify_auth_handler))
+            .route("/api/auth/pair", post(pair_device_handler))
             .route("/api/invoke", post(invoke_handler))
             .route("/api/events", get(events_handler))
+            .route("/api/events/ticket", post(stream_ticket_handler))
+            .route("/api/ws", get(ws_handler))
             .layer(
                 CorsLayer::new()
                     .allow_origin(Any)
@@ -896,5 +2193,182 @@ pub fn start_host_bridge(
         {
             eprintln!("Symphony host bridge stopped with error: {}", error);
         }
-    });
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bridge_with_events(count: usize) -> HostBridgeState {
+        let bridge = HostBridgeState::new();
+        for index in 0..count {
+            bridge.send_event("test-event", json!({ "index": index }));
+        }
+        bridge
+    }
+
+    fn replayed_seqs(replay: &BridgeEventReplay) -> Vec<u64> {
+        replay.events.iter().map(|event| event.id.seq).collect()
+    }
+
+    #[test]
+    fn parses_the_ids_it_sends() {
+        let bridge = bridge_with_events(3);
+        let id = bridge.latest_event_id();
+        assert_eq!(BridgeEventId::parse(&id.to_string()), Some(id));
+        assert_eq!(BridgeEventId::parse("12"), None);
+        assert_eq!(BridgeEventId::parse("zz-3"), None);
+    }
+
+    #[test]
+    fn replays_the_events_after_a_known_id() {
+        let bridge = bridge_with_events(5);
+        let replay = bridge.replay_after(bridge.event_id(2));
+        assert!(replay.gap.is_none());
+        assert_eq!(replay.after_seq, 2);
+        assert_eq!(replayed_seqs(&replay), vec![3, 4, 5]);
+
+        let replay = bridge.replay_after(bridge.latest_event_id());
+        assert!(replay.gap.is_none());
+        assert_eq!(replay.after_seq, 5);
+        assert!(replay.events.is_empty());
+    }
+
+    #[test]
+    fn reports_a_gap_for_evicted_ids() {
+        let bridge = bridge_with_events(EVENT_HISTORY_CAPACITY + 10);
+        let replay = bridge.replay_after(bridge.event_id(3));
+        let gap = replay.gap.as_ref().expect("evicted events are a gap");
+        assert_eq!(gap.last_event_id, bridge.event_id(3).to_string());
+        assert_eq!(gap.next_event_id, bridge.event_id(11).to_string());
+        assert_eq!(replay.after_seq, 3);
+        assert_eq!(replay.events.len(), EVENT_HISTORY_CAPACITY);
+        assert_eq!(replay.events[0].id.seq, 11);
+
+        // The oldest event still in the history follows directly.
+        let replay = bridge.replay_after(bridge.event_id(10));
+        assert!(replay.gap.is_none());
+        assert_eq!(replay.events[0].id.seq, 11);
+    }
+
+    #[test]
+    fn replays_everything_for_ids_of_another_host() {
+        let bridge = bridge_with_events(5);
+        let foreign_id = BridgeEventId {
+            epoch: bridge.epoch.wrapping_add(1),
+            seq: 3,
+        };
+        let replay = bridge.replay_after(foreign_id);
+        let gap = replay.gap.as_ref().expect("a foreign epoch is a gap");
+        assert_eq!(gap.last_event_id, foreign_id.to_string());
+        assert_eq!(gap.next_event_id, bridge.event_id(1).to_string());
+        assert_eq!(replay.after_seq, 0);
+        assert_eq!(replayed_seqs(&replay), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn replays_everything_for_ids_not_sent_yet() {
+        let bridge = bridge_with_events(5);
+        let replay = bridge.replay_after(bridge.event_id(9));
+        assert!(replay.gap.is_some());
+        assert_eq!(replay.after_seq, 0);
+        assert_eq!(replayed_seqs(&replay), vec![1, 2, 3, 4, 5]);
+    }
+
+    fn event(name: &str, payload: Value) -> BridgeEvent {
+        BridgeEvent {
+            id: BridgeEventId { epoch: 1, seq: 1 },
+            event: name.to_string(),
+            payload,
+        }
+    }
+
+    fn filter(value: Value) -> BridgeEventFilter {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn empty_filter_matches_every_event() {
+        let filter = BridgeEventFilter::default();
+        assert!(filter.matches(&event("repo-agent-done", json!({ "agentId": 1 })), None));
+        assert!(filter.matches(&event("repos-changed", json!(null)), None));
+    }
+
+    #[test]
+    fn filters_by_event_name() {
+        let filter = filter(json!({ "events": ["repo-agent-done"] }));
+        assert!(filter.matches(&event("repo-agent-done", json!({})), None));
+        assert!(!filter.matches(&event("repo-agent-stdout", json!({})), None));
+    }
+
+    #[test]
+    fn filters_by_agent() {
+        let filter = filter(json!({ "agentId": [1, 2] }));
+        assert!(filter.matches(&event("repo-agent-done", json!({ "agentId": 2 })), None));
+        assert!(!filter.matches(&event("repo-agent-done", json!({ "agentId": 3 })), None));
+        assert!(!filter.matches(&event("repos-changed", json!({})), None));
+    }
+
+    #[test]
+    fn filters_by_run() {
+        let filter = filter(json!({ "runId": ["run-1"] }));
+        assert!(filter.matches(&event("repo-agent-done", json!({ "runId": "run-1" })), None));
+        assert!(!filter.matches(&event("repo-agent-done", json!({ "runId": "run-2" })), None));
+        assert!(!filter.matches(&event("repo-agent-done", json!({ "agentId": 1 })), None));
+    }
+
+    #[test]
+    fn filters_by_repo_directly_or_through_the_agent() {
+        let filter = filter(json!({ "repoId": [7] }));
+        let repo_event = event("repo-pulled", json!({ "repoId": 7 }));
+        assert_eq!(filter.agent_for_repo_match(&repo_event), None);
+        assert!(filter.matches(&repo_event, None));
+        assert!(!filter.matches(&event("repo-pulled", json!({ "repoId": 8 })), None));
+
+        let agent_event = event("repo-agent-done", json!({ "agentId": 3 }));
+        assert_eq!(filter.agent_for_repo_match(&agent_event), Some(3));
+        assert!(filter.matches(&agent_event, Some(7)));
+        assert!(!filter.matches(&agent_event, Some(8)));
+        assert!(!filter.matches(&agent_event, None));
+
+        // Without a repo filter the agent's repo is never looked up.
+        assert_eq!(
+            BridgeEventFilter::default().agent_for_repo_match(&agent_event),
+            None
+        );
+    }
+
+    #[test]
+    fn requires_every_set_field_to_match() {
+        let filter = filter(json!({ "events": ["repo-agent-done"], "agentId": [1] }));
+        assert!(filter.matches(&event("repo-agent-done", json!({ "agentId": 1 })), None));
+        assert!(!filter.matches(&event("repo-agent-stdout", json!({ "agentId": 1 })), None));
+        assert!(!filter.matches(&event("repo-agent-done", json!({ "agentId": 2 })), None));
+    }
+
+    #[test]
+    fn rejects_unknown_filter_fields() {
+        assert!(serde_json::from_value::<BridgeEventFilter>(json!({ "agentIds": [1] })).is_err());
+    }
+
+    #[test]
+    fn parses_filters_from_the_query_string() {
+        let query: EventQueryParameters = serde_json::from_value(json!({
+            "events": "repo-agent-done, repo-agent-stdout",
+            "agentId": "1,2",
+            "runId": "run-1",
+            "repoId": "7",
+        }))
+        .unwrap();
+        let filter = BridgeEventFilter::from_query(&query).unwrap();
+        assert_eq!(filter.events.map(|events| events.len()), Some(2));
+        assert_eq!(filter.agent_ids, Some(HashSet::from([1, 2])));
+        assert_eq!(filter.run_ids, Some(HashSet::from(["run-1".to_string()])));
+        assert_eq!(filter.repo_ids, Some(HashSet::from([7])));
+
+        let query: EventQueryParameters =
+            serde_json::from_value(json!({ "agentId": "one" })).unwrap();
+        assert!(BridgeEventFilter::from_query(&query).is_err());
+    }
 }
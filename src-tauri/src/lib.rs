@@ -1,44 +1,105 @@
+mod access_tokens;
+mod agent_approvals;
+mod agent_backend;
+mod agent_batches;
+mod agent_input;
+mod agent_limits;
+mod agent_policy;
+mod agent_queue;
+mod agent_run_changes;
+mod agent_scheduler;
+mod agent_schedules;
+mod agent_stream;
+mod agent_usage;
+mod async_runtime;
+mod checkpoints;
+#[cfg(feature = "cli")]
+mod cli;
 mod commands;
 mod db;
+mod device_pairing;
+mod host;
 mod host_api;
+mod worktrees;
 
-use commands::{
-    add_repo, clone_repo, commit_working_tree, create_agent, create_group, create_local_branch,
-    delete_agent, delete_group, delete_local_branch, get_commit_changes, get_current_branch,
-    get_remote_url, get_repo_sync_status, get_repo_working_tree_status, get_working_tree_file_diff,
-    list_agent_models, list_agents, list_git_history, list_groups, list_local_branches, list_repos,
-    list_working_tree_changes, move_repo_to_group, open_in_cursor, open_in_file_manager, pull_repo,
-    remove_repo, rename_agent, rename_group, run_repo_agent, stop_repo_agent, switch_branch,
-    AgentRuntimeState,
-};
+use agent_schedules::start_schedule_runner;
+#[cfg(feature = "cli")]
+pub use cli::run_cli;
 use db::Database;
-use host_api::{
-    create_host_access_state, get_host_access_settings, get_lan_listen_url,
-    set_host_access_settings, start_host_bridge, HostBridgeState,
-};
-use std::collections::HashMap;
+use host::Host;
+use host_api::{create_host_access_state, start_host_bridge, HostBridgeState};
 
+fn create_host() -> Host {
+    let database = Database::new().expect("Failed to initialize database");
+    Host::new(database, HostBridgeState::new(), create_host_access_state())
+}
+
+/// Runs the host bridge, agent runtime and schedules without the desktop window,
+/// until the host bridge stops.
+pub fn run_headless() {
+    let host = create_host();
+    start_schedule_runner(host.clone());
+    let Some(bridge) = start_host_bridge(host) else {
+        std::process::exit(1);
+    };
+    let _ = async_runtime::block_on(bridge);
+    std::process::exit(1);
+}
+
+#[cfg(feature = "desktop")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let database = Database::new().expect("Failed to initialize database");
-    let host_bridge_state = HostBridgeState::new();
-    let host_access_state = create_host_access_state();
+    use access_tokens::{
+        create_access_token, list_access_tokens, list_paired_devices, revoke_access_token,
+    };
+    use agent_approvals::{approve_tool_call, deny_tool_call};
+    use agent_batches::{
+        delete_agent_batch, get_agent_batch, list_agent_batches, run_group_agents,
+    };
+    use agent_input::send_agent_input;
+    use agent_policy::list_agent_policy_violations;
+    use agent_queue::{cancel_queued_prompt, list_agent_queue, reorder_agent_queue};
+    use agent_run_changes::{get_agent_run_changes, revert_agent_run};
+    use agent_scheduler::{
+        get_scheduler_settings, list_scheduled_runs, set_repo_run_limit, set_scheduler_settings,
+    };
+    use agent_schedules::{
+        create_agent_schedule, delete_agent_schedule, list_agent_schedules,
+        set_agent_schedule_enabled,
+    };
+    use agent_usage::{get_agent_stats, get_group_stats, get_repo_stats};
+    use checkpoints::{
+        delete_agent_checkpoint, list_agent_checkpoints, restore_agent_checkpoint,
+        set_agent_checkpoints,
+    };
+    use commands::{
+        add_repo, clone_repo, commit_working_tree, create_agent, create_group, create_local_branch,
+        delete_agent, delete_group, delete_local_branch, get_agent_run_transcript,
+        get_commit_changes, get_current_branch, get_remote_url, get_repo_sync_status,
+        get_repo_working_tree_status, get_working_tree_file_diff, list_agent_models,
+        list_agent_runs, list_agents, list_git_history, list_groups, list_local_branches,
+        list_repos, list_working_tree_changes, move_repo_to_group, open_in_cursor,
+        open_in_file_manager, pull_repo, remove_repo, rename_agent, rename_group,
+        reset_agent_session, run_repo_agent, set_agent_backend, set_agent_run_limits,
+        stop_repo_agent, switch_branch,
+    };
+    use device_pairing::{cancel_device_pairing, start_device_pairing};
+    use host_api::{get_host_access_settings, get_lan_listen_url, set_host_access_settings};
+    use worktrees::{
+        cleanup_stale_worktrees, create_agent_worktree, discard_agent_worktree,
+        merge_agent_worktree, rebase_agent_worktree,
+    };
+
+    let host = create_host();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
-        .manage(database)
-        .manage(host_bridge_state.clone())
-        .manage(host_access_state.clone())
-        .manage(AgentRuntimeState {
-            pids_by_agent_id: std::sync::Mutex::new(HashMap::new()),
-        })
+        .manage(host.clone())
         .setup(move |app| {
-            start_host_bridge(
-                app.handle().clone(),
-                host_bridge_state.clone(),
-                host_access_state.clone(),
-            );
+            host.attach_app(app.handle().clone());
+            start_host_bridge(host.clone());
+            start_schedule_runner(host.clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -66,9 +127,47 @@ pub fn run() {
             create_agent,
             delete_agent,
             rename_agent,
+            set_agent_backend,
+            reset_agent_session,
+            set_agent_run_limits,
             list_agent_models,
             run_repo_agent,
             stop_repo_agent,
+            send_agent_input,
+            approve_tool_call,
+            deny_tool_call,
+            list_agent_queue,
+            reorder_agent_queue,
+            cancel_queued_prompt,
+            list_scheduled_runs,
+            get_scheduler_settings,
+            set_scheduler_settings,
+            set_repo_run_limit,
+            list_agent_schedules,
+            create_agent_schedule,
+            set_agent_schedule_enabled,
+            delete_agent_schedule,
+            list_agent_runs,
+            get_agent_run_transcript,
+            get_agent_run_changes,
+            list_agent_policy_violations,
+            get_agent_stats,
+            get_repo_stats,
+            get_group_stats,
+            run_group_agents,
+            get_agent_batch,
+            list_agent_batches,
+            delete_agent_batch,
+            revert_agent_run,
+            set_agent_checkpoints,
+            list_agent_checkpoints,
+            restore_agent_checkpoint,
+            delete_agent_checkpoint,
+            create_agent_worktree,
+            merge_agent_worktree,
+            rebase_agent_worktree,
+            discard_agent_worktree,
+            cleanup_stale_worktrees,
             list_groups,
             create_group,
             rename_group,
@@ -76,7 +175,13 @@ pub fn run() {
             move_repo_to_group,
             get_host_access_settings,
             get_lan_listen_url,
-            set_host_access_settings
+            set_host_access_settings,
+            list_access_tokens,
+            create_access_token,
+            revoke_access_token,
+            list_paired_devices,
+            start_device_pairing,
+            cancel_device_pairing
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -2,5 +2,11 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
-    symphony_lib::run()
+    // The command line is the separate `symphony` binary; `--headless` stays here for
+    // hosts that start the app that way.
+    if std::env::args().nth(1).as_deref() == Some("--headless") {
+        symphony_lib::run_headless()
+    } else {
+        symphony_lib::run()
+    }
 }
@@ -0,0 +1,499 @@
+use crate::agent_queue::ensure_agents_idle;
+use crate::commands::{
+    get_agent_by_id, get_current_branch, run_git_command, run_git_status_command, Agent,
+};
+use crate::host::Host;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorktreeCleanupSummary {
+    pub removed_worktrees: Vec<String>,
+    pub detached_agent_ids: Vec<i64>,
+}
+
+struct AgentWorkspace {
+    agent_name: String,
+    repo_name: String,
+    repo_path: String,
+    worktree_path: Option<String>,
+    branch_name: Option<String>,
+}
+
+fn worktrees_root() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Could not find home directory".to_string())?;
+    Ok(home.join(".symphony").join("worktrees"))
+}
+
+fn load_agent_workspace(conn: &Connection, agent_id: i64) -> Result<AgentWorkspace, String> {
+    conn.query_row(
+        "SELECT agents.name, repos.name, repos.path, agents.worktree_path, agents.branch_name
+         FROM agents
+         JOIN repos ON repos.id = agents.repo_id
+         WHERE agents.id = ?1",
+        rusqlite::params![agent_id],
+        |row| {
+            Ok(AgentWorkspace {
+                agent_name: row.get(0)?,
+                repo_name: row.get(1)?,
+                repo_path: row.get(2)?,
+                worktree_path: row.get(3)?,
+                branch_name: row.get(4)?,
+            })
+        },
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => "Agent not found".to_string(),
+        other => other.to_string(),
+    })
+}
+
+fn slugify(value: &str) -> String {
+    let mut slug = String::new();
+    for character in value.trim().chars() {
+        if character.is_ascii_alphanumeric() {
+            slug.push(character.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let trimmed = slug.trim_end_matches('-');
+    if trimmed.is_empty() {
+        "agent".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+fn local_branch_exists(repo_path: &str, branch_name: &str) -> Result<bool, String> {
+    run_git_status_command(
+        repo_path,
+        &[
+            "show-ref",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{}", branch_name),
+        ],
+    )
+}
+
+/// Creates a `git worktree` on a dedicated `symphony/<agent-name>` branch for the
+/// agent and records it on the agent row. Git runs without the database lock, so
+/// the caller must not hold it.
+pub(crate) fn provision_agent_worktree(host: &Host, agent_id: i64) -> Result<(), String> {
+    let workspace = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        load_agent_workspace(&conn, agent_id)?
+    };
+    if workspace.worktree_path.is_some() {
+        return Err("This agent already has an isolated worktree".to_string());
+    }
+
+    let agent_slug = slugify(&workspace.agent_name);
+    let mut branch_name = format!("symphony/{}", agent_slug);
+    if local_branch_exists(&workspace.repo_path, &branch_name)? {
+        branch_name = format!("{}-{}", branch_name, agent_id);
+    }
+    if !run_git_status_command(
+        &workspace.repo_path,
+        &["check-ref-format", "--branch", &branch_name],
+    )? {
+        return Err(format!("Invalid worktree branch name '{}'", branch_name));
+    }
+
+    let worktree_path = worktrees_root()?
+        .join(slugify(&workspace.repo_name))
+        .join(format!("{}-{}", agent_slug, agent_id));
+    if worktree_path.exists() {
+        return Err(format!(
+            "Worktree destination already exists: {}",
+            worktree_path.display()
+        ));
+    }
+    if let Some(parent) = worktree_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let worktree_path_string = worktree_path.to_string_lossy().to_string();
+    run_git_command(
+        &workspace.repo_path,
+        &[
+            "worktree".to_string(),
+            "add".to_string(),
+            "-b".to_string(),
+            branch_name.clone(),
+            worktree_path_string.clone(),
+            "HEAD".to_string(),
+        ],
+    )?;
+
+    // Another call may have given the agent a worktree while git was running.
+    let updated_rows = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE agents SET worktree_path = ?1, branch_name = ?2
+             WHERE id = ?3 AND worktree_path IS NULL",
+            rusqlite::params![worktree_path_string, branch_name, agent_id],
+        )
+        .map_err(|e| e.to_string())?
+    };
+    if updated_rows == 0 {
+        let _ = remove_worktree(&workspace.repo_path, &worktree_path_string);
+        let _ = run_git_command(
+            &workspace.repo_path,
+            &["branch".to_string(), "-D".to_string(), branch_name],
+        );
+        return Err("This agent already has an isolated worktree".to_string());
+    }
+
+    Ok(())
+}
+
+/// Returns the directory an agent run should execute in: the agent's worktree if it
+/// has one, otherwise the repository checkout. With `provision` set, agents without a
+/// worktree get one first.
+pub(crate) fn resolve_agent_working_directory(
+    host: &Host,
+    agent_id: i64,
+    repo_path: &str,
+    provision: bool,
+) -> Result<String, String> {
+    let worktree_path: Option<String> = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT worktree_path FROM agents WHERE id = ?1",
+            rusqlite::params![agent_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .flatten()
+    };
+
+    match worktree_path {
+        Some(path) if Path::new(&path).is_dir() => Ok(path),
+        Some(path) => Err(format!(
+            "The agent's worktree no longer exists at {}. Discard it or clean up stale worktrees.",
+            path
+        )),
+        None if provision => {
+            provision_agent_worktree(host, agent_id)?;
+            resolve_agent_working_directory(host, agent_id, repo_path, false)
+        }
+        None => Ok(repo_path.to_string()),
+    }
+}
+
+/// Removes the worktrees and branches of agents that are about to be deleted. Fails
+/// without removing anything if any of them holds work that is not in the repo yet:
+/// uncommitted changes, or commits that were never merged.
+pub(crate) fn release_agent_worktrees(host: &Host, agent_ids: &[i64]) -> Result<(), String> {
+    let workspaces = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        agent_ids
+            .iter()
+            .map(|agent_id| load_agent_workspace(&conn, *agent_id))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    // Worktrees of repos that are gone from disk are left to the stale cleanup.
+    let worktrees: Vec<_> = workspaces
+        .iter()
+        .filter(|workspace| Path::new(&workspace.repo_path).is_dir())
+        .filter_map(|workspace| {
+            let (path, branch) = require_worktree(workspace).ok()?;
+            Some((workspace, path, branch))
+        })
+        .collect();
+
+    for (workspace, worktree_path, branch_name) in &worktrees {
+        if Path::new(worktree_path).is_dir() && has_uncommitted_changes(worktree_path)? {
+            return Err(format!(
+                "Agent '{}' has uncommitted changes in its worktree. Commit or discard them first.",
+                workspace.agent_name
+            ));
+        }
+        if local_branch_exists(&workspace.repo_path, branch_name)?
+            && !run_git_status_command(
+                &workspace.repo_path,
+                &["merge-base", "--is-ancestor", branch_name, "HEAD"],
+            )?
+        {
+            return Err(format!(
+                "Agent '{}' has unmerged commits on '{}'. Merge or discard its worktree first.",
+                workspace.agent_name, branch_name
+            ));
+        }
+    }
+
+    for (workspace, worktree_path, branch_name) in worktrees {
+        remove_worktree(&workspace.repo_path, &worktree_path)?;
+        if local_branch_exists(&workspace.repo_path, &branch_name)? {
+            run_git_command(
+                &workspace.repo_path,
+                &["branch".to_string(), "-d".to_string(), branch_name],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn require_worktree(workspace: &AgentWorkspace) -> Result<(String, String), String> {
+    match (&workspace.worktree_path, &workspace.branch_name) {
+        (Some(path), Some(branch)) => Ok((path.clone(), branch.clone())),
+        _ => Err("This agent does not have an isolated worktree".to_string()),
+    }
+}
+
+fn has_uncommitted_changes(path: &str) -> Result<bool, String> {
+    let output = run_git_command(path, &["status".to_string(), "--porcelain".to_string()])?;
+    Ok(!output.trim().is_empty())
+}
+
+fn remove_worktree(repo_path: &str, worktree_path: &str) -> Result<(), String> {
+    if Path::new(worktree_path).exists() {
+        run_git_command(
+            repo_path,
+            &[
+                "worktree".to_string(),
+                "remove".to_string(),
+                "--force".to_string(),
+                worktree_path.to_string(),
+            ],
+        )?;
+    }
+    run_git_command(repo_path, &["worktree".to_string(), "prune".to_string()])?;
+    Ok(())
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn create_agent_worktree(host: Host, agent_id: i64) -> Result<Agent, String> {
+    provision_agent_worktree(&host, agent_id)?;
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    get_agent_by_id(&conn, agent_id)
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn merge_agent_worktree(host: Host, agent_id: i64) -> Result<String, String> {
+    let workspace = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        load_agent_workspace(&conn, agent_id)?
+    };
+    let (worktree_path, branch_name) = require_worktree(&workspace)?;
+
+    if has_uncommitted_changes(&worktree_path)? {
+        return Err("Commit the agent's changes in its worktree before merging".to_string());
+    }
+
+    let target_branch = get_current_branch(workspace.repo_path.clone())?;
+    let merge_result = run_git_command(
+        &workspace.repo_path,
+        &[
+            "merge".to_string(),
+            "--no-ff".to_string(),
+            "--no-edit".to_string(),
+            branch_name.clone(),
+        ],
+    );
+    if let Err(error) = merge_result {
+        let _ = run_git_command(
+            &workspace.repo_path,
+            &["merge".to_string(), "--abort".to_string()],
+        );
+        return Err(error);
+    }
+
+    Ok(format!(
+        "Merged '{}' into '{}'.",
+        branch_name, target_branch
+    ))
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn rebase_agent_worktree(host: Host, agent_id: i64) -> Result<String, String> {
+    let workspace = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        load_agent_workspace(&conn, agent_id)?
+    };
+    let (worktree_path, branch_name) = require_worktree(&workspace)?;
+
+    if has_uncommitted_changes(&worktree_path)? {
+        return Err("Commit or discard the agent's changes before rebasing".to_string());
+    }
+
+    let upstream = run_git_command(
+        &workspace.repo_path,
+        &["rev-parse".to_string(), "HEAD".to_string()],
+    )?
+    .trim()
+    .to_string();
+    let target_branch = get_current_branch(workspace.repo_path.clone())?;
+
+    let rebase_result = run_git_command(&worktree_path, &["rebase".to_string(), upstream]);
+    if let Err(error) = rebase_result {
+        let _ = run_git_command(
+            &worktree_path,
+            &["rebase".to_string(), "--abort".to_string()],
+        );
+        return Err(error);
+    }
+
+    Ok(format!(
+        "Rebased '{}' onto '{}'.",
+        branch_name, target_branch
+    ))
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn discard_agent_worktree(host: Host, agent_id: i64) -> Result<(), String> {
+    ensure_agents_idle(
+        &host,
+        &[agent_id],
+        "Stop the agent before discarding its worktree",
+    )?;
+    let workspace = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        load_agent_workspace(&conn, agent_id)?
+    };
+    let (worktree_path, branch_name) = require_worktree(&workspace)?;
+
+    remove_worktree(&workspace.repo_path, &worktree_path)?;
+    if local_branch_exists(&workspace.repo_path, &branch_name)? {
+        run_git_command(
+            &workspace.repo_path,
+            &["branch".to_string(), "-D".to_string(), branch_name],
+        )?;
+    }
+
+    let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE agents SET worktree_path = NULL, branch_name = NULL WHERE id = ?1",
+        rusqlite::params![agent_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn cleanup_stale_worktrees(host: Host) -> Result<WorktreeCleanupSummary, String> {
+    let root = worktrees_root()?;
+
+    let mut summary = WorktreeCleanupSummary {
+        removed_worktrees: Vec::new(),
+        detached_agent_ids: Vec::new(),
+    };
+
+    let (agent_worktrees, repo_paths) = {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT id, worktree_path FROM agents WHERE worktree_path IS NOT NULL")
+            .map_err(|e| e.to_string())?;
+        let agent_worktrees = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let mut repo_stmt = conn
+            .prepare("SELECT path FROM repos")
+            .map_err(|e| e.to_string())?;
+        let repo_paths = repo_stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        (agent_worktrees, repo_paths)
+    };
+
+    // Agents pointing at a worktree that was deleted from disk get detached.
+    let mut referenced_paths: HashSet<PathBuf> = HashSet::new();
+    for (agent_id, worktree_path) in agent_worktrees {
+        if Path::new(&worktree_path).is_dir() {
+            referenced_paths.insert(PathBuf::from(worktree_path));
+        } else {
+            summary.detached_agent_ids.push(agent_id);
+        }
+    }
+    if !summary.detached_agent_ids.is_empty() {
+        let conn = host.db().conn.lock().map_err(|e| e.to_string())?;
+        for agent_id in &summary.detached_agent_ids {
+            conn.execute(
+                "UPDATE agents SET worktree_path = NULL, branch_name = NULL WHERE id = ?1",
+                rusqlite::params![agent_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    // Worktrees under ~/.symphony/worktrees that no agent refers to any more are removed.
+    for repo_path in repo_paths {
+        if !Path::new(&repo_path).is_dir() {
+            continue;
+        }
+        run_git_command(&repo_path, &["worktree".to_string(), "prune".to_string()])?;
+
+        let output = run_git_command(
+            &repo_path,
+            &[
+                "worktree".to_string(),
+                "list".to_string(),
+                "--porcelain".to_string(),
+            ],
+        )?;
+        for (worktree_path, branch_ref) in parse_worktree_list(&output) {
+            let path = PathBuf::from(&worktree_path);
+            if !path.starts_with(&root) || referenced_paths.contains(&path) {
+                continue;
+            }
+
+            remove_worktree(&repo_path, &worktree_path)?;
+            if let Some(branch_name) = branch_ref
+                .as_deref()
+                .and_then(|branch| branch.strip_prefix("refs/heads/"))
+                .filter(|branch| branch.starts_with("symphony/"))
+            {
+                let _ = run_git_command(
+                    &repo_path,
+                    &[
+                        "branch".to_string(),
+                        "-D".to_string(),
+                        branch_name.to_string(),
+                    ],
+                );
+            }
+            summary.removed_worktrees.push(worktree_path);
+        }
+    }
+
+    Ok(summary)
+}
+
+fn parse_worktree_list(raw_output: &str) -> Vec<(String, Option<String>)> {
+    let mut worktrees = Vec::new();
+    let mut current_path: Option<String> = None;
+    let mut current_branch: Option<String> = None;
+
+    for line in raw_output.lines() {
+        if let Some(path) = line.strip_prefix("worktree ") {
+            if let Some(previous_path) = current_path.take() {
+                worktrees.push((previous_path, current_branch.take()));
+            }
+            current_path = Some(path.to_string());
+            current_branch = None;
+        } else if let Some(branch) = line.strip_prefix("branch ") {
+            current_branch = Some(branch.to_string());
+        }
+    }
+
+    if let Some(path) = current_path {
+        worktrees.push((path, current_branch));
+    }
+
+    worktrees
+}
@@ -25,17 +25,21 @@ import {
 	listen,
 	openPath,
 	openUrl,
+	pairWithHost,
 	setWebAuthToken,
 	verifyWebAuthToken,
 } from '@/lib/host-bridge';
 import type {
 	Agent,
+	AgentApprovalRequest,
 	AgentConversationEntry,
 	AgentModelOption,
 	AgentRunModelChoice,
+	DevicePairing,
 	GitCommit,
 	GitCommitFileDiff,
 	Group,
+	PairedDevice,
 	Repo,
 	RepoSyncStatus,
 } from '@/lib/types';
@@ -63,10 +67,27 @@ type AgentStreamPayload = {
 	line: string;
 };
 
+type AgentRunOutcome =
+	| 'succeeded'
+	| 'failed'
+	| 'cancelled'
+	| 'timed_out'
+	| 'crashed';
+
+type AgentTerminationReason =
+	| 'cancelled'
+	| 'timeout'
+	| 'idle_timeout'
+	| 'cpu_time_limit'
+	| 'policy_violation';
+
 type AgentDonePayload = {
 	runId: string;
 	agentId: number;
 	success: boolean;
+	outcome?: AgentRunOutcome;
+	exitCode?: number | null;
+	terminationReason?: AgentTerminationReason | null;
 };
 
 type AgentStreamPayloadWire = AgentStreamPayload & {
@@ -112,31 +133,105 @@ function isMacOS() {
 const SHORTCUT_MODIFIER_LABEL = isMacOS() ? 'Cmd' : 'Ctrl';
 const SIMULATOR_MODE_STORAGE_KEY = 'symphony:simulator-mode';
 const RAW_LOGS_STORAGE_KEY = 'symphony:raw-logs';
+const ASK_BEFORE_TOOL_CALLS_STORAGE_KEY = 'symphony:ask-before-tool-calls';
 const ACCESS_TOKEN_QUERY_PARAM = 'access_token';
+// Only scoped tokens may be passed in the URL; the host token must be typed in.
+const SCOPED_ACCESS_TOKEN_PREFIX = 'sym_';
+const PAIRING_CODE_QUERY_PARAM = 'pairing_code';
+const PAIRING_CODE_PATTERN = /^[a-z\d]{4}-?[a-z\d]{4}$/i;
 
 function randomRunId() {
 	if ('randomUUID' in crypto) return crypto.randomUUID();
 	return `${Date.now()}-${Math.random().toString(36).slice(2)}`;
 }
 
-function getAccessTokenFromQueryParameter() {
+/** Only backends that speak Symphony's approval protocol can pause a run to ask about a tool call. */
+function canAskBeforeToolCalls(
+	agent: Agent | undefined,
+	isSimulatorMode: boolean,
+): boolean {
+	return isSimulatorMode || agent?.supports_tool_approval === true;
+}
+
+function describeFailedAgentRun(payload: AgentDonePayload): string {
+	switch (payload.outcome) {
+		case 'cancelled': {
+			return 'Agent run stopped.';
+		}
+		case 'timed_out': {
+			return payload.terminationReason === 'idle_timeout'
+				? 'Agent run stopped after producing no output for too long.'
+				: 'Agent run timed out.';
+		}
+		case 'crashed': {
+			return payload.terminationReason === 'cpu_time_limit'
+				? 'Agent run exceeded its CPU time limit.'
+				: 'Agent run crashed.';
+		}
+		case 'failed': {
+			if (payload.terminationReason === 'policy_violation') {
+				return 'Agent run stopped by the repository policy.';
+			}
+			return typeof payload.exitCode === 'number'
+				? `Agent run failed with exit code ${payload.exitCode}.`
+				: 'Agent run failed.';
+		}
+		default: {
+			return 'Agent run stopped or failed.';
+		}
+	}
+}
+
+function getQueryParameter(name: string) {
 	if (globalThis.window === undefined) return null;
 	const searchParameters = new URLSearchParams(
 		globalThis.window.location.search,
 	);
-	const token = searchParameters.get(ACCESS_TOKEN_QUERY_PARAM)?.trim();
-	if (!token) return null;
-	return token;
+	const value = searchParameters.get(name)?.trim();
+	if (!value) return null;
+	return value;
 }
 
-function removeAccessTokenFromQueryParameter() {
+function removeQueryParameter(name: string) {
 	if (globalThis.window === undefined) return;
 	const url = new URL(globalThis.window.location.href);
-	if (!url.searchParams.has(ACCESS_TOKEN_QUERY_PARAM)) return;
-	url.searchParams.delete(ACCESS_TOKEN_QUERY_PARAM);
+	if (!url.searchParams.has(name)) return;
+	url.searchParams.delete(name);
 	globalThis.window.history.replaceState({}, document.title, url.toString());
 }
 
+const DEVICE_PLATFORM_PATTERNS: Array<[string, RegExp]> = [
+	['iPhone', /iPhone/],
+	['iPad', /iPad/],
+	['Android', /Android/],
+	['Mac', /Macintosh|Mac OS/],
+	['Windows', /Windows/],
+	['Linux', /Linux/],
+];
+const DEVICE_BROWSER_PATTERNS: Array<[string, RegExp]> = [
+	['Edge', /Edg\//],
+	['Firefox', /Firefox\/|FxiOS/],
+	['Chrome', /Chrome\/|CriOS/],
+	['Safari', /Safari\//],
+];
+
+function matchUserAgent(
+	patterns: Array<[string, RegExp]>,
+	userAgent: string,
+) {
+	return patterns.find(([, pattern]) => pattern.test(userAgent))?.[0];
+}
+
+/** A name for this browser in the host's list of paired devices. */
+function describeThisDevice() {
+	if (typeof navigator === 'undefined') return 'Browser';
+	const {userAgent} = navigator;
+	const platform = matchUserAgent(DEVICE_PLATFORM_PATTERNS, userAgent);
+	const browser =
+		matchUserAgent(DEVICE_BROWSER_PATTERNS, userAgent) ?? 'Browser';
+	return platform ? `${browser} on ${platform}` : browser;
+}
+
 function parseAgentConversationLine(
 	rawLine: string,
 ): Omit<AgentConversationEntry, 'id'> | null {
@@ -307,9 +402,22 @@ function App() {
 			return false;
 		}
 	});
+	const [askBeforeToolCalls, setAskBeforeToolCalls] = useState<boolean>(() => {
+		try {
+			return localStorage.getItem(ASK_BEFORE_TOOL_CALLS_STORAGE_KEY) === 'true';
+		} catch {
+			return false;
+		}
+	});
 	const [hostLanAccessEnabled, setHostLanAccessEnabled] = useState(false);
 	const [lanListenUrl, setLanListenUrl] = useState<string | null>(null);
 	const [isHostLanAccessLoading, setIsHostLanAccessLoading] = useState(false);
+	const [pairedDevices, setPairedDevices] = useState<PairedDevice[]>([]);
+	const [devicePairing, setDevicePairing] = useState<DevicePairing | null>(
+		null,
+	);
+	const [isDevicePairingLoading, setIsDevicePairingLoading] = useState(false);
+	const [revokingDeviceId, setRevokingDeviceId] = useState<number | null>(null);
 	const [agentsByRepoId, setAgentsByRepoId] = useState<Record<number, Agent[]>>(
 		{},
 	);
@@ -331,6 +439,9 @@ function App() {
 	const [selectedAgentId, setSelectedAgentId] = useState<number | null>(null);
 	const [agentPrompt, setAgentPrompt] = useState('');
 	const [runningAgentIds, setRunningAgentIds] = useState<number[]>([]);
+	const [pendingApprovalsByAgentId, setPendingApprovalsByAgentId] = useState<
+		Record<number, AgentApprovalRequest[]>
+	>({});
 	const [agentMessagesById, setAgentMessagesById] = useState<
 		Record<number, AgentConversationEntry[]>
 	>({});
@@ -411,6 +522,22 @@ function App() {
 		return false;
 	}, []);
 
+	const pairThisDevice = useCallback(
+		async (code: string) => {
+			try {
+				const token = await pairWithHost(code, describeThisDevice());
+				return await authenticateHostToken(token);
+			} catch (error) {
+				setHostAuthState('unauthorized');
+				setHostAuthError(
+					error instanceof Error ? error.message : String(error),
+				);
+				return false;
+			}
+		},
+		[authenticateHostToken],
+	);
+
 	const loadHostAccessSettings = useCallback(async () => {
 		if (!isRuntimeAuthorized) return;
 		setIsHostLanAccessLoading(true);
@@ -455,6 +582,53 @@ function App() {
 		}
 	}, []);
 
+	const loadPairedDevices = useCallback(async () => {
+		if (!isTauriRuntime) return;
+		try {
+			const devices = await invoke<PairedDevice[]>('list_paired_devices');
+			setPairedDevices(devices);
+		} catch (error) {
+			console.error('Failed to load paired devices:', error);
+		}
+	}, []);
+
+	const startDevicePairing = useCallback(async () => {
+		setIsDevicePairingLoading(true);
+		try {
+			const pairing = await invoke<DevicePairing>('start_device_pairing');
+			setDevicePairing(pairing);
+		} catch (error) {
+			toast.error(String(error));
+		} finally {
+			setIsDevicePairingLoading(false);
+		}
+	}, []);
+
+	const cancelDevicePairing = useCallback(async () => {
+		setDevicePairing(null);
+		try {
+			await invoke('cancel_device_pairing');
+		} catch (error) {
+			toast.error(String(error));
+		}
+	}, []);
+
+	const revokePairedDevice = useCallback(
+		async (device: PairedDevice) => {
+			setRevokingDeviceId(device.id);
+			try {
+				await invoke('revoke_access_token', {tokenId: device.id});
+				toast.success(`Revoked ${device.name}`);
+				await loadPairedDevices();
+			} catch (error) {
+				toast.error(String(error));
+			} finally {
+				setRevokingDeviceId(null);
+			}
+		},
+		[loadPairedDevices],
+	);
+
 	const openSelectedRepoInExplorer = useCallback(async () => {
 		if (!selectedRepo) return;
 		try {
@@ -680,8 +854,26 @@ function App() {
 
 	useEffect(() => {
 		if (isTauriRuntime) return;
-		const queryToken = getAccessTokenFromQueryParameter();
+		const pairingCode = getQueryParameter(PAIRING_CODE_QUERY_PARAM);
+		const queryParameterToken = getQueryParameter(ACCESS_TOKEN_QUERY_PARAM);
+		if (queryParameterToken) {
+			removeQueryParameter(ACCESS_TOKEN_QUERY_PARAM);
+		}
+		const queryToken = queryParameterToken?.startsWith(SCOPED_ACCESS_TOKEN_PREFIX)
+			? queryParameterToken
+			: null;
 		const savedToken = getWebAuthToken();
+		if (pairingCode) {
+			// Codes work once, so drop it from the URL whether or not pairing works.
+			removeQueryParameter(PAIRING_CODE_QUERY_PARAM);
+			setHostAuthState('checking');
+			void pairThisDevice(pairingCode).then(isPaired => {
+				if (!isPaired && savedToken) {
+					void authenticateHostToken(savedToken);
+				}
+			});
+			return;
+		}
 		const startupToken = queryToken ?? savedToken;
 		if (queryToken) {
 			setHostAuthTokenInput(queryToken);
@@ -691,27 +883,44 @@ function App() {
 			return;
 		}
 		setHostAuthState('checking');
-		void authenticateHostToken(startupToken).then(isAuthenticated => {
-			if (isAuthenticated && queryToken) {
-				removeAccessTokenFromQueryParameter();
-			}
-		});
-	}, [authenticateHostToken]);
+		void authenticateHostToken(startupToken);
+	}, [authenticateHostToken, pairThisDevice]);
 
 	useEffect(() => {
 		if (!isRuntimeAuthorized) return;
 		loadRepos();
 		loadGroups();
 		loadHostAccessSettings();
+		loadPairedDevices();
 		loadAgentModelOptions();
 	}, [
 		isRuntimeAuthorized,
 		loadRepos,
 		loadGroups,
 		loadHostAccessSettings,
+		loadPairedDevices,
 		loadAgentModelOptions,
 	]);
 
+	useEffect(() => {
+		if (!isTauriRuntime) {
+			return () => {};
+		}
+		const unlistenPromise = listen<{tokenId: number; name: string}>(
+			'host-device-paired',
+			event => {
+				toast.success(`Paired ${event.payload.name}`);
+				setDevicePairing(null);
+				void loadPairedDevices();
+			},
+		);
+		return () => {
+			void unlistenPromise.then(unlisten => {
+				unlisten();
+			});
+		};
+	}, [loadPairedDevices]);
+
 	useEffect(() => {
 		(async () => {
 			setIsVersionLoading(true);
@@ -749,6 +958,17 @@ function App() {
 		}
 	}, [showRawLogs]);
 
+	useEffect(() => {
+		try {
+			localStorage.setItem(
+				ASK_BEFORE_TOOL_CALLS_STORAGE_KEY,
+				askBeforeToolCalls ? 'true' : 'false',
+			);
+		} catch {
+			// Ignore storage errors (private mode / restricted environments).
+		}
+	}, [askBeforeToolCalls]);
+
 	useEffect(() => {
 		if (!isRuntimeAuthorized) return;
 		void checkRepoUpdates(false);
@@ -1012,6 +1232,10 @@ function App() {
 				const payload = event.payload as AgentStreamPayloadWire;
 				const agentId = payload.agentId ?? payload.agent_id;
 				if (typeof agentId !== 'number') return;
+				// A queued prompt starts right after the previous run's done event.
+				setRunningAgentIds(previous =>
+					previous.includes(agentId) ? previous : [...previous, agentId],
+				);
 				appendAgentLog(agentId, payload.line);
 				const streamRecord = parseAgentStreamRecord(payload.line);
 				if (streamRecord?.type === 'thinking') {
@@ -1084,14 +1308,59 @@ function App() {
 				if (!payload.success) {
 					appendAgentMessage(agentId, {
 						role: 'error',
-						text: 'Agent run stopped or failed.',
+						text: describeFailedAgentRun(payload),
 					});
 				}
 				delete pendingEditedPathByAgentReference.current[agentId];
 				setRunningAgentIds(previous => previous.filter(id => id !== agentId));
+				setPendingApprovalsByAgentId(previous => {
+					if (!previous[agentId]) return previous;
+					const next = {...previous};
+					delete next[agentId];
+					return next;
+				});
+			},
+		);
+
+		const unlistenApprovalRequestPromise = listen<AgentApprovalRequest>(
+			'repo-agent-approval-request',
+			event => {
+				const request = event.payload;
+				setPendingApprovalsByAgentId(previous => ({
+					...previous,
+					[request.agentId]: [
+						...(previous[request.agentId] ?? []).filter(
+							pending => pending.callId !== request.callId,
+						),
+						request,
+					],
+				}));
 			},
 		);
 
+		const unlistenApprovalResolvedPromise = listen<{
+			agentId: number;
+			callId: string;
+		}>('repo-agent-approval-resolved', event => {
+			const {agentId, callId} = event.payload;
+			setPendingApprovalsByAgentId(previous => ({
+				...previous,
+				[agentId]: (previous[agentId] ?? []).filter(
+					pending => pending.callId !== callId,
+				),
+			}));
+		});
+
+		const unlistenCheckpointFailedPromise = listen<{
+			agentId: number;
+			error: string;
+		}>('repo-agent-checkpoint-failed', event => {
+			appendAgentMessage(event.payload.agentId, {
+				role: 'error',
+				text: `No checkpoint was created for this run: ${event.payload.error}`,
+			});
+		});
+
 		return () => {
 			void unlistenStdoutPromise.then(unlisten => {
 				unlisten();
@@ -1102,6 +1371,15 @@ function App() {
 			void unlistenDonePromise.then(unlisten => {
 				unlisten();
 			});
+			void unlistenCheckpointFailedPromise.then(unlisten => {
+				unlisten();
+			});
+			void unlistenApprovalRequestPromise.then(unlisten => {
+				unlisten();
+			});
+			void unlistenApprovalResolvedPromise.then(unlisten => {
+				unlisten();
+			});
 		};
 	}, [
 		isRuntimeAuthorized,
@@ -1111,6 +1389,49 @@ function App() {
 		finalizeThinkingMessage,
 	]);
 
+	// Events missed while the host bridge could not replay them, e.g. after a host
+	// restart. Reloads what they would have changed.
+	const reloadAfterEventGap = useCallback(async () => {
+		void loadRepos();
+		void loadGroups();
+		if (runningAgentIds.length === 0) return;
+		try {
+			const scheduledRuns =
+				await invoke<Array<{agentId: number}>>('list_scheduled_runs');
+			const finishedAgentIds = await Promise.all(
+				runningAgentIds.map(async agentId => {
+					if (scheduledRuns.some(run => run.agentId === agentId)) {
+						return undefined;
+					}
+					const [latestRun] = await invoke<Array<{status: string}>>(
+						'list_agent_runs',
+						{agentId, limit: 1},
+					);
+					return latestRun?.status === 'running' ? undefined : agentId;
+				}),
+			);
+			setRunningAgentIds(previous =>
+				previous.filter(agentId => !finishedAgentIds.includes(agentId)),
+			);
+		} catch (error) {
+			console.error('Failed to reload agent runs:', error);
+		}
+	}, [loadRepos, loadGroups, runningAgentIds]);
+
+	useEffect(() => {
+		if (!isRuntimeAuthorized) {
+			return () => {};
+		}
+		const unlistenEventGapPromise = listen('host-events-gap', () => {
+			void reloadAfterEventGap();
+		});
+		return () => {
+			void unlistenEventGapPromise.then(unlisten => {
+				unlisten();
+			});
+		};
+	}, [isRuntimeAuthorized, reloadAfterEventGap]);
+
 	const createAgent = useCallback(
 		async (repoId: number, name: string) => {
 			setIsCreatingAgentRepoId(repoId);
@@ -1144,10 +1465,15 @@ function App() {
 		if (!selectedRepo || !selectedAgentId) return;
 		const trimmedPrompt = agentPrompt.trim();
 		if (!trimmedPrompt) return;
-		if (runningAgentIds.includes(selectedAgentId)) {
-			toast.error('This agent is already running.');
-			return;
-		}
+		const isAgentBusy = runningAgentIds.includes(selectedAgentId);
+		const asksForApproval =
+			askBeforeToolCalls &&
+			canAskBeforeToolCalls(
+				agentsByRepoId[selectedRepo.id]?.find(
+					agent => agent.id === selectedAgentId,
+				),
+				isSimulatorMode,
+			);
 
 		const runId = randomRunId();
 		const modelDisplay = runModelChoiceToDisplay(
@@ -1157,12 +1483,14 @@ function App() {
 			modelDisplay,
 			agentModelOptions,
 		).shortName.trim();
-		delete thinkingMessageIdByAgentReference.current[selectedAgentId];
-		setRunningAgentIds(previous =>
-			previous.includes(selectedAgentId)
-				? previous
-				: [...previous, selectedAgentId],
-		);
+		if (!isAgentBusy) {
+			delete thinkingMessageIdByAgentReference.current[selectedAgentId];
+			setRunningAgentIds(previous =>
+				previous.includes(selectedAgentId)
+					? previous
+					: [...previous, selectedAgentId],
+			);
+		}
 		appendAgentMessage(selectedAgentId, {
 			role: 'user',
 			text: trimmedPrompt,
@@ -1174,19 +1502,27 @@ function App() {
 				prompt: trimmedPrompt,
 				agentId: selectedAgentId,
 				runId,
-				forceApprove: true,
-				simulateMode: isSimulatorMode,
-				model: selectedAgentRunModel || null,
+				options: {
+					forceApprove: !asksForApproval,
+					simulateMode: isSimulatorMode,
+					resume: true,
+					model: selectedAgentRunModel || null,
+				},
 			});
+			if (isAgentBusy) {
+				toast.success('Prompt queued. It will run when the agent finishes.');
+			}
 		} catch (error) {
-			finalizeThinkingMessage(selectedAgentId, true);
 			appendAgentMessage(selectedAgentId, {
 				role: 'error',
 				text: String(error),
 			});
-			setRunningAgentIds(previous =>
-				previous.filter(id => id !== selectedAgentId),
-			);
+			if (!isAgentBusy) {
+				finalizeThinkingMessage(selectedAgentId, true);
+				setRunningAgentIds(previous =>
+					previous.filter(id => id !== selectedAgentId),
+				);
+			}
 		}
 	}, [
 		selectedRepo,
@@ -1195,6 +1531,8 @@ function App() {
 		agentRunModelById,
 		agentModelOptions,
 		isSimulatorMode,
+		askBeforeToolCalls,
+		agentsByRepoId,
 		runningAgentIds,
 		appendAgentMessage,
 		finalizeThinkingMessage,
@@ -1283,6 +1621,34 @@ function App() {
 		}
 	}, [selectedAgentId]);
 
+	const approveToolCall = useCallback(
+		async (callId: string) => {
+			if (!selectedAgentId) return;
+			try {
+				await invoke('approve_tool_call', {agentId: selectedAgentId, callId});
+			} catch (error) {
+				toast.error(String(error));
+			}
+		},
+		[selectedAgentId],
+	);
+
+	const denyToolCall = useCallback(
+		async (callId: string) => {
+			if (!selectedAgentId) return;
+			try {
+				await invoke('deny_tool_call', {
+					agentId: selectedAgentId,
+					callId,
+					reason: null,
+				});
+			} catch (error) {
+				toast.error(String(error));
+			}
+		},
+		[selectedAgentId],
+	);
+
 	const clearSelectedAgentChat = useCallback(() => {
 		if (!selectedAgentId) return;
 		setAgentMessagesById(previous => ({
@@ -1301,7 +1667,10 @@ function App() {
 	async function handleHostLoginSubmit(event: FormEvent) {
 		event.preventDefault();
 		setHostAuthState('checking');
-		const authenticated = await authenticateHostToken(hostAuthTokenInput);
+		const input = hostAuthTokenInput.trim();
+		const authenticated = PAIRING_CODE_PATTERN.test(input)
+			? await pairThisDevice(input)
+			: await authenticateHostToken(input);
 		if (!authenticated) {
 			setHostAuthState('unauthorized');
 		}
@@ -1318,6 +1687,9 @@ function App() {
 	const selectedAgentMessages = selectedAgentId
 		? (agentMessagesById[selectedAgentId] ?? [])
 		: [];
+	const selectedAgentPendingApprovals = selectedAgentId
+		? (pendingApprovalsByAgentId[selectedAgentId] ?? [])
+		: [];
 	const selectedAgentLogs = selectedAgentId
 		? (agentLogsById[selectedAgentId] ?? [])
 		: [];
@@ -1334,12 +1706,14 @@ function App() {
 				<div className="w-full max-w-sm rounded-lg border bg-card p-5 shadow-sm">
 					<h1 className="text-lg font-semibold">Symphony Access</h1>
 					<p className="mt-1 text-sm text-muted-foreground">
-						Enter the host access token to open Symphony.
+						Enter the pairing code shown on the host, or an access token.
 					</p>
 					<form className="mt-4 space-y-3" onSubmit={handleHostLoginSubmit}>
 						<Input
-							type="password"
-							placeholder="Host access token"
+							type="text"
+							autoComplete="off"
+							spellCheck={false}
+							placeholder="Pairing code or access token"
 							value={hostAuthTokenInput}
 							onChange={event => setHostAuthTokenInput(event.target.value)}
 							disabled={hostAuthState === 'checking'}
@@ -1355,7 +1729,7 @@ function App() {
 								hostAuthState === 'checking' || !hostAuthTokenInput.trim()
 							}
 						>
-							{hostAuthState === 'checking' ? 'Verifying...' : 'Unlock'}
+							{hostAuthState === 'checking' ? 'Verifying...' : 'Connect'}
 						</Button>
 					</form>
 				</div>
@@ -1422,6 +1796,13 @@ function App() {
 						hostLanAccessEnabled={hostLanAccessEnabled}
 						isHostLanAccessLoading={isHostLanAccessLoading}
 						onHostLanAccessChange={enabled => void updateHostLanAccess(enabled)}
+						pairedDevices={isTauriRuntime ? pairedDevices : null}
+						devicePairing={devicePairing}
+						isDevicePairingLoading={isDevicePairingLoading}
+						revokingDeviceId={revokingDeviceId}
+						onStartDevicePairing={() => void startDevicePairing()}
+						onCancelDevicePairing={() => void cancelDevicePairing()}
+						onRevokePairedDevice={device => void revokePairedDevice(device)}
 						simulatorMode={isSimulatorMode}
 						onSimulatorModeChange={setIsSimulatorMode}
 						rawLogs={showRawLogs}
@@ -1531,6 +1912,15 @@ function App() {
 									logs={selectedAgentLogs}
 									isRunning={selectedAgentIsRunning}
 									showRawLogs={showRawLogs}
+									pendingApprovals={selectedAgentPendingApprovals}
+									askBeforeToolCalls={askBeforeToolCalls}
+									canAskBeforeToolCalls={canAskBeforeToolCalls(
+										selectedAgent ?? undefined,
+										isSimulatorMode,
+									)}
+									onAskBeforeToolCallsChange={setAskBeforeToolCalls}
+									onApproveToolCall={callId => void approveToolCall(callId)}
+									onDenyToolCall={callId => void denyToolCall(callId)}
 									onOpenEditedFile={path => void openAgentMessageFile(path)}
 									onPromptChange={setAgentPrompt}
 									onModelInputChange={raw => {
@@ -6,6 +6,7 @@ import {Separator} from '@/components/ui/separator';
 import {resolveRunModelFromInput} from '@/lib/agent-model';
 import type {
 	Agent,
+	AgentApprovalRequest,
 	AgentConversationEntry,
 	AgentModelOption,
 } from '@/lib/types';
@@ -20,6 +21,7 @@ import {
 	LoaderCircle,
 	MessageSquareText,
 	Play,
+	ShieldAlert,
 	Square,
 	TerminalSquare,
 	Trash2,
@@ -52,6 +54,12 @@ type RepoAgentsViewProperties = {
 	logs: string[];
 	isRunning: boolean;
 	showRawLogs: boolean;
+	pendingApprovals: AgentApprovalRequest[];
+	askBeforeToolCalls: boolean;
+	canAskBeforeToolCalls: boolean;
+	onAskBeforeToolCallsChange: (ask: boolean) => void;
+	onApproveToolCall: (callId: string) => void;
+	onDenyToolCall: (callId: string) => void;
 	onOpenEditedFile?: (path: string) => void;
 	onPromptChange: (prompt: string) => void;
 	onModelInputChange: (model: string) => void;
@@ -306,6 +314,70 @@ function MessageContent({text}: {text: string}) {
 	);
 }
 
+/** Names the tool (`shellToolCall` -> `shell`) and its most telling argument. */
+function describeToolCall(toolCall: Record<string, unknown>) {
+	const [key, value] = Object.entries(toolCall)[0] ?? ['tool', undefined];
+	const tool = key.replace(/ToolCall$/, '');
+	const arguments_ = (value as {args?: Record<string, unknown>} | undefined)
+		?.args;
+	const detail =
+		arguments_?.command ?? arguments_?.path ?? JSON.stringify(arguments_ ?? {});
+	return {tool, detail: String(detail)};
+}
+
+function ToolCallApprovals({
+	approvals,
+	onApprove,
+	onDeny,
+}: {
+	approvals: AgentApprovalRequest[];
+	onApprove: (callId: string) => void;
+	onDeny: (callId: string) => void;
+}) {
+	return (
+		<div className="space-y-2">
+			{approvals.map(approval => {
+				const {tool, detail} = describeToolCall(approval.toolCall);
+				return (
+					<div
+						key={approval.callId}
+						className="flex flex-wrap items-center gap-2 rounded-lg border border-amber-500/40 bg-amber-500/10 px-3 py-2"
+					>
+						<ShieldAlert className="size-4 shrink-0 text-amber-600" />
+						<div className="min-w-0 flex-1">
+							<p className="text-xs font-medium">
+								The agent wants to use {tool}
+							</p>
+							<p className="truncate font-mono text-xs text-muted-foreground">
+								{detail}
+							</p>
+						</div>
+						<div className="flex items-center gap-2">
+							<Button
+								variant="outline"
+								size="sm"
+								onClick={() => {
+									onDeny(approval.callId);
+								}}
+							>
+								Deny
+							</Button>
+							<Button
+								size="sm"
+								onClick={() => {
+									onApprove(approval.callId);
+								}}
+							>
+								Approve
+							</Button>
+						</div>
+					</div>
+				);
+			})}
+		</div>
+	);
+}
+
 export function RepoAgentsView({
 	selectedAgent,
 	model,
@@ -316,6 +388,12 @@ export function RepoAgentsView({
 	logs,
 	isRunning,
 	showRawLogs,
+	pendingApprovals,
+	askBeforeToolCalls,
+	canAskBeforeToolCalls,
+	onAskBeforeToolCallsChange,
+	onApproveToolCall,
+	onDenyToolCall,
 	onOpenEditedFile,
 	onPromptChange,
 	onModelInputChange,
@@ -856,6 +934,13 @@ export function RepoAgentsView({
 									</PopoverContent>
 								</Popover>
 							</div>
+							{pendingApprovals.length > 0 && (
+								<ToolCallApprovals
+									approvals={pendingApprovals}
+									onApprove={onApproveToolCall}
+									onDeny={onDenyToolCall}
+								/>
+							)}
 							<textarea
 								placeholder="Enter prompt for selected agent"
 								value={prompt}
@@ -878,9 +963,24 @@ export function RepoAgentsView({
 								)}
 							/>
 							<div className="flex flex-wrap items-center justify-between gap-2">
-								<p className="text-[11px] text-muted-foreground">
-									Enter to run, Shift+Enter for a new line.
-								</p>
+								<div className="flex flex-wrap items-center gap-3">
+									<p className="text-[11px] text-muted-foreground">
+										Enter to run, Shift+Enter for a new line.
+									</p>
+									{canAskBeforeToolCalls && (
+										<label className="flex items-center gap-1.5 text-[11px] text-muted-foreground">
+											<input
+												type="checkbox"
+												checked={askBeforeToolCalls}
+												disabled={isRunning}
+												onChange={event => {
+													onAskBeforeToolCallsChange(event.target.checked);
+												}}
+											/>
+											Ask before tool calls
+										</label>
+									)}
+								</div>
 								<div className="flex items-center gap-2">
 									<Button
 										variant="outline"
@@ -1941,7 +1941,8 @@ export function RepoSidebar({
 					<DialogHeader>
 						<DialogTitle>Server URL</DialogTitle>
 						<DialogDescription>
-							Use this URL to open Symphony from other devices on your network.
+							Use this URL to open Symphony from other devices on your network,
+							then enter a pairing code from Settings.
 						</DialogDescription>
 					</DialogHeader>
 					<div className="relative py-2">
@@ -1,3 +1,14 @@
+import {Button} from '@/components/ui/button';
+import type {DevicePairing, PairedDevice} from '@/lib/types';
+
+/** Formats SQLite (`YYYY-MM-DD HH:MM:SS`, UTC) and RFC 3339 times. */
+function formatDeviceTime(value: string) {
+	const date = new Date(
+		value.includes('T') ? value : `${value.replace(' ', 'T')}Z`,
+	);
+	return Number.isNaN(date.getTime()) ? value : date.toLocaleString();
+}
+
 type SettingsViewProperties = {
 	version: string | null;
 	isVersionLoading: boolean;
@@ -5,6 +16,14 @@ type SettingsViewProperties = {
 	hostLanAccessEnabled: boolean;
 	isHostLanAccessLoading: boolean;
 	onHostLanAccessChange: (enabled: boolean) => void;
+	/** `null` where devices cannot be managed, i.e. outside the desktop app. */
+	pairedDevices: PairedDevice[] | null;
+	devicePairing: DevicePairing | null;
+	isDevicePairingLoading: boolean;
+	revokingDeviceId: number | null;
+	onStartDevicePairing: () => void;
+	onCancelDevicePairing: () => void;
+	onRevokePairedDevice: (device: PairedDevice) => void;
 	simulatorMode: boolean;
 	onSimulatorModeChange: (enabled: boolean) => void;
 	rawLogs: boolean;
@@ -18,6 +37,13 @@ export function SettingsView({
 	hostLanAccessEnabled,
 	isHostLanAccessLoading,
 	onHostLanAccessChange,
+	pairedDevices,
+	devicePairing,
+	isDevicePairingLoading,
+	revokingDeviceId,
+	onStartDevicePairing,
+	onCancelDevicePairing,
+	onRevokePairedDevice,
 	simulatorMode,
 	onSimulatorModeChange,
 	rawLogs,
@@ -80,6 +106,98 @@ export function SettingsView({
 						</button>
 					</div>
 				</div>
+				{pairedDevices && (
+					<div className="mt-4 max-w-xl rounded-lg border bg-card p-4">
+						<p className="text-xs font-medium tracking-wide text-muted-foreground uppercase">
+							Paired devices
+						</p>
+						<div className="mt-3 flex items-center justify-between gap-3">
+							<div>
+								<p className="text-sm font-medium">Pair a device</p>
+								<p className="text-xs text-muted-foreground">
+									Show a one-time code to sign in a phone or browser.
+								</p>
+							</div>
+							<Button
+								size="sm"
+								variant="outline"
+								onClick={onStartDevicePairing}
+								disabled={isDevicePairingLoading}
+							>
+								{devicePairing ? 'New code' : 'Pair'}
+							</Button>
+						</div>
+						{devicePairing && (
+							<div className="mt-3 flex items-center gap-4 rounded-md border p-3">
+								<img
+									src={`data:image/svg+xml;utf8,${encodeURIComponent(devicePairing.qrSvg)}`}
+									alt="Pairing QR code"
+									className="size-32 shrink-0 rounded bg-white"
+								/>
+								<div className="min-w-0 space-y-1">
+									<p className="font-mono text-2xl font-semibold tracking-widest">
+										{devicePairing.code}
+									</p>
+									<p className="text-xs text-muted-foreground">
+										Scan the code, or open{' '}
+										<span className="break-all">
+											{devicePairing.pairingUrl.split('?')[0]}
+										</span>{' '}
+										and enter it. Expires{' '}
+										{formatDeviceTime(devicePairing.expiresAt)}.
+									</p>
+									{!hostLanAccessEnabled && (
+										<p className="text-xs text-destructive">
+											Allow mobile/LAN access for other devices to connect.
+										</p>
+									)}
+									<Button
+										size="xs"
+										variant="ghost"
+										onClick={onCancelDevicePairing}
+									>
+										Cancel
+									</Button>
+								</div>
+							</div>
+						)}
+						{pairedDevices.length === 0 ? (
+							<p className="mt-3 text-xs text-muted-foreground">
+								No devices are paired.
+							</p>
+						) : (
+							<ul className="mt-3 divide-y">
+								{pairedDevices.map(device => (
+									<li
+										key={device.id}
+										className="flex items-center justify-between gap-3 py-2"
+									>
+										<div className="min-w-0">
+											<p className="truncate text-sm font-medium">
+												{device.name}
+											</p>
+											<p className="text-xs text-muted-foreground">
+												Paired {formatDeviceTime(device.createdAt)}
+												{' · '}
+												{device.lastUsedAt
+													? `Last used ${formatDeviceTime(device.lastUsedAt)}`
+													: 'Never used'}
+											</p>
+										</div>
+										<Button
+											size="sm"
+											variant="destructive"
+											onClick={() => onRevokePairedDevice(device)}
+											disabled={revokingDeviceId === device.id}
+										>
+											Revoke
+										</Button>
+									</li>
+								))}
+							</ul>
+						)}
+					</div>
+				)}
 				<div className="mt-4 max-w-xl rounded-lg border bg-card p-4">
 					<p className="text-xs font-medium tracking-wide text-muted-foreground uppercase">
 						Development settings
@@ -34,10 +34,14 @@ const resolvedHostBaseUrl =
 const WEB_AUTH_TOKEN_STORAGE_KEY = 'symphony:web-auth-token';
 
 const listenersByEvent = new Map<string, Set<EventListener<unknown>>>();
+const EVENT_STREAM_RETRY_DELAY_MS = 2000;
+
 let eventSource: EventSource | undefined;
-let lastKnownEventUrl = '';
+// The token the current stream was opened with, set while it is being opened too.
+let eventSourceToken = '';
+let lastEventId = '';
 let webAuthTokenCache: string | undefined;
-let isMessageHandlerAttached = false;
+const attachedEventNames = new Set<string>();
 
 function loadStoredWebAuthToken() {
 	if (isTauriRuntime) return undefined;
@@ -62,43 +66,119 @@ function getRequiredWebAuthToken(): string {
 	return webAuthTokenCache;
 }
 
+type StreamTicketResponse = {
+	ok: boolean;
+	ticket?: string;
+};
+
+// EventSource cannot send headers, and URLs end up in logs, so streams are
+// opened with a one-time ticket instead of the token.
+async function requestStreamTicket(token: string): Promise<string> {
+	const response = await fetch(`${resolvedHostBaseUrl}/api/events/ticket`, {
+		method: 'POST',
+		headers: {
+			authorization: `Bearer ${token}`,
+			'x-symphony-token': token,
+		},
+	});
+	const payload = (await response
+		.json()
+		.catch(() => ({ok: false}))) as StreamTicketResponse;
+	if (!payload.ok || !payload.ticket) {
+		throw new Error(
+			`Event stream ticket request failed: HTTP ${response.status}`,
+		);
+	}
+	return payload.ticket;
+}
+
 function ensureEventSource() {
 	const token = getRequiredWebAuthToken();
-	const nextEventUrl = `${resolvedHostBaseUrl}/api/events?token=${encodeURIComponent(token)}`;
-	if (eventSource && lastKnownEventUrl === nextEventUrl) return;
-	if (eventSource) eventSource.close();
+	if (eventSourceToken === token) return;
+	closeEventSource();
+	eventSourceToken = token;
+	void openEventSource(token);
+}
 
-	lastKnownEventUrl = nextEventUrl;
-	eventSource = new EventSource(nextEventUrl);
-	isMessageHandlerAttached = false;
+function closeEventSource() {
+	eventSource?.close();
+	eventSource = undefined;
+	eventSourceToken = '';
+	attachedEventNames.clear();
+}
 
-	eventSource.addEventListener('error', error => {
+async function openEventSource(token: string) {
+	let ticket: string;
+	try {
+		ticket = await requestStreamTicket(token);
+	} catch (error) {
 		console.error('Host event stream error', error);
+		retryEventSource(token);
+		return;
+	}
+	// The token changed while the ticket was requested.
+	if (eventSourceToken !== token) return;
+
+	const eventUrl = `${resolvedHostBaseUrl}/api/events?ticket=${encodeURIComponent(ticket)}`;
+	// A new stream has to ask for the last event id explicitly.
+	const source = new EventSource(
+		lastEventId
+			? `${eventUrl}&lastEventId=${encodeURIComponent(lastEventId)}`
+			: eventUrl,
+	);
+	eventSource = source;
+	attachedEventNames.clear();
+
+	source.addEventListener('error', error => {
+		console.error('Host event stream error', error);
+		// Tickets work once, so the browser's own reconnect is refused and the
+		// stream is reopened with a new ticket.
+		if (source.readyState === EventSource.CLOSED && eventSource === source) {
+			eventSource = undefined;
+			retryEventSource(token);
+		}
 	});
 
-	attachMessageHandler();
+	for (const eventName of listenersByEvent.keys()) {
+		attachEventHandler(eventName);
+	}
 }
 
-function attachMessageHandler() {
-	if (!eventSource || isMessageHandlerAttached) return;
-	eventSource.addEventListener('message', (message: MessageEvent<string>) => {
-		let payload: unknown;
-		try {
-			payload = JSON.parse(message.data);
-		} catch {
-			return;
-		}
-		if (!payload || typeof payload !== 'object') return;
-		const envelope = payload as {event?: string; payload?: unknown};
-		const eventName = envelope.event;
-		if (!eventName) return;
-		const subscribers = listenersByEvent.get(eventName);
-		if (!subscribers || subscribers.size === 0) return;
-		for (const listener of subscribers) {
-			listener({payload: envelope.payload});
+function retryEventSource(token: string) {
+	setTimeout(() => {
+		if (eventSourceToken === token && !eventSource) {
+			void openEventSource(token);
 		}
+	}, EVENT_STREAM_RETRY_DELAY_MS);
+}
+
+function handleHostEvent(message: MessageEvent<string>) {
+	if (message.lastEventId) lastEventId = message.lastEventId;
+	let payload: unknown;
+	try {
+		payload = JSON.parse(message.data);
+	} catch {
+		return;
+	}
+	if (!payload || typeof payload !== 'object') return;
+	const envelope = payload as {event?: string; payload?: unknown};
+	const eventName = envelope.event;
+	if (!eventName) return;
+	const subscribers = listenersByEvent.get(eventName);
+	if (!subscribers || subscribers.size === 0) return;
+	for (const listener of subscribers) {
+		listener({payload: envelope.payload});
+	}
+}
+
+// Host events are sent with their name as the SSE event type, so each name
+// needs its own handler.
+function attachEventHandler(eventName: string) {
+	if (!eventSource || attachedEventNames.has(eventName)) return;
+	eventSource.addEventListener(eventName, event => {
+		handleHostEvent(event as MessageEvent<string>);
 	});
-	isMessageHandlerAttached = true;
+	attachedEventNames.add(eventName);
 }
 
 function registerBrowserEventListener<T>(
@@ -111,7 +191,7 @@ function registerBrowserEventListener<T>(
 		listeners = new Set();
 		listenersByEvent.set(eventName, listeners);
 	}
-	attachMessageHandler();
+	attachEventHandler(eventName);
 	listeners.add(handler as EventListener<unknown>);
 
 	return () => {
@@ -187,12 +267,7 @@ export function getWebAuthToken() {
 export function setWebAuthToken(token: string | undefined) {
 	if (isTauriRuntime) return;
 	webAuthTokenCache = token?.trim() || undefined;
-	if (eventSource) {
-		eventSource.close();
-		eventSource = undefined;
-		lastKnownEventUrl = '';
-		isMessageHandlerAttached = false;
-	}
+	closeEventSource();
 	try {
 		if (webAuthTokenCache) {
 			localStorage.setItem(WEB_AUTH_TOKEN_STORAGE_KEY, webAuthTokenCache);
@@ -221,6 +296,33 @@ export async function verifyWebAuthToken(token: string): Promise<boolean> {
 	}
 }
 
+type PairDeviceResponse = {
+	ok: boolean;
+	token?: string;
+	error?: string;
+};
+
+/** Exchanges a pairing code shown by the host for this device's own token. */
+export async function pairWithHost(
+	code: string,
+	deviceName: string,
+): Promise<string> {
+	const response = await fetch(`${resolvedHostBaseUrl}/api/auth/pair`, {
+		method: 'POST',
+		headers: {'content-type': 'application/json'},
+		body: JSON.stringify({code: code.trim(), deviceName}),
+	});
+	const payload = (await response
+		.json()
+		.catch(() => ({ok: false}))) as PairDeviceResponse;
+	if (!payload.ok || !payload.token) {
+		throw new Error(
+			payload.error || `Pairing failed: HTTP ${response.status}`,
+		);
+	}
+	return payload.token;
+}
+
 export async function openUrl(url: string): Promise<void> {
 	if (isTauriRuntime) {
 		await tauriOpenUrl(url);
@@ -45,9 +45,20 @@ export type Agent = {
 	id: number;
 	repo_id: number;
 	name: string;
+	backend: string;
+	/** Whether runs can pause to ask before tool calls */
+	supports_tool_approval: boolean;
 	created_at: string;
 };
 
+/** A tool call the agent is waiting on, from `repo-agent-approval-request` */
+export type AgentApprovalRequest = {
+	runId: string;
+	agentId: number;
+	callId: string;
+	toolCall: Record<string, unknown>;
+};
+
 /** Short id for `--model` and human-readable label from the agent CLI */
 export type AgentModelOption = {
 	id: string;
@@ -66,3 +77,20 @@ export type AgentConversationEntry = {
 	kind?: 'thinking';
 	isPending?: boolean;
 };
+
+/** A device token issued by pairing with the host */
+export type PairedDevice = {
+	id: number;
+	name: string;
+	scopes: string[];
+	createdAt: string;
+	lastUsedAt: string | null;
+};
+
+/** A pending pairing code shown on the host */
+export type DevicePairing = {
+	code: string;
+	pairingUrl: string;
+	qrSvg: string;
+	expiresAt: string;
+};